@@ -0,0 +1,313 @@
+// 客户和账户
+
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// DocumentKind 证件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DocumentKind {
+    IdCard,
+    Passport,
+}
+
+/// IdentityDocument 身份证件, 证件类型和号码唯一确定一个客户
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IdentityDocument {
+    pub kind: DocumentKind,
+    pub number: String,
+}
+
+impl IdentityDocument {
+    pub fn new(kind: DocumentKind, number: String) -> Self {
+        Self { kind, number }
+    }
+}
+
+impl fmt::Display for IdentityDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {}", self.kind, self.number)
+    }
+}
+
+/// Customer 客户
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Customer {
+    pub(crate) id: usize,
+    pub(crate) name: String,
+    pub(crate) date_of_birth: NaiveDate,
+    pub(crate) address: String,
+    pub(crate) document: IdentityDocument,
+    // 客户拥有(或共同拥有)的账户ID
+    pub(crate) accounts: Vec<usize>,
+}
+
+impl Customer {
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_date_of_birth(&self) -> NaiveDate {
+        self.date_of_birth
+    }
+
+    pub fn get_address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn get_document(&self) -> &IdentityDocument {
+        &self.document
+    }
+
+    pub fn get_accounts(&self) -> &[usize] {
+        &self.accounts
+    }
+}
+
+/// AccountKind 账户类型
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountKind {
+    // 支票账户, 允许透支到 overdraft_limit
+    Checking {
+        overdraft_limit: Money,
+    },
+    // 储蓄账户, 按年利率每日复利计息, 每月取款(含转出)次数有上限
+    Savings {
+        annual_rate: Decimal,
+        monthly_withdrawal_cap: u32,
+    },
+}
+
+// 第一个账号的序号, 账号 = 序号 + 1位校验位, 共8位
+const FIRST_ACCOUNT_SEQUENCE: u64 = 1_000_000;
+
+/// AccountIdAllocator 账号分配器, 账号末位是 Luhn 校验位, 序号只增不减, 销户后也不会复用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountIdAllocator {
+    next_sequence: u64,
+}
+
+impl AccountIdAllocator {
+    pub fn new() -> Self {
+        Self {
+            next_sequence: FIRST_ACCOUNT_SEQUENCE,
+        }
+    }
+
+    pub fn allocate(&mut self) -> usize {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        (sequence * 10 + luhn_check_digit(sequence)) as usize
+    }
+
+    // 校验账号的校验位, 输错一位或相邻两位颠倒的账号都会被发现
+    pub fn is_valid(account_id: usize) -> bool {
+        let account_id = account_id as u64;
+        account_id >= 10 && luhn_check_digit(account_id / 10) == account_id % 10
+    }
+}
+
+impl Default for AccountIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Luhn 校验位: 从右往左, 奇数位乘2(大于9减9), 求和后补足到10的倍数
+fn luhn_check_digit(payload: u64) -> u64 {
+    let mut sum = 0;
+    let mut rest = payload;
+    let mut double = true;
+    while rest > 0 {
+        let mut digit = rest % 10;
+        if double {
+            digit *= 2;
+            if digit > 9 {
+                digit -= 9;
+            }
+        }
+        sum += digit;
+        double = !double;
+        rest /= 10;
+    }
+    (10 - sum % 10) % 10
+}
+
+/// AccountStatus 账户状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountStatus {
+    Active,
+    // 冻结, 不能存取款和转账, 可以解冻
+    Frozen,
+    // 睡眠户, 长期没有客户发起的交易, 可以入账, 柜员核实身份激活后才能出账
+    Dormant,
+    // 已销户, 账号不再复用
+    Closed,
+}
+
+/// BankAccount 银行账户
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BankAccount {
+    // 账户ID
+    pub(crate) account_id: usize,
+    // 账户持有人(客户ID), 联名账户有多个持有人
+    pub(crate) owners: Vec<usize>,
+    // 开户分行ID
+    pub(crate) branch_id: usize,
+    // 账户币种, 余额记录在总账的 LedgerAccount::Customer 科目
+    pub(crate) currency: Currency,
+    // 账户类型
+    pub(crate) kind: AccountKind,
+    pub(crate) status: AccountStatus,
+    // 最近一次客户发起交易(开户、存取款、转账、激活)的日期
+    pub(crate) last_activity: NaiveDate,
+    // 利息已经计算到的日期(开户日起算)
+    pub(crate) interest_accrued_to: NaiveDate,
+    // 当前统计的月份(年, 月)和该月已取款次数
+    withdrawal_month: (i32, u32),
+    withdrawal_count: u32,
+    // 最近一次取款的日期, 以及当天和当月累计取款(含转出)的金额
+    withdrawal_day: NaiveDate,
+    withdrawn_today: Money,
+    withdrawn_this_month: Money,
+    // 最近一次收取账户管理费的月份(年, 月)
+    #[serde(default)]
+    pub(crate) maintenance_fee_month: Option<(i32, u32)>,
+}
+
+impl BankAccount {
+    pub fn new(
+        account_id: usize,
+        owners: Vec<usize>,
+        branch_id: usize,
+        currency: Currency,
+        kind: AccountKind,
+        opened_on: NaiveDate,
+    ) -> Self {
+        Self {
+            account_id,
+            owners,
+            branch_id,
+            currency,
+            kind,
+            status: AccountStatus::Active,
+            last_activity: opened_on,
+            interest_accrued_to: opened_on,
+            withdrawal_month: (opened_on.year(), opened_on.month()),
+            withdrawal_count: 0,
+            withdrawal_day: opened_on,
+            withdrawn_today: Money::zero(currency),
+            withdrawn_this_month: Money::zero(currency),
+            maintenance_fee_month: None,
+        }
+    }
+
+    pub fn get_account_id(&self) -> usize {
+        self.account_id
+    }
+
+    pub fn get_owners(&self) -> &[usize] {
+        &self.owners
+    }
+
+    pub fn get_branch_id(&self) -> usize {
+        self.branch_id
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn get_kind(&self) -> &AccountKind {
+        &self.kind
+    }
+
+    pub fn get_status(&self) -> AccountStatus {
+        self.status
+    }
+
+    pub fn get_last_activity(&self) -> NaiveDate {
+        self.last_activity
+    }
+
+    // 某天所在月份已经取款的次数
+    pub fn withdrawals_in_month(&self, date: NaiveDate) -> u32 {
+        if self.withdrawal_month == (date.year(), date.month()) {
+            self.withdrawal_count
+        } else {
+            0
+        }
+    }
+
+    // 某天已经取款的总额
+    pub fn withdrawn_on(&self, date: NaiveDate) -> Money {
+        if self.withdrawal_day == date {
+            self.withdrawn_today
+        } else {
+            Money::zero(self.currency)
+        }
+    }
+
+    // 某天所在月份已经取款的总额
+    pub fn withdrawn_in_month(&self, date: NaiveDate) -> Money {
+        if self.withdrawal_month == (date.year(), date.month()) {
+            self.withdrawn_this_month
+        } else {
+            Money::zero(self.currency)
+        }
+    }
+
+    pub(crate) fn record_withdrawal(
+        &mut self,
+        date: NaiveDate,
+        amount: Money,
+    ) -> Result<(), MoneyError> {
+        self.withdrawn_today = self.withdrawn_on(date).checked_add(amount)?;
+        self.withdrawn_this_month = self.withdrawn_in_month(date).checked_add(amount)?;
+        self.withdrawal_count = self.withdrawals_in_month(date) + 1;
+        self.withdrawal_month = (date.year(), date.month());
+        self.withdrawal_day = date;
+        Ok(())
+    }
+
+    // 撤销 date 当天记下的一笔取款, 当天或当月已经过去的不用恢复
+    pub(crate) fn undo_withdrawal(
+        &mut self,
+        date: NaiveDate,
+        amount: Money,
+    ) -> Result<(), MoneyError> {
+        if self.withdrawal_day == date {
+            self.withdrawn_today = self.withdrawn_today.checked_sub(amount)?;
+        }
+        if self.withdrawal_month == (date.year(), date.month()) {
+            self.withdrawn_this_month = self.withdrawn_this_month.checked_sub(amount)?;
+            self.withdrawal_count = self.withdrawal_count.saturating_sub(1);
+        }
+        Ok(())
+    }
+}
+
+/// LimitPeriod 限额的统计周期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitPeriod {
+    Daily,
+    Monthly,
+}
+
+impl fmt::Display for LimitPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Daily => write!(f, "daily"),
+            Self::Monthly => write!(f, "monthly"),
+        }
+    }
+}
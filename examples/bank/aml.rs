@@ -0,0 +1,360 @@
+// 交易监听和反洗钱监控
+
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+use super::*;
+
+/// TransactionListener 订阅 BankSystem 的交易, 交易所在的操作写入日志后收到通知, 撤销的交易不通知
+/// 通知时持有系统锁, 监听者不能再去锁 BankSystem
+pub trait TransactionListener: Send {
+    fn on_transaction(&mut self, transaction: &dyn TransactionDescription, system: &BankSystem);
+}
+
+/// AmlConfig 反洗钱监控参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmlConfig {
+    // 需要申报的大额现金门槛
+    pub reporting_threshold: Money,
+    // 略低于门槛的范围, 0.1 表示门槛的 90% 到 100% 之间
+    pub structuring_margin: Decimal,
+    // 窗口内达到这么多笔略低于门槛的现金存款视为拆分交易
+    pub structuring_count: usize,
+    // 快进快出: 窗口内转入不少于 rapid_movement_min, 且转出达到转入的 rapid_movement_ratio
+    pub rapid_movement_min: Money,
+    pub rapid_movement_ratio: Decimal,
+    // 窗口内在开户行以外这么多家不同的分行办理业务视为异常
+    pub cross_branch_count: usize,
+    // 统计窗口
+    pub window: chrono::Duration,
+}
+
+impl AmlConfig {
+    pub fn new(reporting_threshold: Money) -> Self {
+        Self {
+            reporting_threshold,
+            structuring_margin: Decimal::new(1, 1),
+            structuring_count: 3,
+            rapid_movement_min: reporting_threshold,
+            rapid_movement_ratio: Decimal::new(9, 1),
+            cross_branch_count: 3,
+            window: chrono::Duration::days(7),
+        }
+    }
+}
+
+/// AlertKind 预警类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    // 拆分交易: 多笔略低于申报门槛的现金存款
+    Structuring,
+    // 快进快出: 资金转入后很快转出
+    RapidMovement,
+    // 短时间内在多家非开户分行办理业务
+    CrossBranch,
+}
+
+/// AlertStatus 预警状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertStatus {
+    Open,
+    // 复核后排除嫌疑
+    Dismissed,
+    // 复核后上报
+    Escalated,
+}
+
+/// AmlAlert 反洗钱预警, 等待柜员复核
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmlAlert {
+    id: usize,
+    kind: AlertKind,
+    account_id: usize,
+    raised_at: DateTime<Utc>,
+    // 触发预警的交易
+    transaction_ids: Vec<u64>,
+    detail: String,
+    status: AlertStatus,
+    reviewed_by: Option<usize>,
+    note: String,
+}
+
+impl AmlAlert {
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_kind(&self) -> AlertKind {
+        self.kind
+    }
+
+    pub fn get_account_id(&self) -> usize {
+        self.account_id
+    }
+
+    pub fn get_raised_at(&self) -> DateTime<Utc> {
+        self.raised_at
+    }
+
+    pub fn get_transaction_ids(&self) -> &[u64] {
+        &self.transaction_ids
+    }
+
+    pub fn get_detail(&self) -> &str {
+        &self.detail
+    }
+
+    pub fn get_status(&self) -> AlertStatus {
+        self.status
+    }
+
+    pub fn get_reviewed_by(&self) -> Option<usize> {
+        self.reviewed_by
+    }
+
+    pub fn get_note(&self) -> &str {
+        &self.note
+    }
+}
+
+// 监控窗口内账户的一笔资金变动
+struct AccountActivity {
+    transaction_id: u64,
+    timestamp: DateTime<Utc>,
+    // 对账户余额的影响(已折成门槛币种), 正数为转入
+    amount: Decimal,
+    // 是否是现金存款
+    cash_deposit: bool,
+    // 账户持有人主动办理时的分行, 被动转入时为空
+    branch_id: Option<usize>,
+}
+
+/// AmlMonitor 反洗钱监控, 订阅交易流, 按账户统计窗口内的资金变动并生成预警队列
+/// 同一账户同一类型已有未复核的预警时不重复预警
+pub struct AmlMonitor {
+    config: AmlConfig,
+    activity: HashMap<usize, VecDeque<AccountActivity>>,
+    alerts: Vec<AmlAlert>,
+}
+
+impl AmlMonitor {
+    pub fn new(config: AmlConfig) -> Self {
+        Self {
+            config,
+            activity: HashMap::new(),
+            alerts: Vec::new(),
+        }
+    }
+
+    pub fn get_alerts(&self) -> &[AmlAlert] {
+        &self.alerts
+    }
+
+    // 等待复核的预警, 先产生的在前
+    pub fn pending_alerts(&self) -> Vec<&AmlAlert> {
+        self.alerts
+            .iter()
+            .filter(|a| a.status == AlertStatus::Open)
+            .collect()
+    }
+
+    // 复核后排除嫌疑
+    // 复核人必须在该分行签到且有复核权限, 调用方先锁 BankSystem 再锁监控对象
+    pub fn dismiss_alert(
+        &mut self,
+        system: &mut BankSystem,
+        alert_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        note: String,
+    ) -> Result<(), BankError> {
+        system.authorize(teller_id, branch_id, Operation::ReviewAlerts)?;
+        self.review_alert(alert_id, teller_id, AlertStatus::Dismissed, note)
+    }
+
+    // 复核后上报
+    pub fn escalate_alert(
+        &mut self,
+        system: &mut BankSystem,
+        alert_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        note: String,
+    ) -> Result<(), BankError> {
+        system.authorize(teller_id, branch_id, Operation::ReviewAlerts)?;
+        self.review_alert(alert_id, teller_id, AlertStatus::Escalated, note)
+    }
+
+    fn review_alert(
+        &mut self,
+        alert_id: usize,
+        teller_id: usize,
+        status: AlertStatus,
+        note: String,
+    ) -> Result<(), BankError> {
+        let alert = self
+            .alerts
+            .iter_mut()
+            .find(|a| a.id == alert_id && a.status == AlertStatus::Open)
+            .ok_or(BankError::AlertNotOpen(alert_id))?;
+        alert.status = status;
+        alert.reviewed_by = Some(teller_id);
+        alert.note = note;
+        Ok(())
+    }
+
+    fn record(&mut self, account_id: usize, activity: AccountActivity, home_branch: usize) {
+        let cutoff = activity.timestamp - self.config.window;
+        let history = self.activity.entry(account_id).or_default();
+        while history.front().is_some_and(|a| a.timestamp < cutoff) {
+            history.pop_front();
+        }
+        let now = activity.timestamp;
+        history.push_back(activity);
+
+        let mut raised = Vec::new();
+        let threshold = self.config.reporting_threshold.amount();
+        let floor = threshold * (Decimal::ONE - self.config.structuring_margin);
+        let structuring: Vec<u64> = history
+            .iter()
+            .filter(|a| a.cash_deposit && a.amount >= floor && a.amount < threshold)
+            .map(|a| a.transaction_id)
+            .collect();
+        if structuring.len() >= self.config.structuring_count {
+            let detail = format!(
+                "{} cash deposits just under {} within {} days",
+                structuring.len(),
+                self.config.reporting_threshold,
+                self.config.window.num_days()
+            );
+            raised.push((AlertKind::Structuring, structuring, detail));
+        }
+
+        let inflow: Decimal = history.iter().map(|a| a.amount.max(Decimal::ZERO)).sum();
+        let outflow: Decimal = history.iter().map(|a| (-a.amount).max(Decimal::ZERO)).sum();
+        let last_is_outflow = history.back().is_some_and(|a| a.amount.is_sign_negative());
+        if last_is_outflow
+            && inflow >= self.config.rapid_movement_min.amount()
+            && outflow >= inflow * self.config.rapid_movement_ratio
+        {
+            let currency = self.config.reporting_threshold.currency();
+            let detail = format!(
+                "{} moved in and {} moved out within {} days",
+                Money::new(inflow, currency),
+                Money::new(outflow, currency),
+                self.config.window.num_days()
+            );
+            let ids = history.iter().map(|a| a.transaction_id).collect();
+            raised.push((AlertKind::RapidMovement, ids, detail));
+        }
+
+        let mut branches: Vec<usize> = history
+            .iter()
+            .filter_map(|a| a.branch_id)
+            .filter(|b| *b != home_branch)
+            .collect();
+        branches.sort_unstable();
+        branches.dedup();
+        if branches.len() >= self.config.cross_branch_count {
+            let detail = format!(
+                "activity at branches {:?} away from home branch {} within {} days",
+                branches,
+                home_branch,
+                self.config.window.num_days()
+            );
+            let ids = history
+                .iter()
+                .filter(|a| a.branch_id.is_some_and(|b| b != home_branch))
+                .map(|a| a.transaction_id)
+                .collect();
+            raised.push((AlertKind::CrossBranch, ids, detail));
+        }
+
+        for (kind, transaction_ids, detail) in raised {
+            self.raise(kind, account_id, now, transaction_ids, detail);
+        }
+    }
+
+    fn raise(
+        &mut self,
+        kind: AlertKind,
+        account_id: usize,
+        raised_at: DateTime<Utc>,
+        transaction_ids: Vec<u64>,
+        detail: String,
+    ) {
+        let open = self
+            .alerts
+            .iter()
+            .any(|a| a.kind == kind && a.account_id == account_id && a.status == AlertStatus::Open);
+        if open {
+            return;
+        }
+        self.alerts.push(AmlAlert {
+            id: self.alerts.len() + 1,
+            kind,
+            account_id,
+            raised_at,
+            transaction_ids,
+            detail,
+            status: AlertStatus::Open,
+            reviewed_by: None,
+            note: String::new(),
+        });
+    }
+}
+
+impl TransactionListener for AmlMonitor {
+    // 只监控客户发起的存取款、转账和货币兑换, 转出和转入账户分别统计
+    // 外币账户的金额按中间价折成门槛币种, 没有牌价时无法折算, 不计入统计
+    // 冲正的原交易从统计窗口中去掉, 冲正本身不计入
+    fn on_transaction(&mut self, ts: &dyn TransactionDescription, system: &BankSystem) {
+        let transaction = ts.get_transaction();
+        let mut accounts = vec![transaction.account_id];
+        if let Some(to) = ts.get_counterparty_account_id() {
+            accounts.push(to);
+        }
+        if let Some(original_id) = ts.get_reversed_id() {
+            for account_id in accounts {
+                if let Some(history) = self.activity.get_mut(&account_id) {
+                    history.retain(|a| a.transaction_id != original_id);
+                }
+            }
+            return;
+        }
+        let kind = ts.get_kind();
+        if !matches!(
+            kind,
+            TransactionKind::Deposit
+                | TransactionKind::Withdrawal
+                | TransactionKind::Transfer
+                | TransactionKind::CurrencyExchange
+        ) {
+            return;
+        }
+        for account_id in accounts {
+            let (Some(effect), Some(account)) = (
+                ts.get_account_effect(account_id),
+                system.get_account(account_id),
+            ) else {
+                continue;
+            };
+            let Ok(effect) =
+                system.convert_at_mid(effect, self.config.reporting_threshold.currency())
+            else {
+                continue;
+            };
+            let initiator = account_id == transaction.account_id;
+            let activity = AccountActivity {
+                transaction_id: transaction.id,
+                timestamp: transaction.timestamp,
+                amount: effect.amount(),
+                cash_deposit: kind == TransactionKind::Deposit,
+                branch_id: transaction.branch_id.filter(|_| initiator),
+            };
+            self.record(account_id, activity, account.branch_id);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// 哈希链审计日志
+
+use std::{fmt, fs, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::*;
+
+/// AuditEventKind 审计事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditEventKind {
+    // 柜员尝试办理无权办理的业务
+    PermissionDenied(Operation),
+    // 柜员签到时密码错误
+    LoginFailed,
+}
+
+/// AuditEvent 审计事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    pub teller_id: usize,
+    pub branch_id: usize,
+    pub kind: AuditEventKind,
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timestamp = self.timestamp.format("%Y-%m-%d %H:%M:%S");
+        match self.kind {
+            AuditEventKind::PermissionDenied(operation) => write!(
+                f,
+                "[{}] Teller {} at branch {} was denied permission to {}",
+                timestamp, self.teller_id, self.branch_id, operation
+            ),
+            AuditEventKind::LoginFailed => write!(
+                f,
+                "[{}] Teller {} failed to sign in at branch {}",
+                timestamp, self.teller_id, self.branch_id
+            ),
+        }
+    }
+}
+
+/// AuditRecord 审计日志记录的内容: 一笔交易或一个审计事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditRecord {
+    Transaction {
+        transaction_id: u64,
+        kind: TransactionKind,
+        account_id: usize,
+        teller_id: usize,
+        branch_id: Option<usize>,
+        balance_after: Option<Money>,
+        description: String,
+    },
+    Event(AuditEvent),
+}
+
+// 第一条记录的 prev_hash
+const AUDIT_GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// AuditLogEntry 审计日志中的一条记录, hash 覆盖序号、时间、上一条记录的 hash 和记录内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub record: AuditRecord,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditLogEntry {
+    fn compute_hash(&self) -> Result<String, BankError> {
+        let content = format!(
+            "{}|{}|{}|{}",
+            self.seq,
+            self.timestamp.to_rfc3339(),
+            self.prev_hash,
+            serde_json::to_string(&self.record)?
+        );
+        Ok(Sha256::digest(content.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+}
+
+/// AuditLog 哈希链审计日志, 每条记录都包含上一条记录的 hash:
+/// 修改、调换或删除中间任何一条记录, 从那条记录起校验都会失败;
+/// 删除末尾的记录需要与上次导出时记下的链头 hash 比较才能发现
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLog {
+    entries: Vec<AuditLogEntry>,
+}
+
+impl AuditLog {
+    pub fn get_entries(&self) -> &[AuditLogEntry] {
+        &self.entries
+    }
+
+    // 最后一条记录的 hash, 空日志为全0
+    pub fn head(&self) -> &str {
+        self.entries
+            .last()
+            .map_or(AUDIT_GENESIS_HASH, |e| e.hash.as_str())
+    }
+
+    pub(crate) fn append(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        record: AuditRecord,
+    ) -> Result<(), BankError> {
+        // 先经过一次序列化, 保证内存中、快照里和导出文件中的记录内容一致(例如 -0 与 0)
+        let record = serde_json::from_str(&serde_json::to_string(&record)?)?;
+        let mut entry = AuditLogEntry {
+            seq: self.entries.len() as u64 + 1,
+            timestamp,
+            record,
+            prev_hash: self.head().to_string(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // 放弃失败操作追加的、还没有写入日志的记录
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+
+    // 校验整条链, 返回链头 hash
+    pub fn verify(&self) -> Result<String, BankError> {
+        Self::verify_entries(&self.entries)
+    }
+
+    // 逐条检查序号连续、prev_hash 等于上一条的 hash、hash 与内容一致
+    pub fn verify_entries(entries: &[AuditLogEntry]) -> Result<String, BankError> {
+        let mut prev_hash = AUDIT_GENESIS_HASH.to_string();
+        for (idx, entry) in entries.iter().enumerate() {
+            let seq = idx as u64 + 1;
+            if entry.seq != seq
+                || entry.prev_hash != prev_hash
+                || entry.compute_hash()? != entry.hash
+            {
+                return Err(BankError::AuditChainBroken { seq });
+            }
+            prev_hash = entry.hash.clone();
+        }
+        Ok(prev_hash)
+    }
+
+    // 导出给审计员: 每行一条 json 记录
+    pub fn export(&self, path: &Path) -> Result<(), BankError> {
+        let mut data = String::new();
+        for entry in &self.entries {
+            data.push_str(&serde_json::to_string(entry)?);
+            data.push('\n');
+        }
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    // 审计员校验导出的文件, 返回链头 hash
+    pub fn verify_export(path: &Path) -> Result<String, BankError> {
+        let mut entries = Vec::new();
+        for line in fs::read_to_string(path)?.lines() {
+            entries.push(serde_json::from_str::<AuditLogEntry>(line)?);
+        }
+        Self::verify_entries(&entries)
+    }
+}
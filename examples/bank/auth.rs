@@ -0,0 +1,306 @@
+// 员工: 排班、角色权限、密码和柜员分配策略
+
+use std::{collections::HashMap, fmt};
+
+use chrono::{DateTime, NaiveTime, Utc};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use super::*;
+
+/// Shift 柜员班次, end 早于 start 时表示跨夜班
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shift {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Shift {
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    // 某个时间是否在班次内, 包含开始时间不包含结束时间
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// TellerRole 柜员角色, 决定柜员可以办理哪些类别的业务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TellerRole {
+    Teller,
+    // 主管, 可以冲正交易、冻结账户
+    Supervisor,
+    // 行长, 还负责现金调拨和日终结算; 在总部签到时负责金库和策略配置
+    BranchManager,
+    // 审计员, 只能查看审计记录, 不办理业务
+    Auditor,
+}
+
+impl TellerRole {
+    pub fn permits(&self, operation: Operation) -> bool {
+        match self {
+            Self::Teller => matches!(
+                operation,
+                Operation::ServeCustomer | Operation::Transact | Operation::PostCardCharges
+            ),
+            Self::Supervisor => matches!(
+                operation,
+                Operation::ServeCustomer
+                    | Operation::Transact
+                    | Operation::PostCardCharges
+                    | Operation::Reverse
+                    | Operation::FreezeAccounts
+                    | Operation::ReviewAlerts
+            ),
+            Self::BranchManager => operation != Operation::Audit,
+            Self::Auditor => matches!(operation, Operation::Audit | Operation::ReviewAlerts),
+        }
+    }
+}
+
+/// Operation 需要授权的业务类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    // 登记客户、开户、激活和销户
+    ServeCustomer,
+    // 存取款、转账、兑换、投资、贷款、信用卡, 以及复核大额现金交易
+    Transact,
+    // 冲正交易
+    Reverse,
+    // 冻结和解冻账户
+    FreezeAccounts,
+    // 分行现金上缴、申请补充现金和日终结算
+    ManageCash,
+    // 开设分行、注入资本、总部金库调拨、定时补款和分行库存现金上下限
+    ManageVault,
+    // 安装交易策略规则和收费标准
+    SetPolicy,
+    // 导入外汇牌价和基金净值
+    LoadMarketData,
+    // 入账商户的信用卡消费
+    PostCardCharges,
+    // 查看审计记录
+    Audit,
+    // 复核反洗钱预警
+    ReviewAlerts,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ServeCustomer => write!(f, "serve customers"),
+            Self::Transact => write!(f, "transact"),
+            Self::Reverse => write!(f, "reverse transactions"),
+            Self::FreezeAccounts => write!(f, "freeze accounts"),
+            Self::ManageCash => write!(f, "manage cash"),
+            Self::ManageVault => write!(f, "manage the vault"),
+            Self::SetPolicy => write!(f, "set policy"),
+            Self::LoadMarketData => write!(f, "load market data"),
+            Self::PostCardCharges => write!(f, "post card charges"),
+            Self::Audit => write!(f, "audit"),
+            Self::ReviewAlerts => write!(f, "review AML alerts"),
+        }
+    }
+}
+
+/// Credential 柜员的登录密码, 只保存加盐后用 PBKDF2-HMAC-SHA256 派生的摘要
+pub struct Credential {
+    salt: [u8; 16],
+    // 派生时的迭代次数, 以后提高迭代次数不影响已设置的密码
+    rounds: u32,
+    digest: [u8; 32],
+}
+
+// 迭代次数让离线暴力破解足够慢; 未优化的调试构建(测试和 cargo run 演示)派生一次要几秒,
+// 所以只在 release 构建中使用生产强度, 摘要记下了迭代次数, 两种构建设置的密码都能校验
+#[cfg(not(debug_assertions))]
+const PBKDF2_ROUNDS: u32 = 600_000;
+#[cfg(debug_assertions)]
+const PBKDF2_ROUNDS: u32 = 1_000;
+
+impl Credential {
+    pub fn new(secret: &str) -> Self {
+        let salt: [u8; 16] = rand::random();
+        Self {
+            salt,
+            rounds: PBKDF2_ROUNDS,
+            digest: Self::derive(&salt, PBKDF2_ROUNDS, secret),
+        }
+    }
+
+    // 比较全部字节, 耗时与密码错在哪一位无关
+    pub fn verify(&self, secret: &str) -> bool {
+        Self::derive(&self.salt, self.rounds, secret)
+            .iter()
+            .zip(self.digest.iter())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+
+    fn derive(salt: &[u8], rounds: u32, secret: &str) -> [u8; 32] {
+        let mut digest = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(secret.as_bytes(), salt, rounds, &mut digest);
+        digest
+    }
+}
+
+/// TellerStatus 柜员状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TellerStatus {
+    Idle,
+    // 正在为叫号的客户办理业务
+    Busy { customer_id: usize },
+    OnBreak,
+}
+
+/// BankTeller 银行柜员
+pub struct BankTeller {
+    pub id: usize,
+    pub(crate) role: TellerRole,
+    // 没有设置密码的柜员不能签到
+    pub(crate) credential: Option<Credential>,
+    // 签到后才能接待客户和办理业务
+    pub(crate) signed_in: bool,
+    // 班次, 没有班次的柜员全天可用
+    shift: Option<Shift>,
+    pub(crate) status: TellerStatus,
+    // 接待过的客户数, 最空闲分配策略按它挑选柜员
+    pub(crate) served: usize,
+}
+
+impl BankTeller {
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            role: TellerRole::Teller,
+            credential: None,
+            signed_in: false,
+            shift: None,
+            status: TellerStatus::Idle,
+            served: 0,
+        }
+    }
+
+    pub fn with_shift(mut self, shift: Shift) -> Self {
+        self.shift = Some(shift);
+        self
+    }
+
+    pub fn with_role(mut self, role: TellerRole) -> Self {
+        self.role = role;
+        self
+    }
+
+    pub fn with_credential(mut self, secret: &str) -> Self {
+        self.credential = Some(Credential::new(secret));
+        self
+    }
+
+    pub fn get_role(&self) -> TellerRole {
+        self.role
+    }
+
+    pub fn is_signed_in(&self) -> bool {
+        self.signed_in
+    }
+
+    pub fn get_shift(&self) -> Option<Shift> {
+        self.shift
+    }
+
+    pub fn get_status(&self) -> TellerStatus {
+        self.status
+    }
+
+    pub fn get_served(&self) -> usize {
+        self.served
+    }
+
+    pub fn is_on_shift(&self, now: DateTime<Utc>) -> bool {
+        self.shift.is_none_or(|shift| shift.contains(now.time()))
+    }
+
+    // 在班且空闲的柜员才能接待新客户
+    pub fn is_available(&self, now: DateTime<Utc>) -> bool {
+        self.signed_in
+            && self.role.permits(Operation::ServeCustomer)
+            && self.status == TellerStatus::Idle
+            && self.is_on_shift(now)
+    }
+}
+
+/// AssignmentPolicy 柜员分配策略, 从可用柜员中挑一个, candidates 按柜员ID排序且不为空
+pub trait AssignmentPolicy: Send + Sync {
+    fn choose(&mut self, candidates: &[&BankTeller]) -> usize;
+}
+
+/// RoundRobin 轮询, 依次分配给ID比上一次更大的柜员
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    last_teller_id: Option<usize>,
+}
+
+impl AssignmentPolicy for RoundRobin {
+    fn choose(&mut self, candidates: &[&BankTeller]) -> usize {
+        let idx = candidates
+            .iter()
+            .position(|t| self.last_teller_id.is_none_or(|last| t.id > last))
+            .unwrap_or(0);
+        self.last_teller_id = Some(candidates[idx].id);
+        idx
+    }
+}
+
+/// LeastBusy 分配给接待客户最少的柜员, 相同时取ID小的
+#[derive(Debug, Default)]
+pub struct LeastBusy;
+
+impl AssignmentPolicy for LeastBusy {
+    fn choose(&mut self, candidates: &[&BankTeller]) -> usize {
+        candidates
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, t)| t.served)
+            .map_or(0, |(idx, _)| idx)
+    }
+}
+
+/// SeededRandom 随机分配, 相同种子得到相同的分配顺序
+pub struct SeededRandom {
+    rng: StdRng,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl AssignmentPolicy for SeededRandom {
+    fn choose(&mut self, candidates: &[&BankTeller]) -> usize {
+        self.rng.gen_range(0..candidates.len())
+    }
+}
+
+/// TellerStats 根据交易记录统计的柜员业绩
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TellerStats {
+    pub teller_id: usize,
+    // 办理的交易笔数
+    pub transactions: usize,
+    // 办理过业务的不同客户数
+    pub customers_served: usize,
+    pub by_kind: HashMap<TransactionKind, usize>,
+    pub first_at: Option<DateTime<Utc>>,
+    pub last_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,273 @@
+// 银行: 总部和各家分行
+
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{NaiveDate, NaiveTime};
+use rust_decimal::Decimal;
+
+use super::*;
+
+/// Bank 银行
+pub struct Bank {
+    // 记录分行
+    branches: Vec<Arc<Mutex<BankBranch>>>,
+    // 总部员工
+    pub(crate) staff: Vec<BankTeller>,
+
+    pub(crate) bank_system: Arc<Mutex<BankSystem>>,
+}
+
+impl Bank {
+    // 银行的记账币种取决于总现金的币种, 总现金作为资本存入总部金库
+    pub fn new(total_cash: Money) -> Result<Self, BankError> {
+        Self::with_clock(total_cash, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(total_cash: Money, clock: Box<dyn Clock>) -> Result<Self, BankError> {
+        let mut bank_system = BankSystem::with_clock(total_cash.currency(), clock);
+        bank_system.record_capital(LedgerAccount::Vault, total_cash)?;
+        Ok(Self {
+            branches: Vec::new(),
+            staff: Vec::new(),
+            bank_system: Arc::new(Mutex::new(bank_system)),
+        })
+    }
+
+    // 从目录恢复银行, 全新目录时把总现金作为资本存入总部金库
+    // 分行对象按恢复出的分行重建, 柜员需要重新登记
+    pub fn open(dir: &Path, total_cash: Money, clock: Box<dyn Clock>) -> Result<Self, BankError> {
+        let mut bank_system = BankSystem::open(dir, total_cash.currency(), clock)?;
+        if bank_system.get_ledger().get_entries().is_empty() {
+            bank_system.record_capital(LedgerAccount::Vault, total_cash)?;
+        }
+        let addresses: Vec<(usize, String)> = bank_system
+            .branches
+            .iter()
+            .map(|(id, address)| (*id, address.clone()))
+            .collect();
+        let bank_system = Arc::new(Mutex::new(bank_system));
+        let branches = addresses
+            .into_iter()
+            .map(|(id, address)| {
+                let branch = BankBranch::new(id, address, Arc::clone(&bank_system));
+                Arc::new(Mutex::new(branch))
+            })
+            .collect();
+        Ok(Self {
+            branches,
+            staff: Vec::new(),
+            bank_system,
+        })
+    }
+
+    pub fn get_branches(&self) -> &[Arc<Mutex<BankBranch>>] {
+        &self.branches
+    }
+
+    // 添加总部员工
+    pub fn add_staff(&mut self, staff: BankTeller) {
+        self.staff.push(staff);
+    }
+
+    // 总部员工用密码签到
+    pub fn sign_in(&mut self, staff_id: usize, secret: &str) -> Result<(), BankError> {
+        let staff = self
+            .staff
+            .iter_mut()
+            .find(|t| t.id == staff_id)
+            .ok_or(BankError::TellerNotFound(staff_id))?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .sign_in_staff(staff, HEADQUARTERS_ID, secret)?;
+        staff.signed_in = true;
+        Ok(())
+    }
+
+    pub fn sign_out(&mut self, staff_id: usize) -> Result<(), BankError> {
+        let staff = self
+            .staff
+            .iter_mut()
+            .find(|t| t.id == staff_id)
+            .ok_or(BankError::TellerNotFound(staff_id))?;
+        staff.signed_in = false;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .sign_out_staff(staff_id, HEADQUARTERS_ID);
+        Ok(())
+    }
+
+    // 写快照
+    pub fn snapshot(&self) -> Result<(), BankError> {
+        self.bank_system.lock().unwrap().snapshot()
+    }
+
+    // 银行总现金(总部金库), 由总账推导
+    pub fn get_total_cash(&self) -> Money {
+        self.bank_system.lock().unwrap().get_vault_cash()
+    }
+
+    // 总部经理添加分行, initial_funds 初始化基金
+    pub fn add_branch(
+        &mut self,
+        manager_id: usize,
+        address: String,
+        initial_funds: Money,
+    ) -> Result<Arc<Mutex<BankBranch>>, BankError> {
+        let branch_id = self.bank_system.lock().unwrap().open_branch(
+            manager_id,
+            address.clone(),
+            initial_funds,
+        )?;
+        let branch = BankBranch::new(branch_id, address, Arc::clone(&self.bank_system));
+        let branch = Arc::new(Mutex::new(branch));
+        self.branches.push(Arc::clone(&branch));
+        Ok(branch)
+    }
+
+    // 储蓄账户计息
+    pub fn accrue_interest(&mut self, as_of: NaiveDate) -> Result<Money, BankError> {
+        self.bank_system.lock().unwrap().accrue_interest(as_of)
+    }
+
+    // 总部员工收集各个分行的存款
+    pub fn collect_cash(&mut self, manager_id: usize, ratio: Decimal) -> Result<(), BankError> {
+        for branch in &self.branches {
+            let branch_id = branch.lock().unwrap().get_id();
+            self.bank_system
+                .lock()
+                .unwrap()
+                .collect_cash_by_ratio(branch_id, manager_id, ratio)?;
+        }
+        Ok(())
+    }
+
+    // 从 csv 文件导入基金净值
+    pub fn load_fund_navs(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.bank_system
+            .lock()
+            .unwrap()
+            .load_fund_navs(manager_id, path)
+    }
+
+    // 从配置文件加载收费标准
+    pub fn load_fee_schedule(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.bank_system
+            .lock()
+            .unwrap()
+            .load_fee_schedule(manager_id, path)
+    }
+
+    // 月度收费
+    pub fn charge_monthly_fees(&mut self, as_of: NaiveDate) -> Result<Money, BankError> {
+        self.bank_system.lock().unwrap().charge_monthly_fees(as_of)
+    }
+
+    // 从 csv 文件导入外汇牌价
+    pub fn load_fx_rates(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.bank_system
+            .lock()
+            .unwrap()
+            .load_fx_rates(manager_id, path)
+    }
+
+    // 定期存款到期支付
+    pub fn mature_term_deposits(&mut self, as_of: NaiveDate) -> Result<Money, BankError> {
+        self.bank_system.lock().unwrap().mature_term_deposits(as_of)
+    }
+
+    // 启动反洗钱监控, 返回的监控对象供柜员查看和复核预警
+    pub fn start_aml_monitor(&mut self, config: AmlConfig) -> Arc<Mutex<AmlMonitor>> {
+        let monitor = Arc::new(Mutex::new(AmlMonitor::new(config)));
+        self.bank_system
+            .lock()
+            .unwrap()
+            .subscribe(Arc::clone(&monitor) as Arc<Mutex<dyn TransactionListener>>);
+        monitor
+    }
+
+    // 从配置文件加载交易策略规则
+    pub fn load_policy(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.bank_system
+            .lock()
+            .unwrap()
+            .load_policy(manager_id, path)
+    }
+
+    // 设置分行库存现金上下限
+    pub fn set_branch_cash_limits(
+        &mut self,
+        manager_id: usize,
+        branch_id: usize,
+        min: Money,
+        max: Money,
+    ) -> Result<(), BankError> {
+        self.bank_system
+            .lock()
+            .unwrap()
+            .set_cash_limits(manager_id, branch_id, min, max)
+    }
+
+    // 每天 at 时间之后第一次调用 run_scheduled_replenishment 时补款
+    pub fn schedule_replenishment(
+        &mut self,
+        manager_id: usize,
+        at: NaiveTime,
+    ) -> Result<(), BankError> {
+        self.bank_system
+            .lock()
+            .unwrap()
+            .schedule_replenishment(manager_id, at)
+    }
+
+    pub fn run_scheduled_replenishment(&mut self) -> Result<ReplenishmentRun, BankError> {
+        self.bank_system
+            .lock()
+            .unwrap()
+            .run_scheduled_replenishment()
+    }
+
+    // 总部员工为所有分行日终结算, counted_cash 为各分行清点的实际现金(分行ID -> 金额)
+    // 没有清点数据的分行按账面现金结算
+    pub fn end_of_day(
+        &mut self,
+        manager_id: usize,
+        counted_cash: &BTreeMap<usize, Money>,
+        ratio: Decimal,
+    ) -> Result<Vec<SettlementReport>, BankError> {
+        let mut reports = Vec::new();
+        for branch in &self.branches {
+            let branch = branch.lock().unwrap();
+            let counted = match counted_cash.get(&branch.get_id()) {
+                Some(counted) => *counted,
+                None => branch.get_cash_on_hand(),
+            };
+            reports.push(self.bank_system.lock().unwrap().settle_branch(
+                branch.get_id(),
+                manager_id,
+                counted,
+                ratio,
+            )?);
+        }
+        Ok(reports)
+    }
+
+    pub fn print_transactions(&self) {
+        for transaction in self.bank_system.lock().unwrap().get_transactions() {
+            println!(
+                "#{} [{}] {}",
+                transaction.get_transaction().get_id(),
+                transaction
+                    .get_transaction()
+                    .get_timestamp()
+                    .format("%Y-%m-%d %H:%M:%S"),
+                transaction.get_transaction_description()
+            );
+        }
+    }
+}
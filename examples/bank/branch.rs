@@ -0,0 +1,620 @@
+// 分行柜台
+
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use super::*;
+
+/// BankBranch 分行
+pub struct BankBranch {
+    // 分行ID
+    id: usize,
+    // 分行地址
+    address: String,
+    // 所有分行共享的银行系统
+    bank_system: Arc<Mutex<BankSystem>>,
+    // 分行柜员, 按柜员ID排序
+    tellers: Vec<BankTeller>,
+    // 排队等待叫号的客户
+    customer_queue: VecDeque<usize>,
+    assignment_policy: Box<dyn AssignmentPolicy>,
+}
+
+impl BankBranch {
+    pub fn new(id: usize, address: String, bank_system: Arc<Mutex<BankSystem>>) -> Self {
+        Self {
+            id,
+            address,
+            bank_system,
+            tellers: Vec::new(),
+            customer_queue: VecDeque::new(),
+            assignment_policy: Box::new(RoundRobin::default()),
+        }
+    }
+
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_address(&self) -> &str {
+        &self.address
+    }
+
+    // 分行持有的现金, 由总账中的分行现金科目推导
+    pub fn get_cash_on_hand(&self) -> Money {
+        self.bank_system.lock().unwrap().get_branch_cash(self.id)
+    }
+
+    // 添加柜员
+    pub fn add_teller(&mut self, teller: BankTeller) {
+        let idx = self.tellers.partition_point(|t| t.id < teller.id);
+        self.tellers.insert(idx, teller);
+    }
+
+    pub fn get_tellers(&self) -> &[BankTeller] {
+        &self.tellers
+    }
+
+    // 柜员用密码签到, 签到后才能接待客户和办理业务; 密码错误记录审计事件
+    pub fn sign_in(&mut self, teller_id: usize, secret: &str) -> Result<(), BankError> {
+        let teller = self
+            .tellers
+            .iter_mut()
+            .find(|t| t.id == teller_id)
+            .ok_or(BankError::TellerNotFound(teller_id))?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .sign_in_staff(teller, self.id, secret)?;
+        teller.signed_in = true;
+        Ok(())
+    }
+
+    pub fn sign_out(&mut self, teller_id: usize) -> Result<(), BankError> {
+        self.get_teller_mut(teller_id)?.signed_in = false;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .sign_out_staff(teller_id, self.id);
+        Ok(())
+    }
+
+    // 审计员导出审计日志, 返回链头 hash 供下次核对
+    pub fn export_audit_log(
+        &mut self,
+        auditor_id: usize,
+        path: &Path,
+    ) -> Result<String, BankError> {
+        let mut system = self.bank_system.lock().unwrap();
+        system.authorize(auditor_id, self.id, Operation::Audit)?;
+        let log = system.get_audit_log();
+        log.export(path)?;
+        Ok(log.head().to_string())
+    }
+
+    // 审计员查看本行的审计记录
+    pub fn get_audit_events(&mut self, auditor_id: usize) -> Result<Vec<AuditEvent>, BankError> {
+        let mut system = self.bank_system.lock().unwrap();
+        system.authorize(auditor_id, self.id, Operation::Audit)?;
+        Ok(system.get_audit_events().to_vec())
+    }
+
+    pub fn set_assignment_policy(&mut self, policy: Box<dyn AssignmentPolicy>) {
+        self.assignment_policy = policy;
+    }
+
+    // 柜员开始休息, 正在办理业务的柜员要先结束服务
+    pub fn start_break(&mut self, teller_id: usize) -> Result<(), BankError> {
+        let teller = self.get_teller_mut(teller_id)?;
+        if teller.status != TellerStatus::Idle {
+            return Err(BankError::NoTellerAvailable);
+        }
+        teller.status = TellerStatus::OnBreak;
+        Ok(())
+    }
+
+    pub fn end_break(&mut self, teller_id: usize) -> Result<(), BankError> {
+        let teller = self.get_teller_mut(teller_id)?;
+        if teller.status == TellerStatus::OnBreak {
+            teller.status = TellerStatus::Idle;
+        }
+        Ok(())
+    }
+
+    // 客户取号排队
+    pub fn enqueue_customer(&mut self, customer_id: usize) {
+        self.customer_queue.push_back(customer_id);
+    }
+
+    pub fn get_queue(&self) -> &VecDeque<usize> {
+        &self.customer_queue
+    }
+
+    // 叫号: 把队首客户分配给一个空闲柜员, 柜员保持忙碌直到 finish_service
+    // 返回(客户ID, 柜员ID), 没有人排队时返回 None
+    pub fn call_next_customer(&mut self) -> Result<Option<(usize, usize)>, BankError> {
+        let Some(&customer_id) = self.customer_queue.front() else {
+            return Ok(None);
+        };
+        let teller_id = self.assign_teller()?;
+        self.customer_queue.pop_front();
+        self.get_teller_mut(teller_id)?.status = TellerStatus::Busy { customer_id };
+        Ok(Some((customer_id, teller_id)))
+    }
+
+    // 柜员办完当前客户的业务, 回到空闲状态
+    pub fn finish_service(&mut self, teller_id: usize) -> Result<(), BankError> {
+        let teller = self.get_teller_mut(teller_id)?;
+        if let TellerStatus::Busy { .. } = teller.status {
+            teller.status = TellerStatus::Idle;
+        }
+        Ok(())
+    }
+
+    // 柜员业绩
+    pub fn get_teller_stats(&self) -> Vec<TellerStats> {
+        self.bank_system.lock().unwrap().get_teller_stats(self.id)
+    }
+
+    // 登记客户
+    pub fn register_customer(
+        &mut self,
+        name: String,
+        date_of_birth: NaiveDate,
+        address: String,
+        document: IdentityDocument,
+    ) -> Result<usize, BankError> {
+        let teller_id = self.get_staffed_teller()?;
+        self.bank_system.lock().unwrap().register_customer(
+            self.id,
+            teller_id,
+            name,
+            date_of_birth,
+            address,
+            document,
+        )
+    }
+
+    // 开户, owners 为账户持有人(客户ID)
+    pub fn open_account(
+        &mut self,
+        owners: Vec<usize>,
+        kind: AccountKind,
+    ) -> Result<usize, BankError> {
+        let teller_id = self.get_teller_serving(&owners)?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .open_account(owners, kind, self.id, teller_id)
+    }
+
+    // 开立外币账户
+    pub fn open_account_in(
+        &mut self,
+        owners: Vec<usize>,
+        kind: AccountKind,
+        currency: Currency,
+    ) -> Result<usize, BankError> {
+        let teller_id = self.get_teller_serving(&owners)?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .open_account_in(owners, kind, currency, self.id, teller_id)
+    }
+
+    // 柜员办理两个账户之间的货币兑换, 返回转入目标账户的金额
+    pub fn exchange_currency(
+        &mut self,
+        from_account_id: usize,
+        to_account_id: usize,
+        amount: Money,
+    ) -> Result<Money, BankError> {
+        let teller_id = self.get_available_teller(Some(from_account_id))?;
+        self.bank_system.lock().unwrap().exchange_currency(
+            from_account_id,
+            to_account_id,
+            self.id,
+            teller_id,
+            amount,
+        )
+    }
+
+    // 存钱
+    pub fn deposit(&mut self, account_id: usize, amount: Money) -> Result<(), BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .deposit(account_id, self.id, teller_id, amount, None)
+    }
+
+    // 由另一名柜员复核的大额存款
+    pub fn deposit_with_approval(
+        &mut self,
+        account_id: usize,
+        amount: Money,
+        approver_id: usize,
+    ) -> Result<(), BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.ensure_approver(approver_id, teller_id)?;
+        self.bank_system.lock().unwrap().deposit(
+            account_id,
+            self.id,
+            teller_id,
+            amount,
+            Some(approver_id),
+        )
+    }
+
+    // 取钱
+    pub fn withdraw(&mut self, account_id: usize, amount: Money) -> Result<(), BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .withdraw(account_id, self.id, teller_id, amount, None)
+    }
+
+    // 由另一名柜员复核的大额取款
+    pub fn withdraw_with_approval(
+        &mut self,
+        account_id: usize,
+        amount: Money,
+        approver_id: usize,
+    ) -> Result<(), BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.ensure_approver(approver_id, teller_id)?;
+        self.bank_system.lock().unwrap().withdraw(
+            account_id,
+            self.id,
+            teller_id,
+            amount,
+            Some(approver_id),
+        )
+    }
+
+    // 转账
+    pub fn transfer(
+        &mut self,
+        from_account_id: usize,
+        to_account_id: usize,
+        amount: Money,
+    ) -> Result<(), BankError> {
+        let teller_id = self.get_available_teller(Some(from_account_id))?;
+        self.bank_system.lock().unwrap().transfer(
+            from_account_id,
+            to_account_id,
+            self.id,
+            teller_id,
+            amount,
+        )
+    }
+
+    // 柜员办理定期存款, 本金从客户账户转出
+    pub fn open_term_deposit(
+        &mut self,
+        account_id: usize,
+        principal: Money,
+        product: TermDepositProduct,
+    ) -> Result<usize, BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .open_term_deposit(account_id, self.id, teller_id, principal, product)
+    }
+
+    // 柜员办理定期存款支取, 未到期时按提前支取处理
+    pub fn withdraw_term_deposit(&mut self, deposit_id: usize) -> Result<Money, BankError> {
+        let account_id = self
+            .bank_system
+            .lock()
+            .unwrap()
+            .get_term_deposit(deposit_id)
+            .map(|d| d.get_account_id());
+        let teller_id = self.get_available_teller(account_id)?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .withdraw_term_deposit(deposit_id, self.id, teller_id)
+    }
+
+    // 柜员办理基金申购
+    pub fn buy_fund(
+        &mut self,
+        account_id: usize,
+        fund_code: &str,
+        amount: Money,
+    ) -> Result<Decimal, BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system.lock().unwrap().buy_fund(
+            account_id,
+            self.id,
+            teller_id,
+            fund_code.to_string(),
+            amount,
+        )
+    }
+
+    // 柜员办理基金赎回
+    pub fn redeem_fund(
+        &mut self,
+        account_id: usize,
+        fund_code: &str,
+        units: Decimal,
+    ) -> Result<Money, BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system.lock().unwrap().redeem_fund(
+            account_id,
+            self.id,
+            teller_id,
+            fund_code.to_string(),
+            units,
+        )
+    }
+
+    // 主管授权冲正本分行办理的一笔交易, 返回冲正交易的ID
+    pub fn reverse_transaction(
+        &mut self,
+        transaction_id: u64,
+        supervisor_id: usize,
+        reason: String,
+    ) -> Result<u64, BankError> {
+        if !self.tellers.iter().any(|t| t.id == supervisor_id) {
+            return Err(BankError::TellerNotFound(supervisor_id));
+        }
+        self.bank_system.lock().unwrap().reverse_transaction(
+            transaction_id,
+            self.id,
+            supervisor_id,
+            reason,
+        )
+    }
+
+    // 柜员核实身份后激活睡眠户
+    pub fn reactivate_account(&mut self, account_id: usize) -> Result<(), BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .reactivate_account(account_id, self.id, teller_id)
+    }
+
+    // 柜员办理销户, 剩余余额以现金支付给客户, 返回支付的金额
+    pub fn close_account(&mut self, account_id: usize) -> Result<Money, BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .close_account(account_id, self.id, teller_id, None)
+    }
+
+    // 销户支付的现金达到大额门槛时由另一名柜员复核
+    pub fn close_account_with_approval(
+        &mut self,
+        account_id: usize,
+        approver_id: usize,
+    ) -> Result<Money, BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.ensure_approver(approver_id, teller_id)?;
+        self.bank_system.lock().unwrap().close_account(
+            account_id,
+            self.id,
+            teller_id,
+            Some(approver_id),
+        )
+    }
+
+    // 柜员办理贷款, 贷款直接发放到客户账户
+    pub fn originate_loan(
+        &mut self,
+        account_id: usize,
+        principal: Money,
+        annual_rate: Decimal,
+        term_months: u32,
+    ) -> Result<usize, BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system.lock().unwrap().originate_loan(
+            account_id,
+            self.id,
+            teller_id,
+            principal,
+            annual_rate,
+            term_months,
+        )
+    }
+
+    // 现金还贷
+    pub fn repay_loan(&mut self, loan_id: usize, amount: Money) -> Result<(), BankError> {
+        let teller_id = self.get_loan_teller(loan_id)?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .repay_loan(loan_id, self.id, teller_id, amount, None)
+    }
+
+    // 大额现金还贷, 由另一名柜员复核
+    pub fn repay_loan_with_approval(
+        &mut self,
+        loan_id: usize,
+        amount: Money,
+        approver_id: usize,
+    ) -> Result<(), BankError> {
+        let teller_id = self.get_loan_teller(loan_id)?;
+        self.ensure_approver(approver_id, teller_id)?;
+        self.bank_system.lock().unwrap().repay_loan(
+            loan_id,
+            self.id,
+            teller_id,
+            amount,
+            Some(approver_id),
+        )
+    }
+
+    // 柜员办理信用卡
+    pub fn issue_credit_card(
+        &mut self,
+        account_id: usize,
+        credit_limit: Money,
+        annual_rate: Decimal,
+        late_fee: Money,
+    ) -> Result<usize, BankError> {
+        let teller_id = self.get_available_teller(Some(account_id))?;
+        self.bank_system.lock().unwrap().issue_credit_card(
+            account_id,
+            self.id,
+            teller_id,
+            credit_limit,
+            annual_rate,
+            late_fee,
+        )
+    }
+
+    // 现金还信用卡
+    pub fn pay_credit_card(&mut self, card_id: usize, amount: Money) -> Result<(), BankError> {
+        let teller_id = self.get_card_teller(card_id)?;
+        self.bank_system
+            .lock()
+            .unwrap()
+            .pay_credit_card(card_id, self.id, teller_id, amount, None)
+    }
+
+    // 大额现金还信用卡, 由另一名柜员复核
+    pub fn pay_credit_card_with_approval(
+        &mut self,
+        card_id: usize,
+        amount: Money,
+        approver_id: usize,
+    ) -> Result<(), BankError> {
+        let teller_id = self.get_card_teller(card_id)?;
+        self.ensure_approver(approver_id, teller_id)?;
+        self.bank_system.lock().unwrap().pay_credit_card(
+            card_id,
+            self.id,
+            teller_id,
+            amount,
+            Some(approver_id),
+        )
+    }
+
+    // 分行行长把分行现金按比例上缴总部, 返回上缴的金额; 行长必须在本分行签到
+    pub fn collect_cash(&mut self, manager_id: usize, ratio: Decimal) -> Result<Money, BankError> {
+        let mut system = self.bank_system.lock().unwrap();
+        system.authorize_local(manager_id, self.id, Operation::ManageCash)?;
+        system.collect_cash_by_ratio(self.id, manager_id, ratio)
+    }
+
+    // 向总部申请补充现金
+    pub fn request_replenishment(
+        &mut self,
+        manager_id: usize,
+        amount: Money,
+    ) -> Result<usize, BankError> {
+        let mut system = self.bank_system.lock().unwrap();
+        system.authorize_local(manager_id, self.id, Operation::ManageCash)?;
+        system.request_replenishment(self.id, manager_id, amount)
+    }
+
+    // 日终结算, counted_cash 为柜员清点的实际现金, ratio 为上缴总部的比例
+    pub fn settle(
+        &mut self,
+        manager_id: usize,
+        counted_cash: Money,
+        ratio: Decimal,
+    ) -> Result<SettlementReport, BankError> {
+        let mut system = self.bank_system.lock().unwrap();
+        system.authorize_local(manager_id, self.id, Operation::ManageCash)?;
+        system.settle_branch(self.id, manager_id, counted_cash, ratio)
+    }
+
+    // 找办理账户业务的柜员
+    fn get_available_teller(&mut self, account_id: Option<usize>) -> Result<usize, BankError> {
+        let owners = account_id
+            .and_then(|account_id| {
+                let system = self.bank_system.lock().unwrap();
+                system.get_account(account_id).map(|a| a.owners.clone())
+            })
+            .unwrap_or_default();
+        self.get_teller_serving(&owners)
+    }
+
+    // 找办理贷款所属账户业务的柜员
+    fn get_loan_teller(&mut self, loan_id: usize) -> Result<usize, BankError> {
+        let account_id = self
+            .bank_system
+            .lock()
+            .unwrap()
+            .get_loan(loan_id)
+            .map(|loan| loan.get_account_id());
+        self.get_available_teller(account_id)
+    }
+
+    // 找办理信用卡所属账户业务的柜员
+    fn get_card_teller(&mut self, card_id: usize) -> Result<usize, BankError> {
+        let account_id = self
+            .bank_system
+            .lock()
+            .unwrap()
+            .get_credit_card(card_id)
+            .map(|card| card.get_account_id());
+        self.get_available_teller(account_id)
+    }
+
+    // 客户已经叫号时由接待他的柜员办理, 否则按分配策略找一个空闲柜员
+    fn get_teller_serving(&mut self, customers: &[usize]) -> Result<usize, BankError> {
+        let serving = self.tellers.iter().find(|t| {
+            matches!(t.status, TellerStatus::Busy { customer_id } if customers.contains(&customer_id))
+        });
+        match serving {
+            Some(teller) => Ok(teller.id),
+            None => self.assign_teller(),
+        }
+    }
+
+    // 按分配策略从在班且空闲的柜员中挑一个, 并记一次接待
+    fn assign_teller(&mut self) -> Result<usize, BankError> {
+        let now = self.bank_system.lock().unwrap().now();
+        let candidates: Vec<&BankTeller> = self
+            .tellers
+            .iter()
+            .filter(|t| t.is_available(now))
+            .collect();
+        if candidates.is_empty() {
+            return Err(BankError::NoTellerAvailable);
+        }
+        let teller_id = candidates[self.assignment_policy.choose(&candidates)].id;
+        let teller = self.get_teller_mut(teller_id)?;
+        teller.served += 1;
+        Ok(teller_id)
+    }
+
+    // 复核柜员必须是本分行的另一名柜员
+    fn ensure_approver(&self, approver_id: usize, teller_id: usize) -> Result<(), BankError> {
+        if approver_id == teller_id || !self.tellers.iter().any(|t| t.id == approver_id) {
+            return Err(BankError::InvalidApprover(approver_id));
+        }
+        Ok(())
+    }
+
+    // 有签到的柜台柜员时分行才对外营业, 返回其中一名柜员
+    fn get_staffed_teller(&self) -> Result<usize, BankError> {
+        self.tellers
+            .iter()
+            .find(|t| t.signed_in && t.role.permits(Operation::ServeCustomer))
+            .map(|t| t.id)
+            .ok_or(BankError::NoTellerAvailable)
+    }
+
+    fn get_teller_mut(&mut self, teller_id: usize) -> Result<&mut BankTeller, BankError> {
+        self.tellers
+            .iter_mut()
+            .find(|t| t.id == teller_id)
+            .ok_or(BankError::TellerNotFound(teller_id))
+    }
+}
@@ -0,0 +1,108 @@
+// 分行日终结算和金库补款
+
+use std::{collections::BTreeMap, fmt};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// SettlementReport 分行日终结算报告
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettlementReport {
+    pub branch_id: usize,
+    pub business_date: NaiveDate,
+    // 上次结算后留在分行的现金, 第一次结算时由账面现金倒推
+    pub opening_cash: Money,
+    // 本次结算周期内的现金收入和支出
+    pub cash_in: Money,
+    pub cash_out: Money,
+    // 应有现金 = 期初 + 收入 - 支出
+    pub expected_cash: Money,
+    // 结算前总账中的分行现金
+    pub ledger_cash: Money,
+    // 柜员清点的实际现金
+    pub counted_cash: Money,
+    // 实际 - 应有, 正数为长款, 负数为短款
+    pub discrepancy: Money,
+    pub transferred_to_headquarters: Money,
+    pub transfer_transaction_id: Option<u64>,
+    // 上缴后留在分行的现金, 作为下次结算的期初现金
+    pub closing_cash: Money,
+    // 本次结算覆盖到的最后一笔交易
+    pub last_transaction_id: u64,
+}
+
+impl SettlementReport {
+    // 实际现金与应有现金不符, 或者账面现金与交易记录推算的不一致
+    pub fn has_discrepancy(&self) -> bool {
+        !self.discrepancy.is_zero() || self.ledger_cash != self.expected_cash
+    }
+}
+
+impl fmt::Display for SettlementReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Branch {} settlement {}: opening {}, in {}, out {}, expected {}, counted {}, discrepancy {}, sent {} to headquarters, closing {}",
+            self.branch_id,
+            self.business_date,
+            self.opening_cash,
+            self.cash_in,
+            self.cash_out,
+            self.expected_cash,
+            self.counted_cash,
+            self.discrepancy,
+            self.transferred_to_headquarters,
+            self.closing_cash
+        )
+    }
+}
+
+/// CashLimits 分行库存现金的上下限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CashLimits {
+    // 低于下限时定时补款
+    pub min: Money,
+    // 补款补到上限
+    pub max: Money,
+}
+
+/// ReplenishmentStatus 补款申请状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplenishmentStatus {
+    Pending,
+    // 已调拨, 记录现金调拨交易的ID
+    Fulfilled { transaction_id: u64 },
+}
+
+/// ReplenishmentRequest 分行向总部申请补充现金
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplenishmentRequest {
+    pub id: usize,
+    pub branch_id: usize,
+    pub amount: Money,
+    pub requested_at: DateTime<Utc>,
+    pub status: ReplenishmentStatus,
+}
+
+/// ReplenishmentRun 一次定时补款的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplenishmentRun {
+    // 本次产生的现金调拨交易ID
+    pub transaction_ids: Vec<u64>,
+    // 没能调拨的申请ID和原因, 这些申请保持待处理, 下次定时补款时重试
+    pub failed: Vec<(usize, BankError)>,
+}
+
+/// Replenishment 总部给分行补充现金的配置和申请记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Replenishment {
+    // 分行ID -> 现金上下限
+    pub(crate) limits: BTreeMap<usize, CashLimits>,
+    pub(crate) requests: Vec<ReplenishmentRequest>,
+    // 每天定时补款的时间, 为空时不自动补款
+    pub(crate) scheduled_at: Option<NaiveTime>,
+    // 上次定时补款的日期, 每天只补一次
+    pub(crate) last_run: Option<NaiveDate>,
+}
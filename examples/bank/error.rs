@@ -0,0 +1,372 @@
+// 银行业务的错误类型
+
+use std::{fmt, io};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use super::*;
+
+/// BankError 银行业务错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BankError {
+    // 账户不存在
+    AccountNotFound(usize),
+    // 账号校验位不对, 多半是输错了
+    InvalidAccountNumber(usize),
+    // 账户余额不足
+    InsufficientFunds {
+        account_id: usize,
+        balance: Money,
+        requested: Money,
+    },
+    // 储蓄账户本月取款次数已达上限
+    WithdrawalLimitExceeded {
+        account_id: usize,
+        limit: u32,
+    },
+    // 超过每天/每月的取款金额上限
+    WithdrawalAmountExceeded {
+        account_id: usize,
+        period: LimitPeriod,
+        limit: Money,
+        withdrawn: Money,
+    },
+    // 大额现金交易需要第二名柜员复核
+    ApprovalRequired {
+        amount: Money,
+        threshold: Money,
+    },
+    // 复核柜员不存在或就是经办柜员本人
+    InvalidApprover(usize),
+    // 分行现金不足
+    BranchCashShortfall {
+        cash_on_hand: Money,
+        requested: Money,
+    },
+    // 分行没有可用的柜员
+    NoTellerAvailable,
+    // 分行没有这个柜员
+    TellerNotFound(usize),
+    BranchNotFound(usize),
+    CustomerNotFound(usize),
+    // 证件已经登记过其他客户
+    DuplicateIdentityDocument(IdentityDocument),
+    // 账户至少要有一个持有人
+    NoAccountOwner,
+    // 账户已冻结, 不能收付款
+    AccountFrozen(usize),
+    // 睡眠户, 激活之前不能出账
+    AccountDormant(usize),
+    // 账户已销户
+    AccountClosed(usize),
+    // 透支的账户要先还清才能销户
+    AccountOverdrawn {
+        account_id: usize,
+        balance: Money,
+    },
+    // 总部金库现金不足
+    VaultCashShortfall {
+        vault_cash: Money,
+        requested: Money,
+    },
+    // 分行现金下限不能高于上限
+    InvalidCashLimits {
+        min: Money,
+        max: Money,
+    },
+    // 补款申请不存在或已经处理
+    ReplenishmentNotPending(usize),
+    // 反洗钱预警不存在或已经复核
+    AlertNotOpen(usize),
+    TransactionNotFound(u64),
+    // 只有存款、取款和转账可以冲正, 并且要在原交易的分行办理
+    TransactionNotReversible(u64),
+    // 交易已经冲正过
+    TransactionAlreadyReversed(u64),
+    // 柜员没有在该分行签到, 或者角色不允许办理该类业务
+    PermissionDenied {
+        teller_id: usize,
+        operation: Operation,
+    },
+    // 柜员没有设置密码或者密码错误
+    InvalidCredentials(usize),
+    // 总部员工和分行柜员不能同时用同一个ID签到, 否则无法区分是谁在办理业务
+    StaffIdInUse(usize),
+    // 审计日志从这条记录(序号从1开始)起被修改、调换或删除
+    AuditChainBroken {
+        seq: u64,
+    },
+    TermDepositNotFound(usize),
+    // 定期存款已经到期支付或提前支取
+    TermDepositNotActive(usize),
+    // 定期存款期限必须大于0
+    InvalidDepositTerm(u32),
+    FundNotFound(String),
+    // 该日期之前没有基金净值
+    NavNotAvailable {
+        fund_code: String,
+        date: NaiveDate,
+    },
+    // 基金份额必须为正数
+    InvalidUnits(Decimal),
+    // 赎回份额超过持有份额
+    InsufficientUnits {
+        fund_code: String,
+        held: Decimal,
+    },
+    // 净值文件某一行格式错误, line 从1开始
+    InvalidNavRecord {
+        line: usize,
+    },
+    // 账户还有定期存款或基金持仓, 不能销户
+    AccountHasInvestments(usize),
+    // 账户还有未还清的贷款或信用卡余额, 不能销户
+    AccountHasOutstandingCredit(usize),
+    // 没有该币种的外汇牌价
+    FxRateNotAvailable(Currency),
+    // 牌价文件某一行格式错误, line 从1开始
+    InvalidFxRecord {
+        line: usize,
+    },
+    // 兑换的两个账户币种相同
+    SameCurrencyExchange(Currency),
+    // 分行只办理记账币种的现金业务
+    ForeignCashNotSupported(Currency),
+    // 金额必须为正数
+    InvalidAmount(Money),
+    // 利率不能为负数
+    InvalidRate(Decimal),
+    // 贷款不存在
+    LoanNotFound(usize),
+    // 贷款期限必须大于0
+    InvalidLoanTerm(u32),
+    // 还款金额超过贷款未还金额
+    RepaymentExceedsBalance {
+        loan_id: usize,
+        outstanding: Money,
+    },
+    // 信用卡不存在
+    CardNotFound(usize),
+    // 超过信用卡额度
+    CreditLimitExceeded {
+        card_id: usize,
+        available: Money,
+    },
+    // 上期账单的还款日还没过, 不能出新账单
+    StatementTooEarly {
+        card_id: usize,
+        due_date: NaiveDate,
+    },
+    // 不能转账给自己
+    SameAccountTransfer(usize),
+    // 分录借贷不平衡
+    UnbalancedEntry,
+    // 金额运算错误(币种不一致、溢出)
+    Money(MoneyError),
+    // 读写日志或快照失败
+    Storage(String),
+    // 日志中间某一行校验失败(不是崩溃造成的末尾残缺记录), line 从1开始
+    JournalCorrupted {
+        line: usize,
+    },
+}
+
+impl fmt::Display for BankError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccountNotFound(account_id) => write!(f, "account {} not found", account_id),
+            Self::InvalidAccountNumber(account_id) => {
+                write!(
+                    f,
+                    "account number {} has an invalid check digit",
+                    account_id
+                )
+            }
+            Self::InsufficientFunds {
+                account_id,
+                balance,
+                requested,
+            } => write!(
+                f,
+                "account {} has insufficient funds: balance {}, requested {}",
+                account_id, balance, requested
+            ),
+            Self::WithdrawalLimitExceeded { account_id, limit } => write!(
+                f,
+                "account {} reached its monthly limit of {} withdrawals",
+                account_id, limit
+            ),
+            Self::WithdrawalAmountExceeded {
+                account_id,
+                period,
+                limit,
+                withdrawn,
+            } => write!(
+                f,
+                "account {} would exceed its {} withdrawal limit of {}, already withdrawn {}",
+                account_id, period, limit, withdrawn
+            ),
+            Self::ApprovalRequired { amount, threshold } => write!(
+                f,
+                "cash transaction of {} reaches {} and requires a second teller's approval",
+                amount, threshold
+            ),
+            Self::InvalidApprover(teller_id) => {
+                write!(f, "teller {} cannot approve this transaction", teller_id)
+            }
+            Self::BranchCashShortfall {
+                cash_on_hand,
+                requested,
+            } => write!(
+                f,
+                "branch cash shortfall: cash on hand {}, requested {}",
+                cash_on_hand, requested
+            ),
+            Self::NoTellerAvailable => write!(f, "no teller available"),
+            Self::TellerNotFound(teller_id) => write!(f, "teller {} not found", teller_id),
+            Self::BranchNotFound(branch_id) => write!(f, "branch {} not found", branch_id),
+            Self::CustomerNotFound(customer_id) => write!(f, "customer {} not found", customer_id),
+            Self::DuplicateIdentityDocument(document) => {
+                write!(f, "identity document {} is already registered", document)
+            }
+            Self::NoAccountOwner => write!(f, "account must have at least one owner"),
+            Self::AccountFrozen(account_id) => write!(f, "account {} is frozen", account_id),
+            Self::AccountDormant(account_id) => write!(f, "account {} is dormant", account_id),
+            Self::AccountClosed(account_id) => write!(f, "account {} is closed", account_id),
+            Self::AccountOverdrawn {
+                account_id,
+                balance,
+            } => write!(
+                f,
+                "account {} is overdrawn with balance {} and cannot be closed",
+                account_id, balance
+            ),
+            Self::VaultCashShortfall {
+                vault_cash,
+                requested,
+            } => write!(
+                f,
+                "vault cash shortfall: vault cash {}, requested {}",
+                vault_cash, requested
+            ),
+            Self::InvalidCashLimits { min, max } => {
+                write!(f, "invalid branch cash limits: min {}, max {}", min, max)
+            }
+            Self::ReplenishmentNotPending(request_id) => {
+                write!(f, "replenishment request {} is not pending", request_id)
+            }
+            Self::AlertNotOpen(alert_id) => write!(f, "AML alert {} is not open", alert_id),
+            Self::TransactionNotFound(transaction_id) => {
+                write!(f, "transaction #{} not found", transaction_id)
+            }
+            Self::TransactionNotReversible(transaction_id) => {
+                write!(f, "transaction #{} cannot be reversed here", transaction_id)
+            }
+            Self::TransactionAlreadyReversed(transaction_id) => {
+                write!(f, "transaction #{} is already reversed", transaction_id)
+            }
+            Self::PermissionDenied {
+                teller_id,
+                operation,
+            } => write!(f, "teller {} is not authorized to {}", teller_id, operation),
+            Self::InvalidCredentials(teller_id) => {
+                write!(f, "invalid credentials for teller {}", teller_id)
+            }
+            Self::StaffIdInUse(teller_id) => write!(
+                f,
+                "staff ID {} is already signed in at headquarters or a branch",
+                teller_id
+            ),
+            Self::AuditChainBroken { seq } => write!(f, "audit log is broken at entry {}", seq),
+            Self::TermDepositNotFound(deposit_id) => {
+                write!(f, "term deposit {} not found", deposit_id)
+            }
+            Self::TermDepositNotActive(deposit_id) => {
+                write!(f, "term deposit {} is no longer active", deposit_id)
+            }
+            Self::InvalidDepositTerm(term_months) => {
+                write!(f, "invalid term deposit term of {} months", term_months)
+            }
+            Self::FundNotFound(fund_code) => write!(f, "fund {} not found", fund_code),
+            Self::NavNotAvailable { fund_code, date } => {
+                write!(f, "no NAV for fund {} on or before {}", fund_code, date)
+            }
+            Self::InvalidUnits(units) => write!(f, "invalid fund units {}", units),
+            Self::InsufficientUnits { fund_code, held } => {
+                write!(f, "only {} units of fund {} are held", held, fund_code)
+            }
+            Self::InvalidNavRecord { line } => write!(f, "invalid NAV record on line {}", line),
+            Self::AccountHasInvestments(account_id) => {
+                write!(f, "account {} still holds investments", account_id)
+            }
+            Self::AccountHasOutstandingCredit(account_id) => write!(
+                f,
+                "account {} still has an unsettled loan or credit card balance",
+                account_id
+            ),
+            Self::FxRateNotAvailable(currency) => write!(f, "no FX rate for {}", currency),
+            Self::InvalidFxRecord { line } => write!(f, "invalid FX rate record on line {}", line),
+            Self::SameCurrencyExchange(currency) => {
+                write!(f, "both accounts are in {}", currency)
+            }
+            Self::ForeignCashNotSupported(currency) => {
+                write!(f, "branches do not handle {} cash", currency)
+            }
+            Self::InvalidAmount(amount) => write!(f, "invalid amount {}", amount),
+            Self::InvalidRate(rate) => write!(f, "invalid interest rate {}", rate),
+            Self::LoanNotFound(loan_id) => write!(f, "loan {} not found", loan_id),
+            Self::InvalidLoanTerm(term_months) => {
+                write!(f, "invalid loan term of {} months", term_months)
+            }
+            Self::RepaymentExceedsBalance {
+                loan_id,
+                outstanding,
+            } => write!(
+                f,
+                "repayment exceeds loan {} outstanding amount {}",
+                loan_id, outstanding
+            ),
+            Self::CardNotFound(card_id) => write!(f, "credit card {} not found", card_id),
+            Self::CreditLimitExceeded { card_id, available } => write!(
+                f,
+                "credit card {} limit exceeded, available credit {}",
+                card_id, available
+            ),
+            Self::StatementTooEarly { card_id, due_date } => write!(
+                f,
+                "credit card {} cannot close a statement until after the previous due date {}",
+                card_id, due_date
+            ),
+            Self::SameAccountTransfer(account_id) => {
+                write!(f, "cannot transfer from account {} to itself", account_id)
+            }
+            Self::UnbalancedEntry => write!(f, "journal entry debits and credits do not balance"),
+            Self::Money(err) => write!(f, "{}", err),
+            Self::Storage(err) => write!(f, "storage error: {}", err),
+            Self::JournalCorrupted { line } => {
+                write!(f, "journal record on line {} is corrupted", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BankError {}
+
+impl From<MoneyError> for BankError {
+    fn from(err: MoneyError) -> Self {
+        Self::Money(err)
+    }
+}
+
+impl From<io::Error> for BankError {
+    fn from(err: io::Error) -> Self {
+        Self::Storage(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for BankError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Storage(err.to_string())
+    }
+}
@@ -0,0 +1,312 @@
+// 复式记账的总账
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// LedgerAccount 总账科目
+/// 复式记账: 每笔分录的借方合计等于贷方合计, 资产类科目余额在借方, 负债和权益类科目余额在贷方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LedgerAccount {
+    // 客户存款(负债), 按账户ID区分
+    Customer(usize),
+    // 分行库存现金(资产), 按分行ID区分
+    BranchCash(usize),
+    // 总部金库(资产)
+    Vault,
+    // 银行资本(权益), 记录银行和分行的初始资金
+    Equity,
+    // 利息支出(费用), 储蓄账户计息时借记
+    InterestExpense,
+    // 应收贷款本金(资产), 按贷款ID区分
+    LoanReceivable(usize),
+    // 利息收入(收入), 贷款还款中的利息部分和信用卡循环利息
+    InterestIncome,
+    // 信用卡应收账款(资产), 按信用卡ID区分
+    CardReceivable(usize),
+    // 应付商户清算款(负债), 信用卡消费后需要支付给商户
+    MerchantSettlement,
+    // 手续费收入(收入)
+    FeeIncome,
+    // 现金长短款(费用), 日终清点的现金少于应有现金时借记, 多于时贷记
+    CashOverShort,
+    // 定期存款(负债), 按定期存款ID区分
+    TermDeposit(usize),
+    // 应付基金清算款(负债), 申购时贷记, 赎回时借记
+    FundSettlement,
+    // 外汇头寸(资产), 按币种区分: 买入外币时借记, 卖出时贷记; 汇兑收益记在记账币种的头寸上,
+    // 使各币种头寸按中间价折算后与汇兑收益相抵
+    FxPosition(Currency),
+    // 汇兑收益(收入)
+    FxIncome,
+}
+
+impl LedgerAccount {
+    // 是否为借方余额科目(资产类)
+    pub fn is_debit_normal(&self) -> bool {
+        matches!(
+            self,
+            Self::BranchCash(_)
+                | Self::Vault
+                | Self::InterestExpense
+                | Self::LoanReceivable(_)
+                | Self::CardReceivable(_)
+                | Self::CashOverShort
+                | Self::FxPosition(_)
+        )
+    }
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Customer(account_id) => write!(f, "customer:{}", account_id),
+            Self::BranchCash(branch_id) => write!(f, "branch-cash:{}", branch_id),
+            Self::Vault => write!(f, "vault"),
+            Self::Equity => write!(f, "equity"),
+            Self::InterestExpense => write!(f, "interest-expense"),
+            Self::LoanReceivable(loan_id) => write!(f, "loan-receivable:{}", loan_id),
+            Self::InterestIncome => write!(f, "interest-income"),
+            Self::CardReceivable(card_id) => write!(f, "card-receivable:{}", card_id),
+            Self::MerchantSettlement => write!(f, "merchant-settlement"),
+            Self::FeeIncome => write!(f, "fee-income"),
+            Self::CashOverShort => write!(f, "cash-over-short"),
+            Self::TermDeposit(deposit_id) => write!(f, "term-deposit:{}", deposit_id),
+            Self::FundSettlement => write!(f, "fund-settlement"),
+            Self::FxPosition(currency) => write!(f, "fx-position:{}", currency),
+            Self::FxIncome => write!(f, "fx-income"),
+        }
+    }
+}
+
+/// Side 借贷方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+/// Posting 分录中的一条借方或贷方记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub side: Side,
+    pub amount: Money,
+}
+
+impl Posting {
+    pub fn debit(account: LedgerAccount, amount: Money) -> Self {
+        Self {
+            account,
+            side: Side::Debit,
+            amount,
+        }
+    }
+
+    pub fn credit(account: LedgerAccount, amount: Money) -> Self {
+        Self {
+            account,
+            side: Side::Credit,
+            amount,
+        }
+    }
+}
+
+/// JournalEntry 会计分录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    id: usize,
+    memo: String,
+    postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_memo(&self) -> &str {
+        &self.memo
+    }
+
+    pub fn get_postings(&self) -> &[Posting] {
+        &self.postings
+    }
+}
+
+/// TrialBalanceLine 试算平衡表中一个科目的借贷发生额
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrialBalanceLine {
+    pub account: LedgerAccount,
+    pub debit: Money,
+    pub credit: Money,
+}
+
+/// TrialBalance 试算平衡表, 由全部分录重新汇总得到
+#[derive(Debug, Clone)]
+pub struct TrialBalance {
+    lines: Vec<TrialBalanceLine>,
+}
+
+impl TrialBalance {
+    pub fn get_lines(&self) -> &[TrialBalanceLine] {
+        &self.lines
+    }
+
+    // 某个币种的借方合计减贷方合计, 账务正确时应为0
+    pub fn net(&self, currency: Currency) -> Result<Money, MoneyError> {
+        let mut net = Money::zero(currency);
+        for line in self.lines.iter().filter(|l| l.debit.currency() == currency) {
+            net = net.checked_add(line.debit)?.checked_sub(line.credit)?;
+        }
+        Ok(net)
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.lines
+            .iter()
+            .all(|line| matches!(self.net(line.debit.currency()), Ok(net) if net.is_zero()))
+    }
+}
+
+/// Ledger 总账, 所有资金变动都以分录的形式记录, 科目余额由分录推导
+pub struct Ledger {
+    pub(crate) entries: Vec<JournalEntry>,
+    // 各科目按币种的借方净额(借方 - 贷方), 随分录过账更新
+    pub(crate) balances: HashMap<(LedgerAccount, Currency), Money>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            balances: HashMap::new(),
+        }
+    }
+
+    pub fn get_entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    // 从快照中的分录重建总账, 重新过账一遍以校验借贷平衡并推导科目余额
+    pub fn restore(entries: Vec<JournalEntry>) -> Result<Self, BankError> {
+        let mut ledger = Self::new();
+        for entry in entries {
+            ledger.post(entry.memo, entry.postings)?;
+        }
+        Ok(ledger)
+    }
+
+    // 过账: 校验借贷平衡后一次性更新所有科目, 任何一步失败都不会留下部分记录
+    pub fn post(&mut self, memo: String, postings: Vec<Posting>) -> Result<usize, BankError> {
+        if postings.len() < 2 {
+            return Err(BankError::UnbalancedEntry);
+        }
+        let mut totals: HashMap<Currency, Money> = HashMap::new();
+        let mut balances = HashMap::new();
+        for posting in &postings {
+            ensure_positive(posting.amount)?;
+            let currency = posting.amount.currency();
+            let total = totals.entry(currency).or_insert(Money::zero(currency));
+            let key = (posting.account, currency);
+            let balance = balances.entry(key).or_insert_with(|| {
+                self.balances
+                    .get(&key)
+                    .copied()
+                    .unwrap_or(Money::zero(currency))
+            });
+            match posting.side {
+                Side::Debit => {
+                    *total = total.checked_add(posting.amount)?;
+                    *balance = balance.checked_add(posting.amount)?;
+                }
+                Side::Credit => {
+                    *total = total.checked_sub(posting.amount)?;
+                    *balance = balance.checked_sub(posting.amount)?;
+                }
+            }
+        }
+        if totals.values().any(|total| !total.is_zero()) {
+            return Err(BankError::UnbalancedEntry);
+        }
+
+        self.balances.extend(balances);
+        let id = self.entries.len() + 1;
+        self.entries.push(JournalEntry { id, memo, postings });
+        Ok(id)
+    }
+
+    // 放弃 len 之后的分录, 科目余额恢复为当时的余额
+    pub(crate) fn rollback(
+        &mut self,
+        len: usize,
+        balances: HashMap<(LedgerAccount, Currency), Money>,
+    ) {
+        self.entries.truncate(len);
+        self.balances = balances;
+    }
+
+    // 两条腿的简单分录: 借 debit, 贷 credit
+    pub fn transfer(
+        &mut self,
+        memo: String,
+        debit: LedgerAccount,
+        credit: LedgerAccount,
+        amount: Money,
+    ) -> Result<usize, BankError> {
+        self.post(
+            memo,
+            vec![
+                Posting::debit(debit, amount),
+                Posting::credit(credit, amount),
+            ],
+        )
+    }
+
+    // 科目某个币种的余额, 按科目的正常余额方向返回(资产为借方余额, 负债/权益为贷方余额)
+    pub fn balance(&self, account: LedgerAccount, currency: Currency) -> Money {
+        let net = self
+            .balances
+            .get(&(account, currency))
+            .copied()
+            .unwrap_or(Money::zero(currency));
+        if account.is_debit_normal() {
+            net
+        } else {
+            -net
+        }
+    }
+
+    pub fn trial_balance(&self) -> Result<TrialBalance, MoneyError> {
+        let mut lines: BTreeMap<(LedgerAccount, Currency), TrialBalanceLine> = BTreeMap::new();
+        for posting in self.entries.iter().flat_map(|e| e.postings.iter()) {
+            let currency = posting.amount.currency();
+            let zero = Money::zero(currency);
+            let line = lines
+                .entry((posting.account, currency))
+                .or_insert(TrialBalanceLine {
+                    account: posting.account,
+                    debit: zero,
+                    credit: zero,
+                });
+            match posting.side {
+                Side::Debit => line.debit = line.debit.checked_add(posting.amount)?,
+                Side::Credit => line.credit = line.credit.checked_add(posting.amount)?,
+            }
+        }
+        Ok(TrialBalance {
+            lines: lines.into_values().collect(),
+        })
+    }
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
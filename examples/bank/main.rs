@@ -0,0 +1,269 @@
+// 设计一家银行
+
+// 背景
+// 银行提供多种金融服务，包括支票和储蓄账户、信用卡和贷款。客户通常必须在使用任何服务之前在银行开设账户。
+// 客户可以存款或取款，甚至购买投资。
+
+// 要求
+// 一些可能要问的问题：
+// 1.银行将提供哪些金融服务？
+// 2.客户需要拥有账户吗？银行会管理它们吗？
+// 3.银行有实体地点和银行柜员吗？
+// 4.我们关心银行的物理安全吗？即银行有金库吗？
+
+// 服务
+// 1.客户可以开设账户和存款/取款
+// 2.我们只关心在物理位置内发生的交易（即通过银行柜员）
+// 3.账户分为支票账户(可以透支到额度)和储蓄账户(按日复利计息，每月取款次数有限)
+// 4.柜员可以为客户办理固定利率的等额本息贷款，客户在分行柜台还款，逾期金额按还款计划计算
+// 5.柜员可以为客户办理信用卡，每月生成账单(最低还款额、还款日; 账单日为出账当天，上期还款日过后才能出新账单)，逾期收取滞纳金，未全额还款收取循环利息
+
+// 出纳员(Tellers)
+//      柜员可以代表客户进行交易
+//          每笔交易都会被记录并与出纳员和客户相关联
+//      柜员按班次上班，有空闲、忙碌、休息三种状态，分行按排队顺序叫号，
+//          由可替换的分配策略(轮询、最空闲、固定种子随机)把客户分配给空闲柜员
+
+// 总部
+//      每个分行地点将在一天结束时将资金汇至中央地点（即银行总部）
+//          我们无需担心交通细节
+//      日终结算: 期初现金加上当天的现金收付得到应有现金，与柜员清点的现金核对，
+//          长短款入账后按比例上缴总部，并生成分行结算报告
+//      总部金库为分行补充现金: 分行可以申请补款，也可以设置现金上下限，
+//          每天定时把低于下限的分行补到上限，每次调拨都记录为交易; 金库不足的申请跳过，下次定时补款时重试
+
+// 设计
+// 顶层设计
+// 1.我们将有一个基本 Transaction 类，该类将由 Deposit(存钱)、Withdrawal(取钱)、Transfer(转账) 和 OpenAccount(开户) 类继承。
+// 2.BankTeller 将简单地封装出纳员的唯一 ID。Customer 记录客户的身份信息(姓名、出生日期、地址、证件)，
+//   BankAccount 只表示账户，一个客户可以拥有多个账户，一个账户也可以由多个客户共同拥有(联名账户)。
+// 3.总部银行将由多个 BankBranch(分行) 对象和一个 BankSystem 组成，该 BankSystem 将成为客户帐户和交易的中央存储。
+// 4.请注意，客户可以与多个分行进行交易，因此我们需要将他们的信息存储在银行系统中。
+// 5.每笔交易都有单调递增的交易ID、交易时间、办理分行和交易后的账户余额，可以按时间段查询和按分行对账。
+// 6.BankSystem 内部有一个复式记账的总账(Ledger)，每笔交易都会生成借贷平衡的分录，
+//   客户余额、分行现金和总部金库都由分录推导，试算平衡表的借贷合计始终相等。
+// 7.BankSystem 可以挂载到本地目录: 每个成功的操作都以带校验和的命令追加到日志并 fsync，
+//   定期把系统状态(包括交易明细)写成快照; 重启时加载快照并重放之后的日志，得到完全相同的余额和交易历史。
+//   每个修改操作要么全部生效并写入日志，要么在任何一步(包括写日志)失败时恢复到操作开始前的状态，
+//   内存中不会留下没有写入日志的修改。
+// 8.BankSystem 和 BankBranch 都放在 Arc<Mutex<..>> 里，可以在多个线程上同时办理业务。
+//   每个操作在持有系统锁期间完成校验、记账和记录交易，不会出现更新丢失;
+//   需要同时持有两把锁时总是先锁分行再锁系统，避免死锁。
+// 9.账号由分配器按序号生成，末位是 Luhn 校验位，销户后账号不会复用; 账户按账号存放在 BTreeMap 里，
+//   按账号查找是 O(log n); 办理业务时先核对校验位，输错的账号与不存在的账号分开报错。
+// 10.账户有正常、冻结、睡眠、销户四种状态，每次状态变更都记录为一笔交易: 冻结的账户不能收付款，
+//   长期没有交易的账户由批处理转为睡眠户，只能入账，柜员核实身份激活后才能出账;
+//   销户在分行柜台办理，剩余余额以现金支付给客户; 贷款或信用卡没有还清时不能销户，销户后也不能再办理贷款和信用卡业务。
+// 11.每笔现金收付(存取款、现金还贷、现金还信用卡、销户支付)和转出(转账、兑换)之前由策略引擎(PolicyEngine)
+//   检查规则: 每个账户每天/每月的取款金额上限、大额现金交易需要第二名柜员复核等，外币金额按中间价折成限额币种比较;
+//   规则从 json 配置文件加载，也可以实现 PolicyRule 添加新规则。
+// 12.BankSystem 记录的交易在所在操作写入日志后通知订阅的 TransactionListener，撤销的操作不会通知。
+//   反洗钱监控(AmlMonitor)按账户统计窗口内的存取款、转账和兑换(外币按中间价折成门槛币种，冲正的交易不计)，
+//   发现拆分交易、快进快出和异常的跨分行业务时生成预警，由主管或审计员复核后排除或上报。
+// 13.办错的存款、取款或转账通过冲正(Reversal)更正: 冲正交易引用原交易ID，按反方向记账恢复余额，
+//   需要原交易分行的主管授权，会出现在对账单里; 原交易记录保持不变，一笔交易只能冲正一次。
+//   冲正取款或转账同时恢复原交易计入的取款次数和限额，并退还原交易引起的手续费。
+// 14.客户可以在柜台办理定期存款和基金: 定期存款按单利计息，到期由批处理自动还本付息，提前支取扣罚金(不损失本金);
+//   基金净值从 csv 文件导入，按交易日最近的净值申购赎回。对账单列出期末的持仓(由历史交易重建)，有持仓的账户不能销户。
+// 15.账户可以使用不同币种开立，总账按科目和币种分别记余额，每个币种各自借贷平衡。外汇牌价从 csv 文件导入，
+//   柜台兑换按买入价/卖出价成交，与中间价的差额记入汇兑收益; 分行只收付记账币种的现金。
+// 16.收费标准(FeeSchedule)从 json 配置文件加载: 超次取款费、跨分行费和透支费在交易办理后收取，
+//   账户管理费由月度批处理收取; 每笔费用都是单独的交易，交易类型按费用区分，描述里引用引起收费的交易。
+// 17.柜员设置密码(只保存加盐后用 PBKDF2 派生的摘要)，签到后才能接待客户。每个带柜员的操作都由 BankSystem 检查柜员是否在该分行签到、
+//   角色是否允许: 柜员办理日常业务，主管还可以冲正和冻结账户，行长负责现金调拨和日终结算，审计员只能查看审计记录;
+//   被拒绝的操作和登录失败记为审计事件写入日志。总部员工在总部签到，负责开设分行、注资、金库调拨、现金上下限、
+//   收费标准和策略配置、导入行情、睡眠户批处理和信用卡消费入账，也可以在各分行行使其角色的权限(签到期间总部员工的ID不能和已签到的分行柜员重复，
+//   分行自己发起的结算、上缴和补款申请只认本分行签到的行长); 计息、月费、定期到期和定时补款等批处理以系统身份执行。
+// 18.每笔交易和审计事件都追加到哈希链审计日志(AuditLog)，每条记录的 hash 包含上一条的 hash，
+//   改动、调换或删除任何一条记录都能校验出来; 审计员导出整条链并记下链头，下次导出时核对。
+//   一次操作产生的审计记录在操作写入日志时才追加到链上，盖上和日志记录相同的时间，之后不再改写;
+//   失败的操作(包括做到一半的批处理)整体撤销，不会在链上留下记录; 重启重放后得到的是同一条链。
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+pub mod account;
+pub mod aml;
+pub mod audit;
+pub mod auth;
+pub mod bank;
+pub mod branch;
+pub mod cash;
+pub mod clock;
+pub mod error;
+pub mod ledger;
+pub mod money;
+pub mod persistence;
+pub mod policy;
+pub mod products;
+pub mod system;
+pub mod transaction;
+
+use account::*;
+use aml::*;
+use audit::*;
+use auth::*;
+use bank::*;
+use branch::*;
+use cash::*;
+use clock::*;
+use error::*;
+use ledger::*;
+use money::*;
+use persistence::*;
+use policy::*;
+use products::*;
+use system::*;
+use transaction::*;
+
+// 系统批处理(如计息)产生的交易没有柜员参与, 使用这个柜员ID
+pub const SYSTEM_TELLER_ID: usize = 0;
+
+// 总部员工签到时使用这个分行ID, 总部员工可以在所有分行行使其角色允许的权限
+pub const HEADQUARTERS_ID: usize = 0;
+
+// 银行内部交易(如分行和总部之间的现金调拨)不涉及客户账户, 使用这个客户ID
+pub const BANK_ACCOUNT_ID: usize = 0;
+
+fn main() -> Result<(), BankError> {
+    let mut bank = Bank::new(Money::from_major(10000, Currency::CNY))?;
+    // 总部行长负责开设分行、信用卡入账和日终结算
+    let manager_id = 100;
+    bank.add_staff(
+        BankTeller::new(manager_id)
+            .with_role(TellerRole::BranchManager)
+            .with_credential("secret"),
+    );
+    bank.sign_in(manager_id, "secret")?;
+    let branch1 = bank.add_branch(
+        manager_id,
+        "123 Main St".to_string(),
+        Money::from_major(1000, Currency::CNY),
+    )?;
+    let branch2 = bank.add_branch(
+        manager_id,
+        "456 Elm St".to_string(),
+        Money::from_major(1000, Currency::CNY),
+    )?;
+
+    for (branch, teller_id) in [(&branch1, 1), (&branch2, 3)] {
+        let mut branch = branch.lock().unwrap();
+        for teller_id in [teller_id, teller_id + 1] {
+            branch.add_teller(BankTeller::new(teller_id).with_credential("secret"));
+            branch.sign_in(teller_id, "secret")?;
+        }
+    }
+
+    let checking = AccountKind::Checking {
+        overdraft_limit: Money::zero(Currency::CNY),
+    };
+    let savings = AccountKind::Savings {
+        annual_rate: Decimal::new(2, 2),
+        monthly_withdrawal_cap: 6,
+    };
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let id_card = |number: &str| IdentityDocument::new(DocumentKind::IdCard, number.to_string());
+    let john = branch1.lock().unwrap().register_customer(
+        "John Doe".to_string(),
+        date(1985, 3, 14),
+        "12 Oak Rd".to_string(),
+        id_card("110101198503140011"),
+    )?;
+    let bob = branch1.lock().unwrap().register_customer(
+        "Bob Smith".to_string(),
+        date(1990, 7, 2),
+        "34 Pine Ave".to_string(),
+        id_card("110101199007020022"),
+    )?;
+    let jane = branch2.lock().unwrap().register_customer(
+        "Jane Doe".to_string(),
+        date(1992, 11, 23),
+        "56 Birch Ln".to_string(),
+        id_card("110101199211230033"),
+    )?;
+    let account_id1 = branch1
+        .lock()
+        .unwrap()
+        .open_account(vec![john], checking.clone())?;
+    let account_id2 = branch1.lock().unwrap().open_account(vec![bob], savings)?;
+    let account_id3 = branch2.lock().unwrap().open_account(vec![jane], checking)?;
+
+    branch1
+        .lock()
+        .unwrap()
+        .deposit(account_id1, Money::from_major(100, Currency::CNY))?;
+    branch1
+        .lock()
+        .unwrap()
+        .deposit(account_id2, Money::from_major(200, Currency::CNY))?;
+    branch2
+        .lock()
+        .unwrap()
+        .deposit(account_id3, Money::from_major(300, Currency::CNY))?;
+    branch1
+        .lock()
+        .unwrap()
+        .withdraw(account_id1, Money::from_minor(5050, Currency::CNY))?;
+    branch2.lock().unwrap().transfer(
+        account_id3,
+        account_id1,
+        Money::from_major(80, Currency::CNY),
+    )?;
+    let loan_id = branch1.lock().unwrap().originate_loan(
+        account_id2,
+        Money::from_major(1200, Currency::CNY),
+        Decimal::new(6, 2),
+        12,
+    )?;
+    branch2
+        .lock()
+        .unwrap()
+        .repay_loan(loan_id, Money::from_major(150, Currency::CNY))?;
+    let card_id = branch2.lock().unwrap().issue_credit_card(
+        account_id3,
+        Money::from_major(5000, Currency::CNY),
+        Decimal::new(18, 2),
+        Money::from_major(50, Currency::CNY),
+    )?;
+    bank.bank_system.lock().unwrap().charge_credit_card(
+        manager_id,
+        card_id,
+        "Coffee Shop".to_string(),
+        Money::from_minor(3250, Currency::CNY),
+    )?;
+    // 余额不足时返回错误, 不会记录交易
+    if let Err(err) = branch2
+        .lock()
+        .unwrap()
+        .withdraw(account_id3, Money::from_major(500, Currency::CNY))
+    {
+        println!("Withdrawal failed: {}", err);
+    }
+    bank.print_transactions();
+    // Possible Output:
+    // Withdrawal failed: account 10000024 has insufficient funds: balance 220.00 CNY, requested 500.00 CNY
+    // #1 [2026-10-17 09:00:00] Teller 1 opened account 10000008
+    // #2 [2026-10-17 09:00:00] Teller 2 opened account 10000016
+    // #3 [2026-10-17 09:00:00] Teller 3 opened account 10000024
+    // #4 [2026-10-17 09:00:00] Teller 1 deposited 100.00 CNY to account 10000008
+    // #5 [2026-10-17 09:00:00] Teller 2 deposited 200.00 CNY to account 10000016
+    // #6 [2026-10-17 09:00:00] Teller 4 deposited 300.00 CNY to account 10000024
+    // #7 [2026-10-17 09:00:00] Teller 1 withdraw 50.50 CNY from account 10000008
+    // #8 [2026-10-17 09:00:00] Teller 3 transferred 80.00 CNY from account 10000024 to account 10000008 (cross-branch)
+    // #9 [2026-10-17 09:00:00] Teller 2 originated loan 1 of 1200.00 CNY at 6.00% for 12 months to account 10000016
+    // #10 [2026-10-17 09:00:00] Teller 4 received repayment of 150.00 CNY for loan 1 from account 10000016
+    // #11 [2026-10-17 09:00:00] Teller 3 issued credit card 1 with limit 5000.00 CNY to account 10000024
+    // #12 [2026-10-17 09:00:00] Credit card 1 of account 10000024 charged 32.50 CNY at Coffee Shop
+    for report in bank.end_of_day(manager_id, &BTreeMap::new(), Decimal::new(5, 1))? {
+        println!("{}", report);
+    }
+    // Branch 1 settlement 2026-10-17: opening 1000.00 CNY, in 300.00 CNY, out 50.50 CNY, expected 1249.50 CNY, counted 1249.50 CNY, discrepancy 0.00 CNY, sent 624.75 CNY to headquarters, closing 624.75 CNY
+    // Branch 2 settlement 2026-10-17: opening 1000.00 CNY, in 450.00 CNY, out 0.00 CNY, expected 1450.00 CNY, counted 1450.00 CNY, discrepancy 0.00 CNY, sent 725.00 CNY to headquarters, closing 725.00 CNY
+    println!("Total cash: {}", bank.get_total_cash());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,242 @@
+// 币种、金额和外汇牌价
+
+use std::{cmp::Ordering, fmt, fs, ops::Neg, path::Path};
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// Currency 币种(ISO 4217)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Currency {
+    CNY,
+    USD,
+    EUR,
+    GBP,
+    JPY,
+}
+
+impl Currency {
+    // ISO 4217 货币代码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::CNY => "CNY",
+            Self::USD => "USD",
+            Self::EUR => "EUR",
+            Self::GBP => "GBP",
+            Self::JPY => "JPY",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        [Self::CNY, Self::USD, Self::EUR, Self::GBP, Self::JPY]
+            .into_iter()
+            .find(|c| c.code() == code)
+    }
+
+    // 最小货币单位的小数位数(人民币精确到分即2位, 日元没有辅币即0位)
+    pub fn minor_units(&self) -> u32 {
+        match self {
+            Self::JPY => 0,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// MoneyError 金额运算错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    // 两个金额的币种不一致, 不能直接运算
+    CurrencyMismatch(Currency, Currency),
+    // 运算结果溢出
+    Overflow,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CurrencyMismatch(a, b) => write!(f, "currency mismatch: {} vs {}", a, b),
+            Self::Overflow => write!(f, "money arithmetic overflow"),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+/// Money 金额(数值 + 币种), 使用 rust_decimal 保证精确计算, 不会像浮点数那样丢失精度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Money {
+    amount: Decimal,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Self {
+        Self { amount, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(Decimal::ZERO, currency)
+    }
+
+    // 以主币单位创建, 例如 Money::from_major(100, Currency::CNY) 为 100 元
+    pub fn from_major(units: i64, currency: Currency) -> Self {
+        Self::new(Decimal::from(units), currency)
+    }
+
+    // 以最小货币单位创建, 例如 Money::from_minor(150, Currency::CNY) 为 1.50 元
+    pub fn from_minor(minor: i64, currency: Currency) -> Self {
+        Self::new(Decimal::new(minor, currency.minor_units()), currency)
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount.is_sign_negative() && !self.amount.is_zero()
+    }
+
+    pub fn checked_add(&self, other: Money) -> Result<Money, MoneyError> {
+        self.ensure_same_currency(&other)?;
+        let amount = self
+            .amount
+            .checked_add(other.amount)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    pub fn checked_sub(&self, other: Money) -> Result<Money, MoneyError> {
+        self.ensure_same_currency(&other)?;
+        let amount = self
+            .amount
+            .checked_sub(other.amount)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    // 乘以一个系数(利率、比例等), 结果不做舍入, 需要时调用 round
+    pub fn checked_mul(&self, factor: Decimal) -> Result<Money, MoneyError> {
+        let amount = self
+            .amount
+            .checked_mul(factor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    // 除以一个系数(汇率等), 除数为0或溢出时返回错误, 结果不做舍入
+    pub fn checked_div(&self, divisor: Decimal) -> Result<Money, MoneyError> {
+        let amount = self
+            .amount
+            .checked_div(divisor)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    // 按币种的最小单位舍入, 默认使用银行家舍入法(四舍六入五成双)
+    pub fn round(&self) -> Money {
+        self.round_with(RoundingStrategy::MidpointNearestEven)
+    }
+
+    pub fn round_with(&self, strategy: RoundingStrategy) -> Money {
+        let amount = self
+            .amount
+            .round_dp_with_strategy(self.currency.minor_units(), strategy);
+        Self::new(amount, self.currency)
+    }
+
+    fn ensure_same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, other.currency));
+        }
+        Ok(())
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Self::new(-self.amount, self.currency)
+    }
+}
+
+// 不同币种的金额无法比较大小
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.amount.partial_cmp(&other.amount)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let amount = self.amount.round_dp(self.currency.minor_units());
+        write!(
+            f,
+            "{:.*} {}",
+            self.currency.minor_units() as usize,
+            amount,
+            self.currency
+        )
+    }
+}
+
+/// FxRate 外汇牌价: 1单位外币折合记账币种的买入价(bid)和卖出价(ask)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FxRate {
+    pub currency: Currency,
+    // 银行买入外币的价格
+    pub bid: Decimal,
+    // 银行卖出外币的价格
+    pub ask: Decimal,
+}
+
+impl FxRate {
+    // 中间价
+    pub fn mid(&self) -> Decimal {
+        // 先除后加, 牌价再大也不会溢出
+        self.bid / Decimal::TWO + self.ask / Decimal::TWO
+    }
+
+    // 读取 csv 牌价文件, 第一行是表头 currency,bid,ask; 牌价以 base 记账币种报价
+    pub fn load_csv(path: &Path, base: Currency) -> Result<Vec<Self>, BankError> {
+        let data = fs::read_to_string(path)?;
+        let mut rates = Vec::new();
+        for (idx, line) in data.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = BankError::InvalidFxRecord { line: idx + 1 };
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [currency, bid, ask] = fields[..] else {
+                return Err(invalid);
+            };
+            let currency = Currency::from_code(currency).ok_or(invalid.clone())?;
+            let bid = bid.parse::<Decimal>().map_err(|_| invalid.clone())?;
+            let ask = ask.parse::<Decimal>().map_err(|_| invalid.clone())?;
+            if currency == base || bid <= Decimal::ZERO || bid > ask {
+                return Err(invalid);
+            }
+            rates.push(Self { currency, bid, ask });
+        }
+        Ok(rates)
+    }
+}
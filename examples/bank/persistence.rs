@@ -0,0 +1,482 @@
+// 操作日志、快照和失败操作的回滚
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// Command 日志中记录的一次状态变更, 对应 BankSystem 的一个公开操作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    OpenBranch {
+        address: String,
+        initial_funds: Money,
+    },
+    InjectCapital {
+        account: LedgerAccount,
+        amount: Money,
+    },
+    RegisterCustomer {
+        name: String,
+        date_of_birth: NaiveDate,
+        address: String,
+        document: IdentityDocument,
+    },
+    OpenAccount {
+        owners: Vec<usize>,
+        kind: AccountKind,
+        branch_id: usize,
+        teller_id: usize,
+        // 旧日志没有币种, 按记账币种开户
+        #[serde(default)]
+        currency: Option<Currency>,
+    },
+    AddAccountOwner {
+        account_id: usize,
+        customer_id: usize,
+    },
+    FreezeAccount {
+        account_id: usize,
+        #[serde(default)]
+        branch_id: usize,
+        #[serde(default)]
+        teller_id: usize,
+        reason: String,
+    },
+    UnfreezeAccount {
+        account_id: usize,
+        #[serde(default)]
+        branch_id: usize,
+        #[serde(default)]
+        teller_id: usize,
+        reason: String,
+    },
+    MarkDormantAccounts {
+        as_of: NaiveDate,
+        inactive_days: u32,
+    },
+    ReactivateAccount {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+    },
+    CloseAccount {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        #[serde(default)]
+        approved_by: Option<usize>,
+    },
+    OpenTermDeposit {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        principal: Money,
+        product: TermDepositProduct,
+    },
+    WithdrawTermDeposit {
+        deposit_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+    },
+    MatureTermDeposits {
+        as_of: NaiveDate,
+    },
+    ImportFundNavs {
+        records: Vec<NavRecord>,
+    },
+    BuyFund {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        fund_code: String,
+        amount: Money,
+    },
+    RedeemFund {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        fund_code: String,
+        units: Decimal,
+    },
+    ImportFxRates {
+        rates: Vec<FxRate>,
+    },
+    SetFeeSchedule {
+        schedule: FeeSchedule,
+    },
+    RecordAuditEvent {
+        event: AuditEvent,
+    },
+    ChargeMonthlyFees {
+        as_of: NaiveDate,
+    },
+    ExchangeCurrency {
+        from_account_id: usize,
+        to_account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+    },
+    // 记录冲正的全部内容, 重放时不需要再查找原交易
+    ReverseTransaction {
+        original_id: u64,
+        original_kind: TransactionKind,
+        account_id: usize,
+        counterparty_account_id: Option<usize>,
+        branch_id: usize,
+        supervisor_id: usize,
+        amount: Money,
+        reason: String,
+    },
+    Deposit {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        #[serde(default)]
+        approved_by: Option<usize>,
+    },
+    Withdraw {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        #[serde(default)]
+        approved_by: Option<usize>,
+    },
+    Transfer {
+        from_account_id: usize,
+        to_account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+    },
+    CollectCash {
+        branch_id: usize,
+        amount: Money,
+    },
+    SettleBranch {
+        branch_id: usize,
+        counted_cash: Money,
+        ratio: Decimal,
+    },
+    SetCashLimits {
+        branch_id: usize,
+        limits: CashLimits,
+    },
+    RequestReplenishment {
+        branch_id: usize,
+        amount: Money,
+    },
+    FulfillReplenishment {
+        request_id: usize,
+    },
+    ScheduleReplenishment {
+        at: NaiveTime,
+    },
+    // 定时补款中的每笔申请和调拨单独记录, 这条只记录当天已经补过
+    CompleteScheduledReplenishment,
+    OriginateLoan {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        principal: Money,
+        annual_rate: Decimal,
+        term_months: u32,
+    },
+    RepayLoan {
+        loan_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        #[serde(default)]
+        approved_by: Option<usize>,
+    },
+    IssueCreditCard {
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        credit_limit: Money,
+        annual_rate: Decimal,
+        late_fee: Money,
+    },
+    ChargeCreditCard {
+        card_id: usize,
+        merchant: String,
+        amount: Money,
+    },
+    PayCreditCard {
+        card_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        #[serde(default)]
+        approved_by: Option<usize>,
+    },
+    GenerateCardStatement {
+        card_id: usize,
+    },
+    AccrueInterest {
+        as_of: NaiveDate,
+    },
+}
+
+/// JournalRecord 日志记录, seq 从1开始连续递增
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub seq: u64,
+    // 操作发生的时间, 重放时交易使用这个时间
+    pub timestamp: DateTime<Utc>,
+    pub command: Command,
+}
+
+pub(crate) const JOURNAL_FILE: &str = "journal.log";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+/// Journal 只追加的日志文件, 每行是 "<crc32> <json>", 写入后立即 fsync
+pub struct Journal {
+    pub(crate) dir: PathBuf,
+    pub(crate) file: File,
+    pub(crate) next_seq: u64,
+    // 已经完整写入的字节数
+    len: u64,
+    // 写入失败且没能截掉写了一半的记录, 之后的记录会接在残缺的行后面, 重新打开之前不再写入
+    failed: bool,
+}
+
+impl Journal {
+    // 打开(或创建)日志并读出全部记录
+    // 崩溃可能留下写了一半的最后一行, 这样的残缺记录会被截掉; 中间的记录损坏则报错
+    pub(crate) fn open(dir: &Path) -> Result<(Self, Vec<JournalRecord>), BankError> {
+        let path = dir.join(JOURNAL_FILE);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut records: Vec<JournalRecord> = Vec::new();
+        let mut valid_len = 0;
+        let mut offset = 0;
+        let mut line_no = 0;
+        while offset < data.len() {
+            line_no += 1;
+            let (line, next, complete) = match data[offset..].iter().position(|b| *b == b'\n') {
+                Some(pos) => (&data[offset..offset + pos], offset + pos + 1, true),
+                None => (&data[offset..], data.len(), false),
+            };
+            let record = decode_line(line)
+                .and_then(|line| serde_json::from_str::<JournalRecord>(line).ok())
+                .filter(|r| r.seq == records.last().map_or(1, |last| last.seq + 1));
+            match record {
+                Some(record) if complete => {
+                    records.push(record);
+                    valid_len = next;
+                }
+                // 最后一行没写完
+                _ if next == data.len() => break,
+                _ => return Err(BankError::JournalCorrupted { line: line_no }),
+            }
+            offset = next;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if valid_len < data.len() {
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+        let journal = Self {
+            dir: dir.to_path_buf(),
+            file,
+            next_seq: records.last().map_or(1, |r| r.seq + 1),
+            len: valid_len as u64,
+            failed: false,
+        };
+        Ok((journal, records))
+    }
+
+    pub(crate) fn append(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        command: Command,
+    ) -> Result<u64, BankError> {
+        if self.failed {
+            return Err(BankError::Storage(
+                "journal write failed earlier, reopen the bank".to_string(),
+            ));
+        }
+        let record = JournalRecord {
+            seq: self.next_seq,
+            timestamp,
+            command,
+        };
+        let line = encode_line(&serde_json::to_string(&record)?);
+        let written = self
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data());
+        if let Err(err) = written {
+            // 截掉可能写了一半的记录
+            if self
+                .file
+                .set_len(self.len)
+                .and_then(|_| self.file.sync_all())
+                .is_err()
+            {
+                self.failed = true;
+            }
+            return Err(err.into());
+        }
+        self.len += line.len() as u64;
+        self.next_seq += 1;
+        Ok(record.seq)
+    }
+}
+
+/// Snapshot 快照, 记录写快照时最后一条日志的 seq
+#[derive(Deserialize)]
+pub(crate) struct Snapshot {
+    pub(crate) seq: u64,
+    pub(crate) currency: Currency,
+    pub(crate) branches: BTreeMap<usize, String>,
+    #[serde(default)]
+    pub(crate) customers: Vec<Customer>,
+    pub(crate) accounts: BTreeMap<usize, BankAccount>,
+    pub(crate) account_ids: AccountIdAllocator,
+    pub(crate) ledger: Vec<JournalEntry>,
+    pub(crate) loans: Vec<Loan>,
+    pub(crate) credit_cards: Vec<CreditCard>,
+    #[serde(default)]
+    pub(crate) settlements: Vec<SettlementReport>,
+    #[serde(default)]
+    pub(crate) replenishment: Replenishment,
+    #[serde(default)]
+    pub(crate) reversals: BTreeMap<u64, u64>,
+    #[serde(default)]
+    pub(crate) term_deposits: Vec<TermDeposit>,
+    #[serde(default)]
+    pub(crate) funds: BTreeMap<String, Fund>,
+    #[serde(default)]
+    pub(crate) fund_holdings: Vec<FundHolding>,
+    #[serde(default)]
+    pub(crate) fx_rates: BTreeMap<Currency, FxRate>,
+    #[serde(default)]
+    pub(crate) fees: FeeSchedule,
+    #[serde(default)]
+    pub(crate) audit_events: Vec<AuditEvent>,
+    #[serde(default)]
+    pub(crate) audit_log: AuditLog,
+    #[serde(default)]
+    pub(crate) transactions: Vec<StoredTransaction>,
+    pub(crate) next_transaction_id: u64,
+}
+
+// 写快照时借用系统状态, 避免复制
+#[derive(Serialize)]
+pub(crate) struct SnapshotRef<'a> {
+    pub(crate) seq: u64,
+    pub(crate) currency: Currency,
+    pub(crate) branches: &'a BTreeMap<usize, String>,
+    pub(crate) customers: &'a [Customer],
+    pub(crate) accounts: &'a BTreeMap<usize, BankAccount>,
+    pub(crate) account_ids: &'a AccountIdAllocator,
+    pub(crate) ledger: &'a [JournalEntry],
+    pub(crate) loans: &'a [Loan],
+    pub(crate) credit_cards: &'a [CreditCard],
+    pub(crate) settlements: &'a [SettlementReport],
+    pub(crate) replenishment: &'a Replenishment,
+    pub(crate) reversals: &'a BTreeMap<u64, u64>,
+    pub(crate) term_deposits: &'a [TermDeposit],
+    pub(crate) funds: &'a BTreeMap<String, Fund>,
+    pub(crate) fund_holdings: &'a [FundHolding],
+    pub(crate) fx_rates: &'a BTreeMap<Currency, FxRate>,
+    pub(crate) fees: &'a FeeSchedule,
+    pub(crate) audit_events: &'a [AuditEvent],
+    pub(crate) audit_log: &'a AuditLog,
+    pub(crate) transactions: Vec<StoredTransactionRef<'a>>,
+    pub(crate) next_transaction_id: u64,
+}
+
+impl Snapshot {
+    pub(crate) fn load(dir: &Path) -> Result<Option<Self>, BankError> {
+        let data = match fs::read_to_string(dir.join(SNAPSHOT_FILE)) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let json = decode_line(data.trim_end().as_bytes())
+            .ok_or_else(|| BankError::Storage("snapshot checksum mismatch".to_string()))?;
+        Ok(Some(serde_json::from_str(json)?))
+    }
+}
+
+// 先写临时文件并 fsync, 再原子地 rename 覆盖旧快照, 崩溃时不会留下半个快照
+pub(crate) fn write_snapshot(dir: &Path, snapshot: &SnapshotRef) -> Result<(), BankError> {
+    let tmp = dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+    let mut file = File::create(&tmp)?;
+    file.write_all(encode_line(&serde_json::to_string(snapshot)?).as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(SNAPSHOT_FILE))?;
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn encode_line(json: &str) -> String {
+    format!("{:08x} {}\n", crc32(json.as_bytes()), json)
+}
+
+// 校验并取出一行中的 json, 校验和不对时返回 None
+fn decode_line(line: &[u8]) -> Option<&str> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (checksum, json) = line.split_once(' ')?;
+    let checksum = u32::from_str_radix(checksum, 16).ok()?;
+    (crc32(json.as_bytes()) == checksum).then_some(json)
+}
+
+// CRC-32(IEEE 802.3)
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+// 操作开始前的状态, 操作失败时用来恢复
+// 只追加的交易明细、分录和审计记录只记长度, 其余状态整体复制
+pub(crate) struct Checkpoint {
+    pub(crate) customers: Vec<Customer>,
+    pub(crate) customers_by_document: HashMap<IdentityDocument, usize>,
+    pub(crate) accounts: BTreeMap<usize, BankAccount>,
+    pub(crate) account_ids: AccountIdAllocator,
+    pub(crate) branches: BTreeMap<usize, String>,
+    pub(crate) transactions: usize,
+    pub(crate) next_transaction_id: u64,
+    pub(crate) ledger_entries: usize,
+    pub(crate) ledger_balances: HashMap<(LedgerAccount, Currency), Money>,
+    pub(crate) loans: Vec<Loan>,
+    pub(crate) credit_cards: Vec<CreditCard>,
+    pub(crate) term_deposits: Vec<TermDeposit>,
+    pub(crate) funds: BTreeMap<String, Fund>,
+    pub(crate) fund_holdings: Vec<FundHolding>,
+    pub(crate) fx_rates: BTreeMap<Currency, FxRate>,
+    pub(crate) fees: FeeSchedule,
+    pub(crate) settlements: Vec<SettlementReport>,
+    pub(crate) replenishment: Replenishment,
+    pub(crate) reversals: BTreeMap<u64, u64>,
+    pub(crate) audit_events: usize,
+    pub(crate) audit_log: usize,
+}
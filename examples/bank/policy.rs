@@ -0,0 +1,259 @@
+// 交易前检查的策略规则
+
+use std::{fs, path::Path};
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// PolicyRequest 提交给策略引擎检查的交易
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRequest {
+    pub kind: TransactionKind,
+    // 出账账户(存款时为入账账户, 还贷和还信用卡时为贷款和信用卡所属的账户)
+    pub account_id: usize,
+    pub teller_id: usize,
+    pub approved_by: Option<usize>,
+    // 账户币种的金额
+    pub amount: Money,
+    // 是否在柜台收付现金
+    pub cash: bool,
+    // 账户币种对记账币种的中间价, 检查前由 BankSystem 填写, 记账币种的账户为1
+    pub mid_rate: Decimal,
+}
+
+impl PolicyRequest {
+    pub fn new(kind: TransactionKind, account_id: usize, teller_id: usize, amount: Money) -> Self {
+        Self {
+            kind,
+            account_id,
+            teller_id,
+            approved_by: None,
+            amount,
+            cash: false,
+            mid_rate: Decimal::ONE,
+        }
+    }
+
+    // 在柜台收付现金, approved_by 为复核柜员
+    pub fn in_cash(mut self, approved_by: Option<usize>) -> Self {
+        self.cash = true;
+        self.approved_by = approved_by;
+        self
+    }
+
+    // 把账户币种的金额按中间价折成规则使用的记账币种
+    pub fn convert(&self, amount: Money, to: Currency) -> Result<Money, BankError> {
+        if amount.currency() == to {
+            return Ok(amount);
+        }
+        let converted = amount
+            .amount()
+            .checked_mul(self.mid_rate)
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(converted, to).round())
+    }
+}
+
+/// PolicyRule 交易前检查的一条规则
+pub trait PolicyRule: Send + Sync {
+    fn check(
+        &self,
+        request: &PolicyRequest,
+        account: &BankAccount,
+        today: NaiveDate,
+    ) -> Result<(), BankError>;
+
+    // 安装规则前校验参数, currency 为银行的记账币种
+    fn validate(&self, currency: Currency) -> Result<(), BankError>;
+}
+
+// 规则金额必须为正数且使用银行的记账币种
+fn ensure_rule_amount(amount: Money, currency: Currency) -> Result<(), BankError> {
+    if amount.currency() != currency {
+        return Err(MoneyError::CurrencyMismatch(currency, amount.currency()).into());
+    }
+    ensure_positive(amount)
+}
+
+/// WithdrawalLimitRule 每个账户每天/每月取款(含转出和兑换)总额的上限, 外币账户按中间价折算
+pub struct WithdrawalLimitRule {
+    period: LimitPeriod,
+    limit: Money,
+    // 只对某个账户生效, 为空时对所有账户生效
+    account_id: Option<usize>,
+}
+
+impl WithdrawalLimitRule {
+    pub fn new(period: LimitPeriod, limit: Money, account_id: Option<usize>) -> Self {
+        Self {
+            period,
+            limit,
+            account_id,
+        }
+    }
+}
+
+impl PolicyRule for WithdrawalLimitRule {
+    fn check(
+        &self,
+        request: &PolicyRequest,
+        account: &BankAccount,
+        today: NaiveDate,
+    ) -> Result<(), BankError> {
+        if !matches!(
+            request.kind,
+            TransactionKind::Withdrawal
+                | TransactionKind::Transfer
+                | TransactionKind::CurrencyExchange
+        ) || self.account_id.is_some_and(|id| id != request.account_id)
+        {
+            return Ok(());
+        }
+        let withdrawn = match self.period {
+            LimitPeriod::Daily => account.withdrawn_on(today),
+            LimitPeriod::Monthly => account.withdrawn_in_month(today),
+        };
+        let currency = self.limit.currency();
+        let withdrawn = request.convert(withdrawn, currency)?;
+        let amount = request.convert(request.amount, currency)?;
+        if withdrawn.checked_add(amount)? > self.limit {
+            return Err(BankError::WithdrawalAmountExceeded {
+                account_id: request.account_id,
+                period: self.period,
+                limit: self.limit,
+                withdrawn,
+            });
+        }
+        Ok(())
+    }
+
+    fn validate(&self, currency: Currency) -> Result<(), BankError> {
+        ensure_rule_amount(self.limit, currency)
+    }
+}
+
+/// LargeCashApprovalRule 单笔现金收付(存取款、还贷、还信用卡和销户支付)达到门槛时需要另一名柜员复核
+pub struct LargeCashApprovalRule {
+    threshold: Money,
+}
+
+impl LargeCashApprovalRule {
+    pub fn new(threshold: Money) -> Self {
+        Self { threshold }
+    }
+}
+
+impl PolicyRule for LargeCashApprovalRule {
+    fn check(
+        &self,
+        request: &PolicyRequest,
+        _account: &BankAccount,
+        _today: NaiveDate,
+    ) -> Result<(), BankError> {
+        if !request.cash
+            || request.convert(request.amount, self.threshold.currency())? < self.threshold
+        {
+            return Ok(());
+        }
+        match request.approved_by {
+            Some(approver) if approver == request.teller_id => {
+                Err(BankError::InvalidApprover(approver))
+            }
+            Some(_) => Ok(()),
+            None => Err(BankError::ApprovalRequired {
+                amount: request.amount,
+                threshold: self.threshold,
+            }),
+        }
+    }
+
+    fn validate(&self, currency: Currency) -> Result<(), BankError> {
+        ensure_rule_amount(self.threshold, currency)
+    }
+}
+
+/// RuleConfig 配置文件中的一条规则
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum RuleConfig {
+    WithdrawalLimit {
+        period: LimitPeriod,
+        limit: Money,
+        #[serde(default)]
+        account_id: Option<usize>,
+    },
+    LargeCashApproval {
+        threshold: Money,
+    },
+}
+
+impl RuleConfig {
+    pub fn build(&self) -> Box<dyn PolicyRule> {
+        match self {
+            Self::WithdrawalLimit {
+                period,
+                limit,
+                account_id,
+            } => Box::new(WithdrawalLimitRule::new(*period, *limit, *account_id)),
+            Self::LargeCashApproval { threshold } => {
+                Box::new(LargeCashApprovalRule::new(*threshold))
+            }
+        }
+    }
+}
+
+/// PolicyConfig 策略配置文件(json), 例如
+/// {"rules": [{"rule": "withdrawal_limit", "period": "daily", "limit": {"amount": "5000", "currency": "CNY"}},
+///            {"rule": "large_cash_approval", "threshold": {"amount": "50000", "currency": "CNY"}}]}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyConfig {
+    pub rules: Vec<RuleConfig>,
+}
+
+impl PolicyConfig {
+    pub fn load(path: &Path) -> Result<Self, BankError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// PolicyEngine 策略引擎, 每笔现金收付和转出之前检查所有规则, 全部通过才能办理
+#[derive(Default)]
+pub struct PolicyEngine {
+    pub(crate) rules: Vec<Box<dyn PolicyRule>>,
+}
+
+impl PolicyEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: &PolicyConfig) -> Self {
+        Self {
+            rules: config.rules.iter().map(|r| r.build()).collect(),
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: Box<dyn PolicyRule>) {
+        self.rules.push(rule);
+    }
+
+    pub fn check(
+        &self,
+        request: &PolicyRequest,
+        account: &BankAccount,
+        today: NaiveDate,
+    ) -> Result<(), BankError> {
+        self.rules
+            .iter()
+            .try_for_each(|rule| rule.check(request, account, today))
+    }
+
+    pub fn validate(&self, currency: Currency) -> Result<(), BankError> {
+        self.rules
+            .iter()
+            .try_for_each(|rule| rule.validate(currency))
+    }
+}
@@ -0,0 +1,477 @@
+// 贷款、信用卡、收费标准、定期存款和基金
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use chrono::{Months, NaiveDate};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// Installment 还款计划中的一期
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Installment {
+    // 期数, 从1开始
+    pub number: u32,
+    pub due_date: NaiveDate,
+    // 本期应还 = 本金 + 利息
+    pub payment: Money,
+    pub principal: Money,
+    pub interest: Money,
+    // 本期还款后剩余本金
+    pub remaining_principal: Money,
+}
+
+/// Loan 贷款, 按固定利率等额本息还款
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Loan {
+    pub(crate) id: usize,
+    pub(crate) account_id: usize,
+    principal: Money,
+    annual_rate: Decimal,
+    term_months: u32,
+    // 还款计划
+    schedule: Vec<Installment>,
+    // 累计已还金额
+    pub(crate) repaid: Money,
+}
+
+impl Loan {
+    pub fn new(
+        id: usize,
+        account_id: usize,
+        principal: Money,
+        annual_rate: Decimal,
+        term_months: u32,
+        start_date: NaiveDate,
+    ) -> Result<Self, BankError> {
+        let schedule =
+            Self::amortization_schedule(principal, annual_rate, term_months, start_date)?;
+        Ok(Self {
+            id,
+            account_id,
+            principal,
+            annual_rate,
+            term_months,
+            schedule,
+            repaid: Money::zero(principal.currency()),
+        })
+    }
+
+    // 等额本息还款计划: 每期还款 = P * r / (1 - (1 + r)^-n), r 为月利率
+    // 每期利息按剩余本金计算并舍入到分, 最后一期还清剩余本金以消除舍入误差
+    pub fn amortization_schedule(
+        principal: Money,
+        annual_rate: Decimal,
+        term_months: u32,
+        start_date: NaiveDate,
+    ) -> Result<Vec<Installment>, BankError> {
+        ensure_positive(principal)?;
+        if term_months == 0 {
+            return Err(BankError::InvalidLoanTerm(term_months));
+        }
+        if annual_rate.is_sign_negative() {
+            return Err(BankError::InvalidRate(annual_rate));
+        }
+
+        let monthly_rate = annual_rate / Decimal::from(12);
+        let payment = if monthly_rate.is_zero() {
+            principal.checked_mul(Decimal::ONE / Decimal::from(term_months))?
+        } else {
+            let mut growth = Decimal::ONE;
+            for _ in 0..term_months {
+                growth = growth
+                    .checked_mul(Decimal::ONE + monthly_rate)
+                    .ok_or(MoneyError::Overflow)?;
+            }
+            principal.checked_mul(monthly_rate * growth / (growth - Decimal::ONE))?
+        }
+        .round();
+
+        let mut schedule = Vec::with_capacity(term_months as usize);
+        let mut remaining = principal;
+        for number in 1..=term_months {
+            let interest = remaining.checked_mul(monthly_rate)?.round();
+            let principal_part = if number == term_months {
+                remaining
+            } else {
+                payment.checked_sub(interest)?
+            };
+            remaining = remaining.checked_sub(principal_part)?;
+            schedule.push(Installment {
+                number,
+                due_date: start_date
+                    .checked_add_months(Months::new(number))
+                    .ok_or(BankError::InvalidLoanTerm(term_months))?,
+                payment: principal_part.checked_add(interest)?,
+                principal: principal_part,
+                interest,
+                remaining_principal: remaining,
+            });
+        }
+        Ok(schedule)
+    }
+
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_account_id(&self) -> usize {
+        self.account_id
+    }
+
+    pub fn get_principal(&self) -> Money {
+        self.principal
+    }
+
+    pub fn get_annual_rate(&self) -> Decimal {
+        self.annual_rate
+    }
+
+    pub fn get_term_months(&self) -> u32 {
+        self.term_months
+    }
+
+    pub fn get_schedule(&self) -> &[Installment] {
+        &self.schedule
+    }
+
+    pub fn get_repaid(&self) -> Money {
+        self.repaid
+    }
+
+    // 按还款计划还需偿还的总额
+    pub fn outstanding(&self) -> Result<Money, MoneyError> {
+        let mut total = Money::zero(self.principal.currency());
+        for installment in &self.schedule {
+            total = total.checked_add(installment.payment)?;
+        }
+        total.checked_sub(self.repaid)
+    }
+
+    // 截止 as_of 应还但未还的金额(逾期金额)
+    pub fn arrears(&self, as_of: NaiveDate) -> Result<Money, MoneyError> {
+        let mut due = Money::zero(self.principal.currency());
+        for installment in self.schedule.iter().filter(|i| i.due_date <= as_of) {
+            due = due.checked_add(installment.payment)?;
+        }
+        let arrears = due.checked_sub(self.repaid)?;
+        if arrears.is_negative() {
+            return Ok(Money::zero(arrears.currency()));
+        }
+        Ok(arrears)
+    }
+
+    // 把一笔还款拆分成(利息, 本金): 按还款计划逐期先还利息再还本金
+    pub(crate) fn split_repayment(&self, amount: Money) -> Result<(Money, Money), MoneyError> {
+        let zero = Money::zero(amount.currency());
+        let (mut interest, mut principal) = (zero, zero);
+        // 本次还款覆盖的区间为 [repaid, repaid + amount)
+        let start = self.repaid;
+        let end = self.repaid.checked_add(amount)?;
+        let mut cursor = zero;
+        for installment in &self.schedule {
+            for (part, is_interest) in
+                [(installment.interest, true), (installment.principal, false)]
+            {
+                let part_start = cursor;
+                cursor = cursor.checked_add(part)?;
+                let lo = if part_start > start {
+                    part_start
+                } else {
+                    start
+                };
+                let hi = if cursor < end { cursor } else { end };
+                if hi > lo {
+                    let covered = hi.checked_sub(lo)?;
+                    if is_interest {
+                        interest = interest.checked_add(covered)?;
+                    } else {
+                        principal = principal.checked_add(covered)?;
+                    }
+                }
+            }
+        }
+        Ok((interest, principal))
+    }
+}
+
+// 最低还款额为账单金额的 5%, 但不低于 100 个主币单位(账单金额更小时全额还款)
+pub(crate) const CARD_MINIMUM_PAYMENT_RATE: Decimal = Decimal::from_parts(5, 0, 0, false, 2);
+pub(crate) const CARD_MINIMUM_PAYMENT_FLOOR: i64 = 100;
+// 账单日后 25 天为还款日
+pub(crate) const CARD_GRACE_DAYS: i64 = 25;
+
+/// CreditCardStatement 信用卡月度账单
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreditCardStatement {
+    // 账单日
+    pub closing_date: NaiveDate,
+    pub previous_balance: Money,
+    pub purchases: Money,
+    pub payments: Money,
+    // 本期产生的滞纳金
+    pub late_fee: Money,
+    // 本期产生的循环利息
+    pub interest: Money,
+    pub closing_balance: Money,
+    // 最低还款额
+    pub minimum_due: Money,
+    // 还款日
+    pub due_date: NaiveDate,
+}
+
+/// CreditCard 信用卡, 欠款记录在总账的 LedgerAccount::CardReceivable 科目
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CreditCard {
+    pub(crate) id: usize,
+    pub(crate) account_id: usize,
+    pub(crate) credit_limit: Money,
+    // 循环信用的年利率
+    pub(crate) annual_rate: Decimal,
+    // 未按时还最低还款额时收取的滞纳金
+    pub(crate) late_fee: Money,
+    // 本账单周期内的消费
+    pub(crate) cycle_purchases: Money,
+    // 本账单周期内的还款(还款日期, 金额)
+    pub(crate) cycle_payments: Vec<(NaiveDate, Money)>,
+    pub(crate) statements: Vec<CreditCardStatement>,
+}
+
+impl CreditCard {
+    pub fn new(
+        id: usize,
+        account_id: usize,
+        credit_limit: Money,
+        annual_rate: Decimal,
+        late_fee: Money,
+    ) -> Self {
+        Self {
+            id,
+            account_id,
+            credit_limit,
+            annual_rate,
+            late_fee,
+            cycle_purchases: Money::zero(credit_limit.currency()),
+            cycle_payments: Vec::new(),
+            statements: Vec::new(),
+        }
+    }
+
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_account_id(&self) -> usize {
+        self.account_id
+    }
+
+    pub fn get_credit_limit(&self) -> Money {
+        self.credit_limit
+    }
+
+    pub fn get_statements(&self) -> &[CreditCardStatement] {
+        &self.statements
+    }
+
+    // 本周期内 until(含)之前的还款合计
+    pub(crate) fn payments_until(&self, until: Option<NaiveDate>) -> Result<Money, MoneyError> {
+        let mut total = Money::zero(self.credit_limit.currency());
+        for (date, amount) in &self.cycle_payments {
+            if until.is_none_or(|until| *date <= until) {
+                total = total.checked_add(*amount)?;
+            }
+        }
+        Ok(total)
+    }
+}
+
+/// FeeKind 费用类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeKind {
+    // 每月账户管理费
+    MonthlyMaintenance,
+    // 超过每月免费次数的取款
+    ExcessWithdrawal,
+    // 在开户分行以外办理存取款, 或跨分行转账
+    CrossBranch,
+    // 交易后账户透支
+    Overdraft,
+}
+
+impl fmt::Display for FeeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MonthlyMaintenance => write!(f, "monthly maintenance fee"),
+            Self::ExcessWithdrawal => write!(f, "excess withdrawal fee"),
+            Self::CrossBranch => write!(f, "cross-branch fee"),
+            Self::Overdraft => write!(f, "overdraft fee"),
+        }
+    }
+}
+
+/// FeeSchedule 收费标准, 从 json 配置文件加载, 没有配置金额的费用不收取
+/// 费用以记账币种收取, 外币账户不收费
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeSchedule {
+    pub monthly_maintenance: Option<Money>,
+    // 每月前 free_withdrawals 笔取款(含转出)免费, 之后每笔收取 withdrawal_fee
+    pub free_withdrawals: u32,
+    pub withdrawal_fee: Option<Money>,
+    pub cross_branch_fee: Option<Money>,
+    pub overdraft_fee: Option<Money>,
+}
+
+impl FeeSchedule {
+    pub fn load(path: &Path) -> Result<Self, BankError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn fee(&self, kind: FeeKind) -> Option<Money> {
+        match kind {
+            FeeKind::MonthlyMaintenance => self.monthly_maintenance,
+            FeeKind::ExcessWithdrawal => self.withdrawal_fee,
+            FeeKind::CrossBranch => self.cross_branch_fee,
+            FeeKind::Overdraft => self.overdraft_fee,
+        }
+    }
+}
+
+/// TermDepositProduct 定期存款产品: 期限、年利率和提前支取罚金
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TermDepositProduct {
+    pub term_months: u32,
+    pub annual_rate: Decimal,
+    // 提前支取时按本金的这个比例扣罚金, 最多扣完已产生的利息, 不损失本金
+    pub early_withdrawal_penalty: Decimal,
+}
+
+/// TermDepositStatus 定期存款状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TermDepositStatus {
+    Active,
+    Matured,
+    WithdrawnEarly,
+}
+
+/// TermDeposit 定期存款, 按单利计息, 到期一次还本付息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TermDeposit {
+    pub(crate) id: usize,
+    pub(crate) account_id: usize,
+    pub(crate) principal: Money,
+    pub(crate) product: TermDepositProduct,
+    pub(crate) opened_on: NaiveDate,
+    pub(crate) maturity_date: NaiveDate,
+    pub(crate) status: TermDepositStatus,
+}
+
+impl TermDeposit {
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
+    pub fn get_account_id(&self) -> usize {
+        self.account_id
+    }
+
+    pub fn get_principal(&self) -> Money {
+        self.principal
+    }
+
+    pub fn get_maturity_date(&self) -> NaiveDate {
+        self.maturity_date
+    }
+
+    pub fn get_status(&self) -> TermDepositStatus {
+        self.status
+    }
+
+    // 从开立日到 date(不超过到期日)按天计算的单利
+    pub fn interest_to(&self, date: NaiveDate) -> Result<Money, MoneyError> {
+        let days = (date.min(self.maturity_date) - self.opened_on)
+            .num_days()
+            .max(0);
+        let factor = self.product.annual_rate * Decimal::from(days) / Decimal::from(365);
+        Ok(self.principal.checked_mul(factor)?.round())
+    }
+
+    // 提前支取的利息: 已产生的利息扣除罚金, 不低于0
+    pub fn early_interest(&self, date: NaiveDate) -> Result<Money, MoneyError> {
+        let interest = self.interest_to(date)?;
+        let penalty = self
+            .principal
+            .checked_mul(self.product.early_withdrawal_penalty)?
+            .round();
+        let net = interest.checked_sub(penalty)?;
+        Ok(if net.is_negative() {
+            Money::zero(net.currency())
+        } else {
+            net
+        })
+    }
+}
+
+/// Fund 基金及其净值历史
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fund {
+    // 日期 -> 单位净值
+    pub(crate) navs: BTreeMap<NaiveDate, Decimal>,
+}
+
+impl Fund {
+    // 不晚于 date 的最近一个净值
+    pub fn nav_on(&self, date: NaiveDate) -> Option<(NaiveDate, Decimal)> {
+        self.navs
+            .range(..=date)
+            .next_back()
+            .map(|(date, nav)| (*date, *nav))
+    }
+}
+
+/// NavRecord 净值文件中的一行: 基金代码, 日期, 单位净值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NavRecord {
+    pub fund_code: String,
+    pub date: NaiveDate,
+    pub nav: Decimal,
+}
+
+impl NavRecord {
+    // 读取 csv 净值文件, 第一行是表头 fund_code,date,nav
+    pub fn load_csv(path: &Path) -> Result<Vec<Self>, BankError> {
+        let data = fs::read_to_string(path)?;
+        let mut records = Vec::new();
+        for (idx, line) in data.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = BankError::InvalidNavRecord { line: idx + 1 };
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [fund_code, date, nav] = fields[..] else {
+                return Err(invalid);
+            };
+            let date = date.parse::<NaiveDate>().map_err(|_| invalid.clone())?;
+            let nav = nav.parse::<Decimal>().map_err(|_| invalid.clone())?;
+            if fund_code.is_empty() || nav <= Decimal::ZERO {
+                return Err(invalid);
+            }
+            records.push(Self {
+                fund_code: fund_code.to_string(),
+                date,
+                nav,
+            });
+        }
+        Ok(records)
+    }
+}
+
+/// FundHolding 账户持有的基金份额
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FundHolding {
+    pub account_id: usize,
+    pub fund_code: String,
+    pub units: Decimal,
+}
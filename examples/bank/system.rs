@@ -0,0 +1,3426 @@
+// BankSystem: 账户、总账和各项业务的办理
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs, mem,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use super::*;
+
+pub struct BankSystem {
+    // 银行的记账币种
+    currency: Currency,
+    customers: Vec<Customer>,
+    // 证件 -> 客户ID
+    customers_by_document: HashMap<IdentityDocument, usize>,
+    // 账号 -> 账户, 销户的账户保留在这里
+    accounts: BTreeMap<usize, BankAccount>,
+    account_ids: AccountIdAllocator,
+    // 分行ID -> 分行地址
+    pub(crate) branches: BTreeMap<usize, String>,
+    transactions: Vec<Box<dyn TransactionDescription>>,
+    // 下一笔交易的ID
+    next_transaction_id: u64,
+    // 总账, 客户余额、分行现金和总部金库都由分录推导
+    ledger: Ledger,
+    loans: Vec<Loan>,
+    credit_cards: Vec<CreditCard>,
+    term_deposits: Vec<TermDeposit>,
+    // 基金代码 -> 基金净值历史
+    funds: BTreeMap<String, Fund>,
+    fund_holdings: Vec<FundHolding>,
+    // 外币 -> 以记账币种报价的牌价
+    fx_rates: BTreeMap<Currency, FxRate>,
+    // 收费标准, 影响账务所以写入日志和快照(交易策略不影响账务, 不持久化)
+    fees: FeeSchedule,
+    // 分行日终结算报告, 按结算顺序记录
+    settlements: Vec<SettlementReport>,
+    replenishment: Replenishment,
+    // 被冲正的交易ID -> 冲正交易ID
+    reversals: BTreeMap<u64, u64>,
+    // 交易前检查的策略规则, 属于配置而不是状态, 不写入快照
+    policy: PolicyEngine,
+    // (分行ID, 柜员ID) -> 已签到柜员的角色, 柜员ID在分行内唯一; 柜员重新签到即可恢复, 不写入快照
+    staff_on_duty: HashMap<(usize, usize), TellerRole>,
+    // 被拒绝的操作和登录失败记录
+    audit_events: Vec<AuditEvent>,
+    // 交易和审计事件的哈希链
+    audit_log: AuditLog,
+    // 本次操作产生、还没有追加到哈希链的审计记录
+    audit_pending: Vec<AuditRecord>,
+    // 正在执行的操作开始(或上一次写入日志)时的状态
+    checkpoint: Option<Checkpoint>,
+    // 订阅交易的监听者, 例如反洗钱监控
+    listeners: Vec<Arc<Mutex<dyn TransactionListener>>>,
+    // 已经通知过监听者的交易数, 交易所在的操作写入日志后才通知
+    notified: usize,
+    clock: Box<dyn Clock>,
+    // 挂载的持久化日志, 为空时只在内存中运行
+    pub(crate) journal: Option<Journal>,
+    // 重放日志时使用记录里的时间, 而不是时钟的当前时间
+    replay_time: Option<DateTime<Utc>>,
+}
+
+impl BankSystem {
+    pub fn new(currency: Currency) -> Self {
+        Self::with_clock(currency, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(currency: Currency, clock: Box<dyn Clock>) -> Self {
+        Self {
+            currency,
+            customers: Vec::new(),
+            customers_by_document: HashMap::new(),
+            accounts: BTreeMap::new(),
+            account_ids: AccountIdAllocator::new(),
+            branches: BTreeMap::new(),
+            transactions: Vec::new(),
+            next_transaction_id: 1,
+            ledger: Ledger::new(),
+            loans: Vec::new(),
+            credit_cards: Vec::new(),
+            term_deposits: Vec::new(),
+            funds: BTreeMap::new(),
+            fund_holdings: Vec::new(),
+            fx_rates: BTreeMap::new(),
+            fees: FeeSchedule::default(),
+            settlements: Vec::new(),
+            replenishment: Replenishment::default(),
+            reversals: BTreeMap::new(),
+            policy: PolicyEngine::new(),
+            staff_on_duty: HashMap::new(),
+            audit_events: Vec::new(),
+            audit_log: AuditLog::default(),
+            audit_pending: Vec::new(),
+            checkpoint: None,
+            listeners: Vec::new(),
+            notified: 0,
+            clock,
+            journal: None,
+            replay_time: None,
+        }
+    }
+
+    // 从目录恢复银行系统: 先加载快照, 再重放快照之后的日志记录
+    // 目录不存在时创建一个新的空系统, 之后的每个成功操作都会追加到日志
+    pub fn open(dir: &Path, currency: Currency, clock: Box<dyn Clock>) -> Result<Self, BankError> {
+        fs::create_dir_all(dir)?;
+        let mut system = Self::with_clock(currency, clock);
+        let mut snapshot_seq = 0;
+        if let Some(snapshot) = Snapshot::load(dir)? {
+            snapshot_seq = snapshot.seq;
+            system.restore(snapshot)?;
+        }
+        let (journal, records) = Journal::open(dir)?;
+        for record in records.into_iter().filter(|r| r.seq > snapshot_seq) {
+            system.replay_time = Some(record.timestamp);
+            let result = system.apply(record.command);
+            system.replay_time = None;
+            result?;
+        }
+        system.journal = Some(journal);
+        Ok(system)
+    }
+
+    // 把当前状态(包括交易明细)写成快照, 重启时只需要重放快照之后的日志
+    pub fn snapshot(&self) -> Result<(), BankError> {
+        let journal = self
+            .journal
+            .as_ref()
+            .ok_or_else(|| BankError::Storage("no storage attached".to_string()))?;
+        let snapshot = SnapshotRef {
+            seq: journal.next_seq - 1,
+            currency: self.currency,
+            branches: &self.branches,
+            customers: &self.customers,
+            accounts: &self.accounts,
+            account_ids: &self.account_ids,
+            ledger: self.ledger.get_entries(),
+            loans: &self.loans,
+            credit_cards: &self.credit_cards,
+            settlements: &self.settlements,
+            replenishment: &self.replenishment,
+            reversals: &self.reversals,
+            term_deposits: &self.term_deposits,
+            funds: &self.funds,
+            fund_holdings: &self.fund_holdings,
+            fx_rates: &self.fx_rates,
+            fees: &self.fees,
+            audit_events: &self.audit_events,
+            audit_log: &self.audit_log,
+            transactions: self.transactions.iter().map(|ts| ts.stored()).collect(),
+            next_transaction_id: self.next_transaction_id,
+        };
+        write_snapshot(&journal.dir, &snapshot)
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) -> Result<(), BankError> {
+        if snapshot.currency != self.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, snapshot.currency).into());
+        }
+        self.branches = snapshot.branches;
+        self.customers_by_document = snapshot
+            .customers
+            .iter()
+            .map(|c| (c.document.clone(), c.id))
+            .collect();
+        self.customers = snapshot.customers;
+        self.accounts = snapshot.accounts;
+        self.account_ids = snapshot.account_ids;
+        self.ledger = Ledger::restore(snapshot.ledger)?;
+        self.loans = snapshot.loans;
+        self.credit_cards = snapshot.credit_cards;
+        self.settlements = snapshot.settlements;
+        self.replenishment = snapshot.replenishment;
+        self.reversals = snapshot.reversals;
+        self.term_deposits = snapshot.term_deposits;
+        self.funds = snapshot.funds;
+        self.fund_holdings = snapshot.fund_holdings;
+        self.fx_rates = snapshot.fx_rates;
+        self.fees = snapshot.fees;
+        self.audit_events = snapshot.audit_events;
+        self.audit_log = snapshot.audit_log;
+        self.transactions = snapshot
+            .transactions
+            .into_iter()
+            .map(StoredTransaction::into_boxed)
+            .collect();
+        self.next_transaction_id = snapshot.next_transaction_id;
+        self.notified = self.transactions.len();
+        Ok(())
+    }
+
+    // 重放一条日志命令, 走和正常操作相同的校验和记账逻辑
+    fn apply(&mut self, command: Command) -> Result<(), BankError> {
+        match command {
+            Command::OpenBranch {
+                address,
+                initial_funds,
+            } => self
+                .open_branch(SYSTEM_TELLER_ID, address, initial_funds)
+                .map(|_| ()),
+            Command::InjectCapital { account, amount } => self.record_capital(account, amount),
+            Command::RegisterCustomer {
+                name,
+                date_of_birth,
+                address,
+                document,
+            } => self
+                .register_customer(
+                    HEADQUARTERS_ID,
+                    SYSTEM_TELLER_ID,
+                    name,
+                    date_of_birth,
+                    address,
+                    document,
+                )
+                .map(|_| ()),
+            Command::OpenAccount {
+                owners,
+                kind,
+                branch_id,
+                teller_id,
+                currency,
+            } => {
+                let currency = currency.unwrap_or(self.currency);
+                self.open_account_in(owners, kind, currency, branch_id, teller_id)
+                    .map(|_| ())
+            }
+            Command::AddAccountOwner {
+                account_id,
+                customer_id,
+            } => self.add_account_owner(account_id, HEADQUARTERS_ID, SYSTEM_TELLER_ID, customer_id),
+            Command::FreezeAccount {
+                account_id,
+                branch_id,
+                teller_id,
+                reason,
+            } => self.freeze_account(account_id, branch_id, teller_id, reason),
+            Command::UnfreezeAccount {
+                account_id,
+                branch_id,
+                teller_id,
+                reason,
+            } => self.unfreeze_account(account_id, branch_id, teller_id, reason),
+            Command::MarkDormantAccounts {
+                as_of,
+                inactive_days,
+            } => self
+                .mark_dormant_accounts(SYSTEM_TELLER_ID, as_of, inactive_days)
+                .map(|_| ()),
+            Command::ReactivateAccount {
+                account_id,
+                branch_id,
+                teller_id,
+            } => self.reactivate_account(account_id, branch_id, teller_id),
+            Command::CloseAccount {
+                account_id,
+                branch_id,
+                teller_id,
+                approved_by,
+            } => self
+                .close_account(account_id, branch_id, teller_id, approved_by)
+                .map(|_| ()),
+            Command::OpenTermDeposit {
+                account_id,
+                branch_id,
+                teller_id,
+                principal,
+                product,
+            } => self
+                .open_term_deposit(account_id, branch_id, teller_id, principal, product)
+                .map(|_| ()),
+            Command::WithdrawTermDeposit {
+                deposit_id,
+                branch_id,
+                teller_id,
+            } => self
+                .withdraw_term_deposit(deposit_id, branch_id, teller_id)
+                .map(|_| ()),
+            Command::MatureTermDeposits { as_of } => self.mature_term_deposits(as_of).map(|_| ()),
+            Command::ImportFundNavs { records } => self.import_fund_navs(records),
+            Command::BuyFund {
+                account_id,
+                branch_id,
+                teller_id,
+                fund_code,
+                amount,
+            } => self
+                .buy_fund(account_id, branch_id, teller_id, fund_code, amount)
+                .map(|_| ()),
+            Command::RedeemFund {
+                account_id,
+                branch_id,
+                teller_id,
+                fund_code,
+                units,
+            } => self
+                .redeem_fund(account_id, branch_id, teller_id, fund_code, units)
+                .map(|_| ()),
+            Command::ImportFxRates { rates } => self.import_fx_rates(rates),
+            Command::SetFeeSchedule { schedule } => {
+                self.set_fee_schedule(SYSTEM_TELLER_ID, schedule)
+            }
+            Command::RecordAuditEvent { event } => self.push_audit_event(event),
+            Command::ChargeMonthlyFees { as_of } => self.charge_monthly_fees(as_of).map(|_| ()),
+            Command::ExchangeCurrency {
+                from_account_id,
+                to_account_id,
+                branch_id,
+                teller_id,
+                amount,
+            } => self
+                .exchange_currency(from_account_id, to_account_id, branch_id, teller_id, amount)
+                .map(|_| ()),
+            Command::ReverseTransaction {
+                original_id,
+                original_kind,
+                account_id,
+                counterparty_account_id,
+                branch_id,
+                supervisor_id,
+                amount,
+                reason,
+            } => {
+                let ts = Reversal::new(
+                    original_id,
+                    original_kind,
+                    account_id,
+                    counterparty_account_id,
+                    supervisor_id,
+                    amount,
+                    reason,
+                );
+                self.post_reversal(ts, branch_id).map(|_| ())
+            }
+            Command::Deposit {
+                account_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            } => self.deposit(account_id, branch_id, teller_id, amount, approved_by),
+            Command::Withdraw {
+                account_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            } => self.withdraw(account_id, branch_id, teller_id, amount, approved_by),
+            Command::Transfer {
+                from_account_id,
+                to_account_id,
+                branch_id,
+                teller_id,
+                amount,
+            } => self.transfer(from_account_id, to_account_id, branch_id, teller_id, amount),
+            // 以下操作的经办人不记入日志, 重放时不检查授权
+            Command::CollectCash { branch_id, amount } => {
+                self.collect_cash(branch_id, SYSTEM_TELLER_ID, amount)
+            }
+            Command::SettleBranch {
+                branch_id,
+                counted_cash,
+                ratio,
+            } => self
+                .settle_branch(branch_id, SYSTEM_TELLER_ID, counted_cash, ratio)
+                .map(|_| ()),
+            Command::SetCashLimits { branch_id, limits } => {
+                self.set_cash_limits(SYSTEM_TELLER_ID, branch_id, limits.min, limits.max)
+            }
+            Command::RequestReplenishment { branch_id, amount } => self
+                .request_replenishment(branch_id, SYSTEM_TELLER_ID, amount)
+                .map(|_| ()),
+            Command::FulfillReplenishment { request_id } => self
+                .fulfill_replenishment(SYSTEM_TELLER_ID, request_id)
+                .map(|_| ()),
+            Command::ScheduleReplenishment { at } => {
+                self.schedule_replenishment(SYSTEM_TELLER_ID, at)
+            }
+            Command::CompleteScheduledReplenishment => self.complete_scheduled_replenishment(),
+            Command::OriginateLoan {
+                account_id,
+                branch_id,
+                teller_id,
+                principal,
+                annual_rate,
+                term_months,
+            } => self
+                .originate_loan(
+                    account_id,
+                    branch_id,
+                    teller_id,
+                    principal,
+                    annual_rate,
+                    term_months,
+                )
+                .map(|_| ()),
+            Command::RepayLoan {
+                loan_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            } => self.repay_loan(loan_id, branch_id, teller_id, amount, approved_by),
+            Command::IssueCreditCard {
+                account_id,
+                branch_id,
+                teller_id,
+                credit_limit,
+                annual_rate,
+                late_fee,
+            } => self
+                .issue_credit_card(
+                    account_id,
+                    branch_id,
+                    teller_id,
+                    credit_limit,
+                    annual_rate,
+                    late_fee,
+                )
+                .map(|_| ()),
+            Command::ChargeCreditCard {
+                card_id,
+                merchant,
+                amount,
+            } => self.charge_credit_card(SYSTEM_TELLER_ID, card_id, merchant, amount),
+            Command::PayCreditCard {
+                card_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            } => self.pay_credit_card(card_id, branch_id, teller_id, amount, approved_by),
+            Command::GenerateCardStatement { card_id } => self
+                .generate_card_statement(SYSTEM_TELLER_ID, card_id)
+                .map(|_| ()),
+            Command::AccrueInterest { as_of } => self.accrue_interest(as_of).map(|_| ()),
+        }
+    }
+
+    // 操作成功后把本次操作的审计记录追加到哈希链, 再追加到日志; 没有挂载存储或正在重放时不写日志
+    // 写入日志后的状态已经持久化, 之后的步骤失败时只恢复到这里
+    fn append_journal(&mut self, command: Command) -> Result<(), BankError> {
+        let now = self.now();
+        // 审计记录只在这里盖一次时间, 和日志记录的时间相同, 重放时才能得到同一条哈希链
+        for record in mem::take(&mut self.audit_pending) {
+            self.audit_log.append(now, record)?;
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.append(now, command)?;
+        }
+        // 交易已经持久化, 之后不会再撤销, 这时才通知监听者
+        for ts in &self.transactions[self.notified..] {
+            for listener in &self.listeners {
+                listener.lock().unwrap().on_transaction(ts.as_ref(), self);
+            }
+        }
+        self.notified = self.transactions.len();
+        if self.checkpoint.is_some() {
+            self.checkpoint = Some(self.checkpoint());
+        }
+        Ok(())
+    }
+
+    // 公开的修改操作都在这里执行: 任何一步失败(包括写日志失败)都恢复到操作开始时的状态,
+    // 内存中不会留下没有写入日志的修改; 嵌套的操作由最外层负责恢复
+    fn atomically<T>(
+        &mut self,
+        op: impl FnOnce(&mut Self) -> Result<T, BankError>,
+    ) -> Result<T, BankError> {
+        // 重放日志失败时整个恢复过程中止, 不需要保存状态
+        if self.replay_time.is_some() || self.checkpoint.is_some() {
+            return op(self);
+        }
+        self.checkpoint = Some(self.checkpoint());
+        let result = op(self);
+        if let Some(checkpoint) = self.checkpoint.take() {
+            if result.is_err() {
+                self.rollback(&checkpoint);
+            }
+        }
+        result
+    }
+
+    // 批处理中的一项: 失败时只撤销这一项, 恢复到上一次写入日志后的状态, 批处理可以继续
+    fn attempt<T>(
+        &mut self,
+        op: impl FnOnce(&mut Self) -> Result<T, BankError>,
+    ) -> Result<T, BankError> {
+        let result = op(self);
+        if result.is_err() {
+            if let Some(checkpoint) = self.checkpoint.take() {
+                self.rollback(&checkpoint);
+                self.checkpoint = Some(checkpoint);
+            }
+        }
+        result
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            customers: self.customers.clone(),
+            customers_by_document: self.customers_by_document.clone(),
+            accounts: self.accounts.clone(),
+            account_ids: self.account_ids.clone(),
+            branches: self.branches.clone(),
+            transactions: self.transactions.len(),
+            next_transaction_id: self.next_transaction_id,
+            ledger_entries: self.ledger.entries.len(),
+            ledger_balances: self.ledger.balances.clone(),
+            loans: self.loans.clone(),
+            credit_cards: self.credit_cards.clone(),
+            term_deposits: self.term_deposits.clone(),
+            funds: self.funds.clone(),
+            fund_holdings: self.fund_holdings.clone(),
+            fx_rates: self.fx_rates.clone(),
+            fees: self.fees.clone(),
+            settlements: self.settlements.clone(),
+            replenishment: self.replenishment.clone(),
+            reversals: self.reversals.clone(),
+            audit_events: self.audit_events.len(),
+            audit_log: self.audit_log.get_entries().len(),
+        }
+    }
+
+    fn rollback(&mut self, checkpoint: &Checkpoint) {
+        self.customers = checkpoint.customers.clone();
+        self.customers_by_document = checkpoint.customers_by_document.clone();
+        self.accounts = checkpoint.accounts.clone();
+        self.account_ids = checkpoint.account_ids.clone();
+        self.branches = checkpoint.branches.clone();
+        self.transactions.truncate(checkpoint.transactions);
+        self.next_transaction_id = checkpoint.next_transaction_id;
+        self.ledger.rollback(
+            checkpoint.ledger_entries,
+            checkpoint.ledger_balances.clone(),
+        );
+        self.loans = checkpoint.loans.clone();
+        self.credit_cards = checkpoint.credit_cards.clone();
+        self.term_deposits = checkpoint.term_deposits.clone();
+        self.funds = checkpoint.funds.clone();
+        self.fund_holdings = checkpoint.fund_holdings.clone();
+        self.fx_rates = checkpoint.fx_rates.clone();
+        self.fees = checkpoint.fees.clone();
+        self.settlements = checkpoint.settlements.clone();
+        self.replenishment = checkpoint.replenishment.clone();
+        self.reversals = checkpoint.reversals.clone();
+        self.audit_events.truncate(checkpoint.audit_events);
+        self.audit_log.truncate(checkpoint.audit_log);
+        self.audit_pending.clear();
+    }
+
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        self.replay_time.unwrap_or_else(|| self.clock.now())
+    }
+
+    fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+
+    pub fn get_currency(&self) -> Currency {
+        self.currency
+    }
+
+    // 按账号顺序遍历所有账户(包括已销户的)
+    pub fn get_accounts(&self) -> impl Iterator<Item = &BankAccount> {
+        self.accounts.values()
+    }
+
+    pub fn get_customer(&self, customer_id: usize) -> Option<&Customer> {
+        self.customers.get(customer_id.checked_sub(1)?)
+    }
+
+    pub fn find_customer_by_document(&self, document: &IdentityDocument) -> Option<&Customer> {
+        self.get_customer(*self.customers_by_document.get(document)?)
+    }
+
+    // 按姓名查找客户, 忽略大小写, 可能有多个同名客户
+    pub fn find_customers_by_name(&self, name: &str) -> Vec<&Customer> {
+        let name = name.to_lowercase();
+        self.customers
+            .iter()
+            .filter(|c| c.name.to_lowercase() == name)
+            .collect()
+    }
+
+    // 客户拥有(或共同拥有)的账户
+    pub fn get_customer_accounts(&self, customer_id: usize) -> Vec<&BankAccount> {
+        self.get_customer(customer_id)
+            .map(|c| {
+                c.accounts
+                    .iter()
+                    .filter_map(|account_id| self.get_account(*account_id))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_account(&self, account_id: usize) -> Option<&BankAccount> {
+        self.accounts.get(&account_id)
+    }
+
+    // 按账号查找账户: 校验位不对的账号与不存在的账号分开报错
+    fn find_account(&self, account_id: usize) -> Result<&BankAccount, BankError> {
+        if !AccountIdAllocator::is_valid(account_id) {
+            return Err(BankError::InvalidAccountNumber(account_id));
+        }
+        self.get_account(account_id)
+            .ok_or(BankError::AccountNotFound(account_id))
+    }
+
+    fn get_account_mut(&mut self, account_id: usize) -> Option<&mut BankAccount> {
+        self.accounts.get_mut(&account_id)
+    }
+
+    pub fn get_transactions(&self) -> &Vec<Box<dyn TransactionDescription>> {
+        &self.transactions
+    }
+
+    pub fn get_transaction(&self, id: u64) -> Option<&dyn TransactionDescription> {
+        // 交易按ID递增的顺序记录, 可以二分查找
+        let idx = self
+            .transactions
+            .binary_search_by_key(&id, |t| t.get_transaction().id)
+            .ok()?;
+        Some(self.transactions[idx].as_ref())
+    }
+
+    // 冲正一笔存款、取款或转账: 按原交易的反方向记账, 原交易不变, 返回冲正交易的ID
+    // 冲正要在原交易的分行由主管授权办理, 主管身份由分行核实
+    pub fn reverse_transaction(
+        &mut self,
+        transaction_id: u64,
+        branch_id: usize,
+        supervisor_id: usize,
+        reason: String,
+    ) -> Result<u64, BankError> {
+        self.atomically(|system| {
+            system.authorize(supervisor_id, branch_id, Operation::Reverse)?;
+            let original = system
+                .get_transaction(transaction_id)
+                .ok_or(BankError::TransactionNotFound(transaction_id))?;
+            let transaction = original.get_transaction();
+            let kind = original.get_kind();
+            if transaction.branch_id != Some(branch_id)
+                || !matches!(
+                    kind,
+                    TransactionKind::Deposit
+                        | TransactionKind::Withdrawal
+                        | TransactionKind::Transfer
+                )
+            {
+                return Err(BankError::TransactionNotReversible(transaction_id));
+            }
+            let account_id = transaction.account_id;
+            let effect = original
+                .get_account_effect(account_id)
+                .ok_or(BankError::TransactionNotReversible(transaction_id))?;
+            let ts = Reversal::new(
+                transaction_id,
+                kind,
+                account_id,
+                original.get_counterparty_account_id(),
+                supervisor_id,
+                if effect.is_negative() {
+                    -effect
+                } else {
+                    effect
+                },
+                reason,
+            );
+            system.post_reversal(ts, branch_id)
+        })
+    }
+
+    fn post_reversal(&mut self, ts: Reversal, branch_id: usize) -> Result<u64, BankError> {
+        let original_id = ts.original_id;
+        if self.reversals.contains_key(&original_id) {
+            return Err(BankError::TransactionAlreadyReversed(original_id));
+        }
+        let account_id = ts.transaction.account_id;
+        let amount = ts.amount;
+        let original_date = self
+            .get_transaction(original_id)
+            .ok_or(BankError::TransactionNotFound(original_id))?
+            .get_transaction()
+            .timestamp
+            .date_naive();
+        // 冲正后余额减少的账户要有足够的可用余额, 冲正存款还要求分行有足够的现金
+        let (debit, credit) = match (ts.original_kind, ts.counterparty_account_id) {
+            (TransactionKind::Deposit, _) => (
+                LedgerAccount::Customer(account_id),
+                LedgerAccount::BranchCash(branch_id),
+            ),
+            (TransactionKind::Withdrawal, _) => (
+                LedgerAccount::BranchCash(branch_id),
+                LedgerAccount::Customer(account_id),
+            ),
+            (TransactionKind::Transfer, Some(to_account_id)) => (
+                LedgerAccount::Customer(to_account_id),
+                LedgerAccount::Customer(account_id),
+            ),
+            _ => return Err(BankError::TransactionNotReversible(original_id)),
+        };
+        for ledger_account in [debit, credit] {
+            if let LedgerAccount::Customer(id) = ledger_account {
+                if self.get_account_status(id)? == AccountStatus::Closed {
+                    return Err(BankError::AccountClosed(id));
+                }
+            }
+        }
+        if let LedgerAccount::Customer(id) = debit {
+            let available = self.get_available_balance(id)?;
+            if available.checked_sub(amount)?.is_negative() {
+                return Err(BankError::InsufficientFunds {
+                    account_id: id,
+                    balance: self.get_balance(id)?,
+                    requested: amount,
+                });
+            }
+        }
+        if credit == LedgerAccount::BranchCash(branch_id) {
+            let cash_on_hand = self.get_branch_cash(branch_id);
+            if cash_on_hand.checked_sub(amount)?.is_negative() {
+                return Err(BankError::BranchCashShortfall {
+                    cash_on_hand,
+                    requested: amount,
+                });
+            }
+        }
+
+        let command = Command::ReverseTransaction {
+            original_id,
+            original_kind: ts.original_kind,
+            account_id,
+            counterparty_account_id: ts.counterparty_account_id,
+            branch_id,
+            supervisor_id: ts.transaction.teller_id,
+            amount,
+            reason: ts.reason.clone(),
+        };
+        self.ledger
+            .transfer(ts.get_transaction_description(), debit, credit, amount)?;
+        let supervisor_id = ts.transaction.teller_id;
+        let original_kind = ts.original_kind;
+        let reason = ts.reason.clone();
+        let id = self.record_transaction(ts, Some(branch_id))?;
+        self.reversals.insert(original_id, id);
+        // 取款和转出计入的次数和限额一并恢复, 以免冲正后客户仍被限额或超次收费
+        if original_kind != TransactionKind::Deposit {
+            self.get_account_mut(account_id)
+                .ok_or(BankError::AccountNotFound(account_id))?
+                .undo_withdrawal(original_date, amount)?;
+        }
+        self.refund_fees(original_id, branch_id, supervisor_id, &reason)?;
+        self.append_journal(command)?;
+        Ok(id)
+    }
+
+    // 退还原交易引起的手续费: 借 手续费收入, 贷 客户存款, 每笔费用记一笔冲正
+    fn refund_fees(
+        &mut self,
+        original_id: u64,
+        branch_id: usize,
+        supervisor_id: usize,
+        reason: &str,
+    ) -> Result<(), BankError> {
+        let fees: Vec<Reversal> = self
+            .transactions
+            .iter()
+            .filter_map(|t| match t.stored() {
+                StoredTransactionRef::FeeCharge(fee)
+                    if fee.related_transaction_id == Some(original_id) =>
+                {
+                    Some(Reversal::new(
+                        fee.transaction.id,
+                        fee.get_kind(),
+                        fee.transaction.account_id,
+                        None,
+                        supervisor_id,
+                        fee.amount,
+                        reason.to_string(),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        for ts in fees {
+            let fee_id = ts.original_id;
+            self.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::FeeIncome,
+                LedgerAccount::Customer(ts.transaction.account_id),
+                ts.amount,
+            )?;
+            let id = self.record_transaction(ts, Some(branch_id))?;
+            self.reversals.insert(fee_id, id);
+        }
+        Ok(())
+    }
+
+    // 交易被冲正时返回冲正交易的ID
+    pub fn get_reversal_of(&self, transaction_id: u64) -> Option<u64> {
+        self.reversals.get(&transaction_id).copied()
+    }
+
+    // 时间在 [start, end) 区间内的交易
+    pub fn get_transactions_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<&dyn TransactionDescription> {
+        let query = TransactionQuery::new().since(start).until(end);
+        self.query_transactions(&query).transactions
+    }
+
+    // 某家分行各柜员的业绩, 按柜员ID排序, 不含系统批处理
+    pub fn get_teller_stats(&self, branch_id: usize) -> Vec<TellerStats> {
+        let mut stats: BTreeMap<usize, (TellerStats, HashSet<usize>)> = BTreeMap::new();
+        for ts in self.get_branch_transactions(branch_id) {
+            let transaction = ts.get_transaction();
+            if transaction.teller_id == SYSTEM_TELLER_ID {
+                continue;
+            }
+            let (stat, customers) = stats.entry(transaction.teller_id).or_insert_with(|| {
+                let stat = TellerStats {
+                    teller_id: transaction.teller_id,
+                    transactions: 0,
+                    customers_served: 0,
+                    by_kind: HashMap::new(),
+                    first_at: None,
+                    last_at: None,
+                };
+                (stat, HashSet::new())
+            });
+            stat.transactions += 1;
+            *stat.by_kind.entry(ts.get_kind()).or_insert(0) += 1;
+            stat.first_at = stat.first_at.or(Some(transaction.timestamp));
+            stat.last_at = Some(transaction.timestamp);
+            if let Some(account) = self.get_account(transaction.account_id) {
+                customers.extend(account.owners.iter().copied());
+            }
+        }
+        stats
+            .into_values()
+            .map(|(mut stat, customers)| {
+                stat.customers_served = customers.len();
+                stat
+            })
+            .collect()
+    }
+
+    // 某家分行办理的交易, 用于分行对账
+    pub fn get_branch_transactions(&self, branch_id: usize) -> Vec<&dyn TransactionDescription> {
+        let query = TransactionQuery::new().branch(branch_id);
+        self.query_transactions(&query).transactions
+    }
+
+    // 按条件查询交易, 结果按交易ID排序并分页
+    pub fn query_transactions(&self, query: &TransactionQuery) -> TransactionPage<'_> {
+        let matched: Vec<&dyn TransactionDescription> = self
+            .transactions
+            .iter()
+            .map(|t| t.as_ref())
+            .filter(|t| query.matches(*t))
+            .collect();
+        let total = matched.len();
+        let transactions = matched
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+        TransactionPage {
+            transactions,
+            total,
+        }
+    }
+
+    // 生成账户在 [start, end) 期间的对账单, 期初余额由期初之前的交易累计得到
+    pub fn account_statement(
+        &self,
+        account_id: usize,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<AccountStatement, BankError> {
+        let account = self.find_account(account_id)?;
+        let mut balance = Money::zero(account.currency);
+        let mut opening_balance = balance;
+        let mut lines = Vec::new();
+        for ts in &self.transactions {
+            let transaction = ts.get_transaction();
+            if transaction.timestamp >= end {
+                break;
+            }
+            let Some(amount) = ts.get_account_effect(account_id) else {
+                continue;
+            };
+            balance = balance.checked_add(amount)?;
+            if transaction.timestamp < start {
+                opening_balance = balance;
+                continue;
+            }
+            lines.push(StatementLine {
+                transaction_id: transaction.id,
+                timestamp: transaction.timestamp,
+                description: ts.get_transaction_description(),
+                amount,
+                balance,
+            });
+        }
+        let positions = self.investment_positions_before(account_id, end)?;
+        Ok(AccountStatement {
+            account_id,
+            start,
+            end,
+            opening_balance,
+            closing_balance: balance,
+            lines,
+            positions,
+        })
+    }
+
+    pub fn get_branch_address(&self, branch_id: usize) -> Option<&str> {
+        self.branches.get(&branch_id).map(|a| a.as_str())
+    }
+
+    pub fn get_ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    pub fn get_loans(&self) -> &Vec<Loan> {
+        &self.loans
+    }
+
+    pub fn get_loan(&self, loan_id: usize) -> Option<&Loan> {
+        self.loans.iter().find(|l| l.id == loan_id)
+    }
+
+    pub fn get_credit_card(&self, card_id: usize) -> Option<&CreditCard> {
+        self.credit_cards.iter().find(|c| c.id == card_id)
+    }
+
+    // 信用卡当前欠款
+    pub fn get_card_balance(&self, card_id: usize) -> Result<Money, BankError> {
+        let card = self
+            .get_credit_card(card_id)
+            .ok_or(BankError::CardNotFound(card_id))?;
+        Ok(self.ledger.balance(
+            LedgerAccount::CardReceivable(card_id),
+            card.credit_limit.currency(),
+        ))
+    }
+
+    // 截止 as_of 有逾期金额的贷款及其逾期金额
+    pub fn get_loans_in_arrears(&self, as_of: NaiveDate) -> Result<Vec<(usize, Money)>, BankError> {
+        let mut result = Vec::new();
+        for loan in &self.loans {
+            let arrears = loan.arrears(as_of)?;
+            if !arrears.is_zero() {
+                result.push((loan.id, arrears));
+            }
+        }
+        Ok(result)
+    }
+
+    // 客户账户余额
+    pub fn get_balance(&self, account_id: usize) -> Result<Money, BankError> {
+        let account = self.find_account(account_id)?;
+        Ok(self
+            .ledger
+            .balance(LedgerAccount::Customer(account_id), account.currency))
+    }
+
+    // 可用余额, 支票账户包含透支额度
+    pub fn get_available_balance(&self, account_id: usize) -> Result<Money, BankError> {
+        let balance = self.get_balance(account_id)?;
+        let account = self.find_account(account_id)?;
+        match &account.kind {
+            AccountKind::Checking { overdraft_limit } => Ok(balance.checked_add(*overdraft_limit)?),
+            AccountKind::Savings { .. } => Ok(balance),
+        }
+    }
+
+    // 分行库存现金
+    pub fn get_branch_cash(&self, branch_id: usize) -> Money {
+        self.ledger
+            .balance(LedgerAccount::BranchCash(branch_id), self.currency)
+    }
+
+    // 总部金库现金
+    pub fn get_vault_cash(&self) -> Money {
+        self.ledger.balance(LedgerAccount::Vault, self.currency)
+    }
+
+    // 登记分行并注入初始资金, 返回分行ID
+    pub fn open_branch(
+        &mut self,
+        manager_id: usize,
+        address: String,
+        initial_funds: Money,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::ManageVault)?;
+            let branch_id = system.branches.len() + 1;
+            system.post_capital(LedgerAccount::BranchCash(branch_id), initial_funds)?;
+            system.branches.insert(branch_id, address.clone());
+            system.append_journal(Command::OpenBranch {
+                address,
+                initial_funds,
+            })?;
+            Ok(branch_id)
+        })
+    }
+
+    // 注入资本: 借 现金科目(金库或分行), 贷 银行资本
+    pub fn inject_capital(
+        &mut self,
+        manager_id: usize,
+        account: LedgerAccount,
+        amount: Money,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::ManageVault)?;
+            system.record_capital(account, amount)
+        })
+    }
+
+    // 银行成立时的资本还没有员工经办, 直接入账并写入日志
+    pub(crate) fn record_capital(
+        &mut self,
+        account: LedgerAccount,
+        amount: Money,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            if amount.is_zero() {
+                return Ok(());
+            }
+            system.post_capital(account, amount)?;
+            system.append_journal(Command::InjectCapital { account, amount })
+        })
+    }
+
+    fn post_capital(&mut self, account: LedgerAccount, amount: Money) -> Result<(), BankError> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        self.ledger.transfer(
+            format!("Capital injection of {} into {}", amount, account),
+            account,
+            LedgerAccount::Equity,
+            amount,
+        )?;
+        Ok(())
+    }
+
+    // 登记客户身份信息, 同一证件只能登记一次, 返回客户ID
+    pub fn register_customer(
+        &mut self,
+        branch_id: usize,
+        teller_id: usize,
+        name: String,
+        date_of_birth: NaiveDate,
+        address: String,
+        document: IdentityDocument,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::ServeCustomer)?;
+            if system.customers_by_document.contains_key(&document) {
+                return Err(BankError::DuplicateIdentityDocument(document));
+            }
+            let customer_id = system.customers.len() + 1;
+            system.customers.push(Customer {
+                id: customer_id,
+                name: name.clone(),
+                date_of_birth,
+                address: address.clone(),
+                document: document.clone(),
+                accounts: Vec::new(),
+            });
+            system
+                .customers_by_document
+                .insert(document.clone(), customer_id);
+            system.append_journal(Command::RegisterCustomer {
+                name,
+                date_of_birth,
+                address,
+                document,
+            })?;
+            Ok(customer_id)
+        })
+    }
+
+    // 为已开立的账户增加持有人, 使其成为联名账户
+    pub fn add_account_owner(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        customer_id: usize,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::ServeCustomer)?;
+            if system.get_customer(customer_id).is_none() {
+                return Err(BankError::CustomerNotFound(customer_id));
+            }
+            system.find_account(account_id)?;
+            let account = system
+                .get_account_mut(account_id)
+                .ok_or(BankError::AccountNotFound(account_id))?;
+            if !account.owners.contains(&customer_id) {
+                account.owners.push(customer_id);
+                system.customers[customer_id - 1].accounts.push(account_id);
+            }
+            system.append_journal(Command::AddAccountOwner {
+                account_id,
+                customer_id,
+            })
+        })
+    }
+
+    // 冻结账户(例如司法冻结), 冻结期间不能存取款和转账
+    pub fn freeze_account(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        reason: String,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::FreezeAccounts)?;
+            match system.get_account_status(account_id)? {
+                AccountStatus::Active | AccountStatus::Dormant => {}
+                AccountStatus::Frozen => return Err(BankError::AccountFrozen(account_id)),
+                AccountStatus::Closed => return Err(BankError::AccountClosed(account_id)),
+            }
+            system.change_account_status(
+                account_id,
+                teller_id,
+                Some(branch_id),
+                AccountStatus::Frozen,
+                reason.clone(),
+            )?;
+            system.append_journal(Command::FreezeAccount {
+                account_id,
+                branch_id,
+                teller_id,
+                reason,
+            })
+        })
+    }
+
+    // 解冻后账户恢复正常
+    pub fn unfreeze_account(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        reason: String,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::FreezeAccounts)?;
+            match system.get_account_status(account_id)? {
+                AccountStatus::Frozen => {}
+                AccountStatus::Closed => return Err(BankError::AccountClosed(account_id)),
+                AccountStatus::Active | AccountStatus::Dormant => return Ok(()),
+            }
+            system.change_account_status(
+                account_id,
+                teller_id,
+                Some(branch_id),
+                AccountStatus::Active,
+                reason.clone(),
+            )?;
+            system.append_journal(Command::UnfreezeAccount {
+                account_id,
+                branch_id,
+                teller_id,
+                reason,
+            })
+        })
+    }
+
+    // 睡眠户批处理: 超过 inactive_days 天没有客户发起交易的正常账户转为睡眠户, 返回转换的账号
+    pub fn mark_dormant_accounts(
+        &mut self,
+        supervisor_id: usize,
+        as_of: NaiveDate,
+        inactive_days: u32,
+    ) -> Result<Vec<usize>, BankError> {
+        self.atomically(|system| {
+            system.authorize(supervisor_id, HEADQUARTERS_ID, Operation::FreezeAccounts)?;
+            let cutoff = as_of - chrono::Duration::days(inactive_days.into());
+            let inactive: Vec<(usize, NaiveDate)> = system
+                .accounts
+                .values()
+                .filter(|a| a.status == AccountStatus::Active && a.last_activity < cutoff)
+                .map(|a| (a.account_id, a.last_activity))
+                .collect();
+            for (account_id, last_activity) in &inactive {
+                system.change_account_status(
+                    *account_id,
+                    SYSTEM_TELLER_ID,
+                    None,
+                    AccountStatus::Dormant,
+                    format!("no activity since {}", last_activity),
+                )?;
+            }
+            system.append_journal(Command::MarkDormantAccounts {
+                as_of,
+                inactive_days,
+            })?;
+            Ok(inactive
+                .into_iter()
+                .map(|(account_id, _)| account_id)
+                .collect())
+        })
+    }
+
+    // 客户到柜台核实身份后激活睡眠户
+    pub fn reactivate_account(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::ServeCustomer)?;
+            match system.get_account_status(account_id)? {
+                AccountStatus::Dormant => {}
+                AccountStatus::Active => return Ok(()),
+                AccountStatus::Frozen => return Err(BankError::AccountFrozen(account_id)),
+                AccountStatus::Closed => return Err(BankError::AccountClosed(account_id)),
+            }
+            system.change_account_status(
+                account_id,
+                teller_id,
+                Some(branch_id),
+                AccountStatus::Active,
+                "customer identity verified".to_string(),
+            )?;
+            system.record_activity(account_id)?;
+            system.append_journal(Command::ReactivateAccount {
+                account_id,
+                branch_id,
+                teller_id,
+            })
+        })
+    }
+
+    // 在分行柜台销户: 剩余余额以现金支付给客户(借 客户存款, 贷 分行现金), 返回支付的金额
+    // 冻结的账户不能销户, 透支的账户要先还清; 账号保留在账户表里, 不会分配给新账户
+    pub fn close_account(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        approved_by: Option<usize>,
+    ) -> Result<Money, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::ServeCustomer)?;
+            if let Some(approver_id) = approved_by {
+                system.authorize(approver_id, branch_id, Operation::Transact)?;
+            }
+            match system.get_account_status(account_id)? {
+                AccountStatus::Active | AccountStatus::Dormant => {}
+                AccountStatus::Frozen => return Err(BankError::AccountFrozen(account_id)),
+                AccountStatus::Closed => return Err(BankError::AccountClosed(account_id)),
+            }
+            if !system
+                .investment_positions(account_id, system.today())?
+                .is_empty()
+            {
+                return Err(BankError::AccountHasInvestments(account_id));
+            }
+            system.ensure_no_outstanding_credit(account_id)?;
+            let balance = system.get_balance(account_id)?;
+            if balance.is_negative() {
+                return Err(BankError::AccountOverdrawn {
+                    account_id,
+                    balance,
+                });
+            }
+            if !balance.is_zero() {
+                // 外币余额需要先兑换成记账币种
+                system.ensure_cash_currency(balance)?;
+                // 销户支付只需复核大额现金, 不受取款限额约束
+                system.check_policy(
+                    PolicyRequest::new(
+                        TransactionKind::AccountStatusChange,
+                        account_id,
+                        teller_id,
+                        balance,
+                    )
+                    .in_cash(approved_by),
+                )?;
+                let cash_on_hand = system.get_branch_cash(branch_id);
+                if cash_on_hand.checked_sub(balance)?.is_negative() {
+                    return Err(BankError::BranchCashShortfall {
+                        cash_on_hand,
+                        requested: balance,
+                    });
+                }
+                // 销户支付不受储蓄账户每月取款次数限制
+                let mut ts = Withdrawal::new(account_id, teller_id, balance);
+                ts.transaction.approved_by = approved_by;
+                system.ledger.transfer(
+                    ts.get_transaction_description(),
+                    LedgerAccount::Customer(account_id),
+                    LedgerAccount::BranchCash(branch_id),
+                    balance,
+                )?;
+                system.record_transaction(ts, Some(branch_id))?;
+            }
+            system.change_account_status(
+                account_id,
+                teller_id,
+                Some(branch_id),
+                AccountStatus::Closed,
+                format!("paid out {}", balance),
+            )?;
+            system.append_journal(Command::CloseAccount {
+                account_id,
+                branch_id,
+                teller_id,
+                approved_by,
+            })?;
+            Ok(balance)
+        })
+    }
+
+    // 账户的贷款都已还清、信用卡余额都为零
+    fn ensure_no_outstanding_credit(&self, account_id: usize) -> Result<(), BankError> {
+        for loan in self.loans.iter().filter(|l| l.account_id == account_id) {
+            if !loan.outstanding()?.is_zero() {
+                return Err(BankError::AccountHasOutstandingCredit(account_id));
+            }
+        }
+        for card in self
+            .credit_cards
+            .iter()
+            .filter(|c| c.account_id == account_id)
+        {
+            if !self.get_card_balance(card.id)?.is_zero() {
+                return Err(BankError::AccountHasOutstandingCredit(account_id));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_account_status(&self, account_id: usize) -> Result<AccountStatus, BankError> {
+        Ok(self.find_account(account_id)?.status)
+    }
+
+    // 修改账户状态并记录一笔状态变更交易
+    fn change_account_status(
+        &mut self,
+        account_id: usize,
+        teller_id: usize,
+        branch_id: Option<usize>,
+        to: AccountStatus,
+        reason: String,
+    ) -> Result<(), BankError> {
+        self.find_account(account_id)?;
+        let account = self
+            .get_account_mut(account_id)
+            .ok_or(BankError::AccountNotFound(account_id))?;
+        let from = account.status;
+        account.status = to;
+        let ts = AccountStatusChange::new(account_id, teller_id, from, to, reason);
+        self.record_transaction(ts, branch_id)?;
+        Ok(())
+    }
+
+    // 为一个或多个客户开户, 多个持有人时为联名账户
+    pub fn open_account(
+        &mut self,
+        owners: Vec<usize>,
+        kind: AccountKind,
+        branch_id: usize,
+        teller_id: usize,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.open_account_in(owners, kind, system.currency, branch_id, teller_id)
+        })
+    }
+
+    // 开立指定币种的账户, 支票账户的透支额度必须使用账户币种
+    pub fn open_account_in(
+        &mut self,
+        owners: Vec<usize>,
+        kind: AccountKind,
+        currency: Currency,
+        branch_id: usize,
+        teller_id: usize,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::ServeCustomer)?;
+            if owners.is_empty() {
+                return Err(BankError::NoAccountOwner);
+            }
+            if let Some(customer_id) = owners.iter().find(|c| system.get_customer(**c).is_none()) {
+                return Err(BankError::CustomerNotFound(*customer_id));
+            }
+            match &kind {
+                AccountKind::Checking { overdraft_limit } => {
+                    if overdraft_limit.currency() != currency {
+                        return Err(MoneyError::CurrencyMismatch(
+                            currency,
+                            overdraft_limit.currency(),
+                        )
+                        .into());
+                    }
+                    if overdraft_limit.is_negative() {
+                        return Err(BankError::InvalidAmount(*overdraft_limit));
+                    }
+                }
+                AccountKind::Savings { annual_rate, .. } => {
+                    if annual_rate.is_sign_negative() {
+                        return Err(BankError::InvalidRate(*annual_rate));
+                    }
+                }
+            }
+
+            // Create account
+            let account_id = system.account_ids.allocate();
+            let mut unique_owners = Vec::new();
+            for customer_id in &owners {
+                if !unique_owners.contains(customer_id) {
+                    unique_owners.push(*customer_id);
+                    system.customers[customer_id - 1].accounts.push(account_id);
+                }
+            }
+            let account = BankAccount::new(
+                account_id,
+                unique_owners,
+                branch_id,
+                currency,
+                kind.clone(),
+                system.today(),
+            );
+            system.accounts.insert(account_id, account);
+
+            // Log transaction
+            let ts = OpenAccount::new(account_id, teller_id);
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::OpenAccount {
+                owners,
+                kind,
+                branch_id,
+                teller_id,
+                currency: Some(currency),
+            })?;
+            Ok(account_id)
+        })
+    }
+
+    // 存钱: 借 分行现金, 贷 客户存款
+    pub fn deposit(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        approved_by: Option<usize>,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            if let Some(approver_id) = approved_by {
+                system.authorize(approver_id, branch_id, Operation::Transact)?;
+            }
+            ensure_positive(amount)?;
+            system.ensure_account_currency(account_id, amount)?;
+            system.ensure_cash_currency(amount)?;
+            system.ensure_account_can_receive(account_id)?;
+            system.check_policy(
+                PolicyRequest::new(TransactionKind::Deposit, account_id, teller_id, amount)
+                    .in_cash(approved_by),
+            )?;
+
+            let mut ts = Deposit::new(account_id, teller_id, amount);
+            ts.transaction.approved_by = approved_by;
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::BranchCash(branch_id),
+                LedgerAccount::Customer(account_id),
+                amount,
+            )?;
+            system.record_activity(account_id)?;
+            let id = system.record_transaction(ts, Some(branch_id))?;
+            let cross_branch =
+                system.get_account(account_id).map(|a| a.branch_id) != Some(branch_id);
+            system.charge_transaction_fees(
+                account_id,
+                branch_id,
+                teller_id,
+                id,
+                false,
+                cross_branch,
+            )?;
+            system.append_journal(Command::Deposit {
+                account_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            })
+        })
+    }
+
+    // 取钱: 借 客户存款, 贷 分行现金
+    pub fn withdraw(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        approved_by: Option<usize>,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            if let Some(approver_id) = approved_by {
+                system.authorize(approver_id, branch_id, Operation::Transact)?;
+            }
+            ensure_positive(amount)?;
+            system.ensure_account_currency(account_id, amount)?;
+            system.ensure_cash_currency(amount)?;
+            system.ensure_account_active(account_id)?;
+            system.check_policy(
+                PolicyRequest::new(TransactionKind::Withdrawal, account_id, teller_id, amount)
+                    .in_cash(approved_by),
+            )?;
+            system.ensure_can_debit(account_id, amount)?;
+            // 查看该分行现金是否足够
+            let cash_on_hand = system.get_branch_cash(branch_id);
+            if cash_on_hand.checked_sub(amount)?.is_negative() {
+                return Err(BankError::BranchCashShortfall {
+                    cash_on_hand,
+                    requested: amount,
+                });
+            }
+
+            let mut ts = Withdrawal::new(account_id, teller_id, amount);
+            ts.transaction.approved_by = approved_by;
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::Customer(account_id),
+                LedgerAccount::BranchCash(branch_id),
+                amount,
+            )?;
+            system.record_withdrawal(account_id, amount)?;
+            system.record_activity(account_id)?;
+            let id = system.record_transaction(ts, Some(branch_id))?;
+            let cross_branch =
+                system.get_account(account_id).map(|a| a.branch_id) != Some(branch_id);
+            system.charge_transaction_fees(
+                account_id,
+                branch_id,
+                teller_id,
+                id,
+                true,
+                cross_branch,
+            )?;
+            system.append_journal(Command::Withdraw {
+                account_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            })
+        })
+    }
+
+    // 转账: 借 转出账户, 贷 转入账户, 在同一笔分录里完成, 不会只扣款不入账
+    pub fn transfer(
+        &mut self,
+        from_account_id: usize,
+        to_account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            ensure_positive(amount)?;
+            if from_account_id == to_account_id {
+                return Err(BankError::SameAccountTransfer(from_account_id));
+            }
+            system.ensure_account_currency(from_account_id, amount)?;
+            system.ensure_account_currency(to_account_id, amount)?;
+            system.ensure_account_active(from_account_id)?;
+            system.ensure_account_can_receive(to_account_id)?;
+            system.check_policy(PolicyRequest::new(
+                TransactionKind::Transfer,
+                from_account_id,
+                teller_id,
+                amount,
+            ))?;
+            system.ensure_can_debit(from_account_id, amount)?;
+
+            let cross_branch = system.get_account(from_account_id).map(|a| a.branch_id)
+                != system.get_account(to_account_id).map(|a| a.branch_id);
+            let ts = Transfer::new(
+                from_account_id,
+                to_account_id,
+                teller_id,
+                amount,
+                cross_branch,
+            );
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::Customer(from_account_id),
+                LedgerAccount::Customer(to_account_id),
+                amount,
+            )?;
+            system.record_withdrawal(from_account_id, amount)?;
+            system.record_activity(from_account_id)?;
+            system.record_activity(to_account_id)?;
+            let id = system.record_transaction(ts, Some(branch_id))?;
+            system.charge_transaction_fees(
+                from_account_id,
+                branch_id,
+                teller_id,
+                id,
+                true,
+                cross_branch,
+            )?;
+            system.append_journal(Command::Transfer {
+                from_account_id,
+                to_account_id,
+                branch_id,
+                teller_id,
+                amount,
+            })
+        })
+    }
+
+    // 分行现金上缴总部
+    pub fn collect_cash(
+        &mut self,
+        branch_id: usize,
+        manager_id: usize,
+        amount: Money,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, branch_id, Operation::ManageCash)?;
+            system.send_cash_to_headquarters(branch_id, amount)?;
+            system.append_journal(Command::CollectCash { branch_id, amount })
+        })
+    }
+
+    // 按比例上缴分行现金, 上缴的金额按最小货币单位舍入, 返回上缴的金额
+    // 计算和上缴在同一次加锁内完成, 期间其他柜员的取款不会让上缴金额超过现金余额
+    pub fn collect_cash_by_ratio(
+        &mut self,
+        branch_id: usize,
+        manager_id: usize,
+        ratio: Decimal,
+    ) -> Result<Money, BankError> {
+        self.authorize(manager_id, branch_id, Operation::ManageCash)?;
+        let cash_to_collect = self.get_branch_cash(branch_id).checked_mul(ratio)?.round();
+        if cash_to_collect.is_zero() {
+            return Ok(cash_to_collect);
+        }
+        ensure_positive(cash_to_collect)?;
+        self.collect_cash(branch_id, manager_id, cash_to_collect)?;
+        Ok(cash_to_collect)
+    }
+
+    // 订阅之后记录的交易, 重放日志产生的历史交易不会通知
+    pub fn subscribe(&mut self, listener: Arc<Mutex<dyn TransactionListener>>) {
+        self.listeners.push(listener);
+    }
+
+    // 总部替换当前的策略规则; 规则金额必须为正数且使用银行的记账币种
+    pub fn set_policy(&mut self, manager_id: usize, policy: PolicyEngine) -> Result<(), BankError> {
+        self.authorize(manager_id, HEADQUARTERS_ID, Operation::SetPolicy)?;
+        policy.validate(self.currency)?;
+        self.policy = policy;
+        Ok(())
+    }
+
+    // 从配置文件加载策略规则
+    pub fn load_policy(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.authorize(manager_id, HEADQUARTERS_ID, Operation::SetPolicy)?;
+        let config = PolicyConfig::load(path)?;
+        self.set_policy(manager_id, PolicyEngine::from_config(&config))
+    }
+
+    pub fn get_settlements(&self) -> &[SettlementReport] {
+        &self.settlements
+    }
+
+    // 分行日终结算:
+    // 1.上次结算后的期初现金 + 本周期内各笔交易的现金收付 = 应有现金
+    // 2.与柜员清点的实际现金比较, 账面与实际的差额记入现金长短款, 使账面现金等于实际现金
+    // 3.按比例把实际现金上缴总部, 记录一笔现金调拨交易
+    pub fn settle_branch(
+        &mut self,
+        branch_id: usize,
+        manager_id: usize,
+        counted_cash: Money,
+        ratio: Decimal,
+    ) -> Result<SettlementReport, BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, branch_id, Operation::ManageCash)?;
+            if !system.branches.contains_key(&branch_id) {
+                return Err(BankError::BranchNotFound(branch_id));
+            }
+            if counted_cash.currency() != system.currency {
+                return Err(
+                    MoneyError::CurrencyMismatch(system.currency, counted_cash.currency()).into(),
+                );
+            }
+            if counted_cash.is_negative() {
+                return Err(BankError::InvalidAmount(counted_cash));
+            }
+            if ratio.is_sign_negative() || ratio > Decimal::ONE {
+                return Err(BankError::InvalidRate(ratio));
+            }
+
+            let previous = system
+                .settlements
+                .iter()
+                .rev()
+                .find(|r| r.branch_id == branch_id);
+            let since_id = previous.map_or(0, |r| r.last_transaction_id);
+            let zero = Money::zero(system.currency);
+            let (mut cash_in, mut cash_out) = (zero, zero);
+            for ts in &system.transactions {
+                let transaction = ts.get_transaction();
+                if transaction.id <= since_id || transaction.branch_id != Some(branch_id) {
+                    continue;
+                }
+                match ts.get_cash_effect() {
+                    Some(effect) if effect.is_negative() => {
+                        cash_out = cash_out.checked_sub(effect)?
+                    }
+                    Some(effect) => cash_in = cash_in.checked_add(effect)?,
+                    None => {}
+                }
+            }
+            let ledger_cash = system.get_branch_cash(branch_id);
+            let net = cash_in.checked_sub(cash_out)?;
+            let opening_cash = match previous {
+                Some(previous) => previous.closing_cash,
+                None => ledger_cash.checked_sub(net)?,
+            };
+            let expected_cash = opening_cash.checked_add(net)?;
+            let discrepancy = counted_cash.checked_sub(expected_cash)?;
+
+            // 长款: 借 分行现金, 贷 现金长短款; 短款反之
+            let adjustment = counted_cash.checked_sub(ledger_cash)?;
+            if !adjustment.is_zero() {
+                let memo = format!(
+                    "Branch {} cash count adjusted by {} at settlement",
+                    branch_id, adjustment
+                );
+                let (debit, credit) = if adjustment.is_negative() {
+                    (
+                        LedgerAccount::CashOverShort,
+                        LedgerAccount::BranchCash(branch_id),
+                    )
+                } else {
+                    (
+                        LedgerAccount::BranchCash(branch_id),
+                        LedgerAccount::CashOverShort,
+                    )
+                };
+                let amount = if adjustment.is_negative() {
+                    -adjustment
+                } else {
+                    adjustment
+                };
+                system.ledger.transfer(memo, debit, credit, amount)?;
+            }
+
+            let transferred = counted_cash.checked_mul(ratio)?.round();
+            let transfer_transaction_id = if transferred.is_zero() {
+                None
+            } else {
+                Some(system.send_cash_to_headquarters(branch_id, transferred)?)
+            };
+            let report = SettlementReport {
+                branch_id,
+                business_date: system.today(),
+                opening_cash,
+                cash_in,
+                cash_out,
+                expected_cash,
+                ledger_cash,
+                counted_cash,
+                discrepancy,
+                transferred_to_headquarters: transferred,
+                transfer_transaction_id,
+                closing_cash: counted_cash.checked_sub(transferred)?,
+                last_transaction_id: system.next_transaction_id - 1,
+            };
+            system.settlements.push(report.clone());
+            system.append_journal(Command::SettleBranch {
+                branch_id,
+                counted_cash,
+                ratio,
+            })?;
+            Ok(report)
+        })
+    }
+
+    // 总部设置分行库存现金上下限
+    pub fn set_cash_limits(
+        &mut self,
+        manager_id: usize,
+        branch_id: usize,
+        min: Money,
+        max: Money,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::ManageVault)?;
+            if !system.branches.contains_key(&branch_id) {
+                return Err(BankError::BranchNotFound(branch_id));
+            }
+            for limit in [min, max] {
+                if limit.currency() != system.currency {
+                    return Err(
+                        MoneyError::CurrencyMismatch(system.currency, limit.currency()).into(),
+                    );
+                }
+            }
+            if min.is_negative() || max < min {
+                return Err(BankError::InvalidCashLimits { min, max });
+            }
+            let limits = CashLimits { min, max };
+            system.replenishment.limits.insert(branch_id, limits);
+            system.append_journal(Command::SetCashLimits { branch_id, limits })
+        })
+    }
+
+    pub fn get_cash_limits(&self, branch_id: usize) -> Option<CashLimits> {
+        self.replenishment.limits.get(&branch_id).copied()
+    }
+
+    pub fn get_replenishment_requests(&self) -> &[ReplenishmentRequest] {
+        &self.replenishment.requests
+    }
+
+    // 分行申请补充现金, 返回申请ID
+    pub fn request_replenishment(
+        &mut self,
+        branch_id: usize,
+        manager_id: usize,
+        amount: Money,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, branch_id, Operation::ManageCash)?;
+            system.add_replenishment_request(branch_id, amount)
+        })
+    }
+
+    fn add_replenishment_request(
+        &mut self,
+        branch_id: usize,
+        amount: Money,
+    ) -> Result<usize, BankError> {
+        if !self.branches.contains_key(&branch_id) {
+            return Err(BankError::BranchNotFound(branch_id));
+        }
+        ensure_positive(amount)?;
+        if amount.currency() != self.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, amount.currency()).into());
+        }
+        let request_id = self.replenishment.requests.len() + 1;
+        self.replenishment.requests.push(ReplenishmentRequest {
+            id: request_id,
+            branch_id,
+            amount,
+            requested_at: self.now(),
+            status: ReplenishmentStatus::Pending,
+        });
+        self.append_journal(Command::RequestReplenishment { branch_id, amount })?;
+        Ok(request_id)
+    }
+
+    // 总部按申请从金库调拨现金给分行, 返回现金调拨交易的ID
+    pub fn fulfill_replenishment(
+        &mut self,
+        manager_id: usize,
+        request_id: usize,
+    ) -> Result<u64, BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::ManageVault)?;
+            let transaction_id = system.fulfill_request(request_id)?;
+            system.append_journal(Command::FulfillReplenishment { request_id })?;
+            Ok(transaction_id)
+        })
+    }
+
+    // 设置每天定时补款的时间
+    pub fn schedule_replenishment(
+        &mut self,
+        manager_id: usize,
+        at: NaiveTime,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::ManageVault)?;
+            system.replenishment.scheduled_at = Some(at);
+            system.append_journal(Command::ScheduleReplenishment { at })
+        })
+    }
+
+    // 定时补款, 到了当天的补款时间且当天还没有补过时执行:
+    // 1.库存现金(加上待调拨的申请)低于下限的分行生成补款申请, 补到上限
+    // 2.逐笔调拨所有待处理的申请, 每笔单独写入日志; 调拨失败的申请跳过并报告, 不影响其他申请
+    pub fn run_scheduled_replenishment(&mut self) -> Result<ReplenishmentRun, BankError> {
+        self.atomically(|system| {
+            let now = system.now();
+            let due = system
+                .replenishment
+                .scheduled_at
+                .is_some_and(|at| now.time() >= at)
+                && system.replenishment.last_run != Some(now.date_naive());
+            if !due {
+                return Ok(ReplenishmentRun::default());
+            }
+
+            let limits: Vec<(usize, CashLimits)> = system
+                .replenishment
+                .limits
+                .iter()
+                .map(|(branch_id, limits)| (*branch_id, *limits))
+                .collect();
+            for (branch_id, limits) in limits {
+                let mut cash = system.get_branch_cash(branch_id);
+                for request in system.replenishment.requests.iter().filter(|r| {
+                    r.branch_id == branch_id && r.status == ReplenishmentStatus::Pending
+                }) {
+                    cash = cash.checked_add(request.amount)?;
+                }
+                if cash < limits.min {
+                    let amount = limits.max.checked_sub(cash)?;
+                    system.add_replenishment_request(branch_id, amount)?;
+                }
+            }
+
+            let mut run = ReplenishmentRun::default();
+            let pending: Vec<usize> = system
+                .replenishment
+                .requests
+                .iter()
+                .filter(|r| r.status == ReplenishmentStatus::Pending)
+                .map(|r| r.id)
+                .collect();
+            for request_id in pending {
+                let result = system.attempt(|system| {
+                    let transaction_id = system.fulfill_request(request_id)?;
+                    system.append_journal(Command::FulfillReplenishment { request_id })?;
+                    Ok(transaction_id)
+                });
+                match result {
+                    Ok(transaction_id) => run.transaction_ids.push(transaction_id),
+                    Err(err) => run.failed.push((request_id, err)),
+                }
+            }
+            system.complete_scheduled_replenishment()?;
+            Ok(run)
+        })
+    }
+
+    fn complete_scheduled_replenishment(&mut self) -> Result<(), BankError> {
+        self.replenishment.last_run = Some(self.today());
+        self.append_journal(Command::CompleteScheduledReplenishment)
+    }
+
+    fn fulfill_request(&mut self, request_id: usize) -> Result<u64, BankError> {
+        let request = self
+            .replenishment
+            .requests
+            .iter()
+            .find(|r| r.id == request_id && r.status == ReplenishmentStatus::Pending)
+            .ok_or(BankError::ReplenishmentNotPending(request_id))?;
+        let transaction_id = self.send_cash_to_branch(request.branch_id, request.amount)?;
+        self.replenishment.requests[request_id - 1].status =
+            ReplenishmentStatus::Fulfilled { transaction_id };
+        Ok(transaction_id)
+    }
+
+    // 总部金库调拨现金给分行: 借 分行现金, 贷 总部金库, 返回现金调拨交易的ID
+    fn send_cash_to_branch(&mut self, branch_id: usize, amount: Money) -> Result<u64, BankError> {
+        let vault_cash = self.get_vault_cash();
+        if vault_cash.checked_sub(amount)?.is_negative() {
+            return Err(BankError::VaultCashShortfall {
+                vault_cash,
+                requested: amount,
+            });
+        }
+        let ts = CashTransfer::new(branch_id, CashDirection::FromHeadquarters, amount);
+        self.ledger.transfer(
+            ts.get_transaction_description(),
+            LedgerAccount::BranchCash(branch_id),
+            LedgerAccount::Vault,
+            amount,
+        )?;
+        self.record_transaction(ts, Some(branch_id))
+    }
+
+    // 分行现金上缴总部: 借 总部金库, 贷 分行现金, 返回现金调拨交易的ID
+    fn send_cash_to_headquarters(
+        &mut self,
+        branch_id: usize,
+        amount: Money,
+    ) -> Result<u64, BankError> {
+        let cash_on_hand = self.get_branch_cash(branch_id);
+        if cash_on_hand.checked_sub(amount)?.is_negative() {
+            return Err(BankError::BranchCashShortfall {
+                cash_on_hand,
+                requested: amount,
+            });
+        }
+        let ts = CashTransfer::new(branch_id, CashDirection::ToHeadquarters, amount);
+        self.ledger.transfer(
+            ts.get_transaction_description(),
+            LedgerAccount::Vault,
+            LedgerAccount::BranchCash(branch_id),
+            amount,
+        )?;
+        self.record_transaction(ts, Some(branch_id))
+    }
+
+    // 发放贷款: 借 应收贷款, 贷 客户存款(贷款直接放到客户账户)
+    pub fn originate_loan(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        principal: Money,
+        annual_rate: Decimal,
+        term_months: u32,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            // 贷款在柜台用现金归还, 只能以记账币种发放到同币种账户
+            system.ensure_cash_currency(principal)?;
+            system.ensure_account_currency(account_id, principal)?;
+            system.ensure_account_active(account_id)?;
+            let loan_id = system.loans.len() + 1;
+            let loan = Loan::new(
+                loan_id,
+                account_id,
+                principal,
+                annual_rate,
+                term_months,
+                system.today(),
+            )?;
+
+            let ts = LoanOrigination::new(
+                account_id,
+                teller_id,
+                loan_id,
+                principal,
+                annual_rate,
+                term_months,
+            );
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::LoanReceivable(loan_id),
+                LedgerAccount::Customer(account_id),
+                principal,
+            )?;
+            system.loans.push(loan);
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::OriginateLoan {
+                account_id,
+                branch_id,
+                teller_id,
+                principal,
+                annual_rate,
+                term_months,
+            })?;
+            Ok(loan_id)
+        })
+    }
+
+    // 在分行柜台用现金还贷: 借 分行现金, 贷 应收贷款(本金部分)和利息收入(利息部分)
+    pub fn repay_loan(
+        &mut self,
+        loan_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        approved_by: Option<usize>,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            if let Some(approver_id) = approved_by {
+                system.authorize(approver_id, branch_id, Operation::Transact)?;
+            }
+            ensure_positive(amount)?;
+            system.ensure_cash_currency(amount)?;
+            let loan = system
+                .get_loan(loan_id)
+                .ok_or(BankError::LoanNotFound(loan_id))?;
+            system.ensure_account_open(loan.account_id)?;
+            let outstanding = loan.outstanding()?;
+            if outstanding.checked_sub(amount)?.is_negative() {
+                return Err(BankError::RepaymentExceedsBalance {
+                    loan_id,
+                    outstanding,
+                });
+            }
+            let (interest, principal) = loan.split_repayment(amount)?;
+            let account_id = loan.account_id;
+            system.check_policy(
+                PolicyRequest::new(
+                    TransactionKind::LoanRepayment,
+                    account_id,
+                    teller_id,
+                    amount,
+                )
+                .in_cash(approved_by),
+            )?;
+
+            let mut ts = LoanRepayment::new(account_id, teller_id, loan_id, amount);
+            ts.transaction.approved_by = approved_by;
+            let mut postings = vec![Posting::debit(LedgerAccount::BranchCash(branch_id), amount)];
+            if !principal.is_zero() {
+                postings.push(Posting::credit(
+                    LedgerAccount::LoanReceivable(loan_id),
+                    principal,
+                ));
+            }
+            if !interest.is_zero() {
+                postings.push(Posting::credit(LedgerAccount::InterestIncome, interest));
+            }
+            system
+                .ledger
+                .post(ts.get_transaction_description(), postings)?;
+            if let Some(loan) = system.loans.iter_mut().find(|l| l.id == loan_id) {
+                loan.repaid = loan.repaid.checked_add(amount)?;
+            }
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::RepayLoan {
+                loan_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            })
+        })
+    }
+
+    // 办理信用卡
+    pub fn issue_credit_card(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        credit_limit: Money,
+        annual_rate: Decimal,
+        late_fee: Money,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            ensure_positive(credit_limit)?;
+            system.ensure_account_currency(account_id, credit_limit)?;
+            system.ensure_account_currency(account_id, late_fee)?;
+            system.ensure_account_active(account_id)?;
+            if annual_rate.is_sign_negative() {
+                return Err(BankError::InvalidRate(annual_rate));
+            }
+            let card_id = system.credit_cards.len() + 1;
+            system.credit_cards.push(CreditCard::new(
+                card_id,
+                account_id,
+                credit_limit,
+                annual_rate,
+                late_fee,
+            ));
+            let ts = IssueCreditCard::new(account_id, teller_id, card_id, credit_limit);
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::IssueCreditCard {
+                account_id,
+                branch_id,
+                teller_id,
+                credit_limit,
+                annual_rate,
+                late_fee,
+            })?;
+            Ok(card_id)
+        })
+    }
+
+    // 总部入账信用卡消费: 借 信用卡应收账款, 贷 应付商户清算款
+    pub fn charge_credit_card(
+        &mut self,
+        teller_id: usize,
+        card_id: usize,
+        merchant: String,
+        amount: Money,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, HEADQUARTERS_ID, Operation::PostCardCharges)?;
+            ensure_positive(amount)?;
+            let card = system
+                .get_credit_card(card_id)
+                .ok_or(BankError::CardNotFound(card_id))?;
+            system.ensure_account_open(card.account_id)?;
+            let available = card
+                .credit_limit
+                .checked_sub(system.get_card_balance(card_id)?)?;
+            if available.checked_sub(amount)?.is_negative() {
+                return Err(BankError::CreditLimitExceeded { card_id, available });
+            }
+
+            let ts = CreditCardCharge::new(
+                card.account_id,
+                card_id,
+                CardChargeKind::Purchase(merchant.clone()),
+                amount,
+            );
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::CardReceivable(card_id),
+                LedgerAccount::MerchantSettlement,
+                amount,
+            )?;
+            let card = system.get_credit_card_mut(card_id)?;
+            card.cycle_purchases = card.cycle_purchases.checked_add(amount)?;
+            system.record_transaction(ts, None)?;
+            system.append_journal(Command::ChargeCreditCard {
+                card_id,
+                merchant,
+                amount,
+            })
+        })
+    }
+
+    // 在分行柜台用现金还信用卡: 借 分行现金, 贷 信用卡应收账款
+    pub fn pay_credit_card(
+        &mut self,
+        card_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+        approved_by: Option<usize>,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            if let Some(approver_id) = approved_by {
+                system.authorize(approver_id, branch_id, Operation::Transact)?;
+            }
+            ensure_positive(amount)?;
+            let card = system
+                .get_credit_card(card_id)
+                .ok_or(BankError::CardNotFound(card_id))?;
+            system.ensure_account_open(card.account_id)?;
+            // 还款币种必须与信用卡币种一致, 否则之后的账单都无法生成
+            let card_currency = card.credit_limit.currency();
+            if amount.currency() != card_currency {
+                return Err(MoneyError::CurrencyMismatch(card_currency, amount.currency()).into());
+            }
+            system.ensure_cash_currency(amount)?;
+            let account_id = card.account_id;
+            system.check_policy(
+                PolicyRequest::new(
+                    TransactionKind::CreditCardPayment,
+                    account_id,
+                    teller_id,
+                    amount,
+                )
+                .in_cash(approved_by),
+            )?;
+            let mut ts = CreditCardPayment::new(account_id, teller_id, card_id, amount);
+            ts.transaction.approved_by = approved_by;
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::BranchCash(branch_id),
+                LedgerAccount::CardReceivable(card_id),
+                amount,
+            )?;
+            let today = system.today();
+            system
+                .get_credit_card_mut(card_id)?
+                .cycle_payments
+                .push((today, amount));
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::PayCreditCard {
+                card_id,
+                branch_id,
+                teller_id,
+                amount,
+                approved_by,
+            })
+        })
+    }
+
+    // 生成信用卡月度账单:
+    // 1.上期最低还款额没有在还款日前还清, 收取滞纳金
+    // 2.上期账单没有在还款日前全额还清, 对未还部分按月利率收取循环利息
+    pub fn generate_card_statement(
+        &mut self,
+        teller_id: usize,
+        card_id: usize,
+    ) -> Result<CreditCardStatement, BankError> {
+        self.atomically(|system| {
+            let account_id = system
+                .get_credit_card(card_id)
+                .ok_or(BankError::CardNotFound(card_id))?
+                .account_id;
+            system.ensure_account_open(account_id)?;
+            let branch_id = system.find_account(account_id)?.get_branch_id();
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            let card = system
+                .get_credit_card(card_id)
+                .ok_or(BankError::CardNotFound(card_id))?;
+            let currency = card.credit_limit.currency();
+            let zero = Money::zero(currency);
+            let previous = card.statements.last().cloned();
+            // 账单日取系统时钟的当天, 与消费和还款的记账时间一致; 上期的还款日过后才能出新账单
+            let closing_date = system.today();
+            if let Some(previous) = &previous {
+                if closing_date <= previous.due_date {
+                    return Err(BankError::StatementTooEarly {
+                        card_id,
+                        due_date: previous.due_date,
+                    });
+                }
+            }
+            let payments = card.payments_until(None)?;
+
+            let (mut late_fee, mut interest) = (zero, zero);
+            if let Some(previous) = &previous {
+                let paid_by_due = card.payments_until(Some(previous.due_date))?;
+                if paid_by_due < previous.minimum_due {
+                    late_fee = card.late_fee;
+                }
+                let unpaid = previous.closing_balance.checked_sub(paid_by_due)?;
+                if !unpaid.is_negative() {
+                    interest = unpaid
+                        .checked_mul(card.annual_rate / Decimal::from(12))?
+                        .round();
+                }
+            }
+
+            // 借 信用卡应收账款, 贷 手续费收入/利息收入
+            let charges = [
+                (CardChargeKind::LateFee, late_fee, LedgerAccount::FeeIncome),
+                (
+                    CardChargeKind::Interest,
+                    interest,
+                    LedgerAccount::InterestIncome,
+                ),
+            ];
+            for (kind, amount, income) in charges {
+                if amount.is_zero() {
+                    continue;
+                }
+                let ts = CreditCardCharge::new(account_id, card_id, kind, amount);
+                system.ledger.transfer(
+                    ts.get_transaction_description(),
+                    LedgerAccount::CardReceivable(card_id),
+                    income,
+                    amount,
+                )?;
+                system.record_transaction(ts, None)?;
+            }
+
+            let closing_balance = system.get_card_balance(card_id)?;
+            let minimum_due = if closing_balance.is_negative() || closing_balance.is_zero() {
+                zero
+            } else {
+                let floor = Money::from_major(CARD_MINIMUM_PAYMENT_FLOOR, currency);
+                let by_rate = closing_balance
+                    .checked_mul(CARD_MINIMUM_PAYMENT_RATE)?
+                    .round();
+                let minimum = if by_rate > floor { by_rate } else { floor };
+                if minimum > closing_balance {
+                    closing_balance
+                } else {
+                    minimum
+                }
+            };
+
+            let card = system.get_credit_card_mut(card_id)?;
+            let statement = CreditCardStatement {
+                closing_date,
+                previous_balance: previous.map_or(zero, |p| p.closing_balance),
+                purchases: card.cycle_purchases,
+                payments,
+                late_fee,
+                interest,
+                closing_balance,
+                minimum_due,
+                due_date: closing_date + chrono::Duration::days(CARD_GRACE_DAYS),
+            };
+            card.cycle_purchases = zero;
+            card.cycle_payments.clear();
+            card.statements.push(statement.clone());
+            system.append_journal(Command::GenerateCardStatement { card_id })?;
+            Ok(statement)
+        })
+    }
+
+    // 记录交易: 分配单调递增的交易ID, 填写交易时间、分行和交易后的客户账户余额
+    fn record_transaction<T: TransactionDescription + 'static>(
+        &mut self,
+        mut ts: T,
+        branch_id: Option<usize>,
+    ) -> Result<u64, BankError> {
+        let id = self.next_transaction_id;
+        let account_id = ts.get_transaction().account_id;
+        let transaction = ts.get_transaction_mut();
+        transaction.id = id;
+        transaction.timestamp = self.now();
+        transaction.branch_id = branch_id;
+        transaction.branch_address =
+            branch_id.and_then(|b| self.get_branch_address(b).map(|a| a.to_string()));
+        transaction.balance_after = match self.get_account(account_id) {
+            Some(_) => Some(self.get_balance(account_id)?),
+            None => None,
+        };
+        let transaction = ts.get_transaction();
+        let record = AuditRecord::Transaction {
+            transaction_id: id,
+            kind: ts.get_kind(),
+            account_id,
+            teller_id: transaction.teller_id,
+            branch_id: transaction.branch_id,
+            balance_after: transaction.balance_after,
+            description: ts.get_transaction_description(),
+        };
+        self.audit_pending.push(record);
+        self.next_transaction_id += 1;
+        self.transactions.push(Box::new(ts));
+        Ok(id)
+    }
+
+    // 从客户账户转出本金开立定期存款: 借 客户存款, 贷 定期存款
+    pub fn open_term_deposit(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        principal: Money,
+        product: TermDepositProduct,
+    ) -> Result<usize, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            ensure_positive(principal)?;
+            if principal.currency() != system.currency {
+                return Err(
+                    MoneyError::CurrencyMismatch(system.currency, principal.currency()).into(),
+                );
+            }
+            if product.term_months == 0 {
+                return Err(BankError::InvalidDepositTerm(product.term_months));
+            }
+            for rate in [product.annual_rate, product.early_withdrawal_penalty] {
+                if rate.is_sign_negative() {
+                    return Err(BankError::InvalidRate(rate));
+                }
+            }
+            system.ensure_account_currency(account_id, principal)?;
+            system.ensure_account_active(account_id)?;
+            system.ensure_can_debit(account_id, principal)?;
+
+            let deposit_id = system.term_deposits.len() + 1;
+            let opened_on = system.today();
+            let ts = OpenTermDeposit::new(account_id, teller_id, deposit_id, principal, product);
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::Customer(account_id),
+                LedgerAccount::TermDeposit(deposit_id),
+                principal,
+            )?;
+            system.term_deposits.push(TermDeposit {
+                id: deposit_id,
+                account_id,
+                principal,
+                product,
+                opened_on,
+                maturity_date: opened_on + Months::new(product.term_months),
+                status: TermDepositStatus::Active,
+            });
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::OpenTermDeposit {
+                account_id,
+                branch_id,
+                teller_id,
+                principal,
+                product,
+            })?;
+            Ok(deposit_id)
+        })
+    }
+
+    pub fn get_term_deposit(&self, deposit_id: usize) -> Option<&TermDeposit> {
+        self.term_deposits.get(deposit_id.checked_sub(1)?)
+    }
+
+    // 在柜台支取定期存款, 未到期时按提前支取扣罚金, 返回转入账户的本息
+    pub fn withdraw_term_deposit(
+        &mut self,
+        deposit_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+    ) -> Result<Money, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            let deposit = system
+                .get_term_deposit(deposit_id)
+                .ok_or(BankError::TermDepositNotFound(deposit_id))?;
+            if deposit.status != TermDepositStatus::Active {
+                return Err(BankError::TermDepositNotActive(deposit_id));
+            }
+            system.ensure_account_can_receive(deposit.account_id)?;
+            let today = system.today();
+            let early = today < deposit.maturity_date;
+            let interest = if early {
+                deposit.early_interest(today)?
+            } else {
+                deposit.interest_to(today)?
+            };
+            let payout = system.pay_out_term_deposit(
+                deposit_id,
+                teller_id,
+                Some(branch_id),
+                interest,
+                early,
+            )?;
+            system.append_journal(Command::WithdrawTermDeposit {
+                deposit_id,
+                branch_id,
+                teller_id,
+            })?;
+            Ok(payout)
+        })
+    }
+
+    // 定期存款到期批处理: 到期日不晚于 as_of 的定期存款本息自动转入客户账户, 返回支付总额
+    pub fn mature_term_deposits(&mut self, as_of: NaiveDate) -> Result<Money, BankError> {
+        self.atomically(|system| {
+            let mut total = Money::zero(system.currency);
+            let matured: Vec<usize> = system
+                .term_deposits
+                .iter()
+                .filter(|d| d.status == TermDepositStatus::Active && d.maturity_date <= as_of)
+                .map(|d| d.id)
+                .collect();
+            for deposit_id in matured {
+                let deposit = &system.term_deposits[deposit_id - 1];
+                let interest = deposit.interest_to(deposit.maturity_date)?;
+                let payout = system.pay_out_term_deposit(
+                    deposit_id,
+                    SYSTEM_TELLER_ID,
+                    None,
+                    interest,
+                    false,
+                )?;
+                total = total.checked_add(payout)?;
+            }
+            system.append_journal(Command::MatureTermDeposits { as_of })?;
+            Ok(total)
+        })
+    }
+
+    // 借 定期存款(本金)和利息支出(利息), 贷 客户存款
+    fn pay_out_term_deposit(
+        &mut self,
+        deposit_id: usize,
+        teller_id: usize,
+        branch_id: Option<usize>,
+        interest: Money,
+        early: bool,
+    ) -> Result<Money, BankError> {
+        let deposit = &self.term_deposits[deposit_id - 1];
+        let account_id = deposit.account_id;
+        let principal = deposit.principal;
+        let payout = principal.checked_add(interest)?;
+        let ts = TermDepositPayout::new(
+            account_id, teller_id, deposit_id, principal, interest, early,
+        );
+        let mut postings = vec![Posting::debit(
+            LedgerAccount::TermDeposit(deposit_id),
+            principal,
+        )];
+        if !interest.is_zero() {
+            postings.push(Posting::debit(LedgerAccount::InterestExpense, interest));
+        }
+        postings.push(Posting::credit(LedgerAccount::Customer(account_id), payout));
+        self.ledger
+            .post(ts.get_transaction_description(), postings)?;
+        self.term_deposits[deposit_id - 1].status = if early {
+            TermDepositStatus::WithdrawnEarly
+        } else {
+            TermDepositStatus::Matured
+        };
+        self.record_transaction(ts, branch_id)?;
+        Ok(payout)
+    }
+
+    // 从 csv 文件导入基金净值, 同一基金同一日期的净值以后导入的为准
+    pub fn load_fund_navs(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::LoadMarketData)?;
+            let records = NavRecord::load_csv(path)?;
+            system.import_fund_navs(records)
+        })
+    }
+
+    // 导入的净值写入日志, 重放申购赎回时使用相同的净值
+    fn import_fund_navs(&mut self, records: Vec<NavRecord>) -> Result<(), BankError> {
+        for record in &records {
+            self.funds
+                .entry(record.fund_code.clone())
+                .or_default()
+                .navs
+                .insert(record.date, record.nav);
+        }
+        self.append_journal(Command::ImportFundNavs { records })
+    }
+
+    pub fn get_fund(&self, fund_code: &str) -> Option<&Fund> {
+        self.funds.get(fund_code)
+    }
+
+    // 账户持有的某只基金的份额
+    pub fn get_fund_units(&self, account_id: usize, fund_code: &str) -> Decimal {
+        self.fund_holdings
+            .iter()
+            .find(|h| h.account_id == account_id && h.fund_code == fund_code)
+            .map_or(Decimal::ZERO, |h| h.units)
+    }
+
+    // 基金在某天的净值
+    fn fund_nav(
+        &self,
+        fund_code: &str,
+        date: NaiveDate,
+    ) -> Result<(NaiveDate, Decimal), BankError> {
+        self.get_fund(fund_code)
+            .ok_or_else(|| BankError::FundNotFound(fund_code.to_string()))?
+            .nav_on(date)
+            .ok_or_else(|| BankError::NavNotAvailable {
+                fund_code: fund_code.to_string(),
+                date,
+            })
+    }
+
+    // 按当天最近的净值申购基金, 份额保留4位小数, 返回申购的份额
+    // 借 客户存款, 贷 应付基金清算款
+    pub fn buy_fund(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        fund_code: String,
+        amount: Money,
+    ) -> Result<Decimal, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            ensure_positive(amount)?;
+            // 基金净值以记账币种计价
+            if amount.currency() != system.currency {
+                return Err(
+                    MoneyError::CurrencyMismatch(system.currency, amount.currency()).into(),
+                );
+            }
+            system.ensure_account_currency(account_id, amount)?;
+            system.ensure_account_active(account_id)?;
+            system.ensure_can_debit(account_id, amount)?;
+            let (_, nav) = system.fund_nav(&fund_code, system.today())?;
+            let units = (amount.amount() / nav).round_dp(4);
+            if units.is_zero() {
+                return Err(BankError::InvalidUnits(units));
+            }
+
+            let ts = FundTrade::new(
+                account_id,
+                teller_id,
+                fund_code.clone(),
+                units,
+                nav,
+                amount,
+                false,
+            );
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::Customer(account_id),
+                LedgerAccount::FundSettlement,
+                amount,
+            )?;
+            match system
+                .fund_holdings
+                .iter_mut()
+                .find(|h| h.account_id == account_id && h.fund_code == fund_code)
+            {
+                Some(holding) => holding.units += units,
+                None => system.fund_holdings.push(FundHolding {
+                    account_id,
+                    fund_code: fund_code.clone(),
+                    units,
+                }),
+            }
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::BuyFund {
+                account_id,
+                branch_id,
+                teller_id,
+                fund_code,
+                amount,
+            })?;
+            Ok(units)
+        })
+    }
+
+    // 按当天最近的净值赎回基金份额, 返回转入账户的金额
+    // 借 应付基金清算款, 贷 客户存款
+    pub fn redeem_fund(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        fund_code: String,
+        units: Decimal,
+    ) -> Result<Money, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            if units <= Decimal::ZERO {
+                return Err(BankError::InvalidUnits(units));
+            }
+            system.ensure_account_can_receive(account_id)?;
+            let held = system.get_fund_units(account_id, &fund_code);
+            if units > held {
+                return Err(BankError::InsufficientUnits { fund_code, held });
+            }
+            let (_, nav) = system.fund_nav(&fund_code, system.today())?;
+            let amount = Money::new(units * nav, system.currency).round();
+
+            let ts = FundTrade::new(
+                account_id,
+                teller_id,
+                fund_code.clone(),
+                units,
+                nav,
+                amount,
+                true,
+            );
+            system.ledger.transfer(
+                ts.get_transaction_description(),
+                LedgerAccount::FundSettlement,
+                LedgerAccount::Customer(account_id),
+                amount,
+            )?;
+            system.fund_holdings.retain_mut(|h| {
+                if h.account_id == account_id && h.fund_code == fund_code {
+                    h.units -= units;
+                }
+                !h.units.is_zero()
+            });
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::RedeemFund {
+                account_id,
+                branch_id,
+                teller_id,
+                fund_code,
+                units,
+            })?;
+            Ok(amount)
+        })
+    }
+
+    // 对账单期末的持仓: 由 end 之前的开立、兑付和申购赎回交易重建, 利息和市值按 end 当天计算
+    fn investment_positions_before(
+        &self,
+        account_id: usize,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<InvestmentPosition>, BankError> {
+        let mut deposit_ids = Vec::new();
+        let mut units: Vec<(&str, Decimal)> = Vec::new();
+        for ts in &self.transactions {
+            let transaction = ts.get_transaction();
+            if transaction.timestamp >= end {
+                break;
+            }
+            if transaction.account_id != account_id {
+                continue;
+            }
+            match ts.stored() {
+                StoredTransactionRef::OpenTermDeposit(open) => deposit_ids.push(open.deposit_id),
+                StoredTransactionRef::TermDepositPayout(payout) => {
+                    deposit_ids.retain(|id| *id != payout.deposit_id)
+                }
+                StoredTransactionRef::FundTrade(trade) => {
+                    let change = if trade.redemption {
+                        -trade.units
+                    } else {
+                        trade.units
+                    };
+                    match units.iter_mut().find(|(code, _)| *code == trade.fund_code) {
+                        Some((_, held)) => *held += change,
+                        None => units.push((&trade.fund_code, change)),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let date = end.date_naive();
+        let mut positions = Vec::new();
+        for deposit in self
+            .term_deposits
+            .iter()
+            .filter(|d| deposit_ids.contains(&d.id))
+        {
+            positions.push(InvestmentPosition::TermDeposit {
+                deposit_id: deposit.id,
+                principal: deposit.principal,
+                annual_rate: deposit.product.annual_rate,
+                maturity_date: deposit.maturity_date,
+                accrued_interest: deposit.interest_to(date)?,
+            });
+        }
+        for (fund_code, units) in units.into_iter().filter(|(_, u)| !u.is_zero()) {
+            let (nav_date, nav) = self.fund_nav(fund_code, date)?;
+            positions.push(InvestmentPosition::Fund {
+                fund_code: fund_code.to_string(),
+                units,
+                nav,
+                nav_date,
+                value: Money::new(units * nav, self.currency).round(),
+            });
+        }
+        Ok(positions)
+    }
+
+    // 账户当前的定期存款和基金持仓, 利息和市值按 date 计算
+    pub fn investment_positions(
+        &self,
+        account_id: usize,
+        date: NaiveDate,
+    ) -> Result<Vec<InvestmentPosition>, BankError> {
+        let mut positions = Vec::new();
+        for deposit in &self.term_deposits {
+            if deposit.account_id != account_id || deposit.status != TermDepositStatus::Active {
+                continue;
+            }
+            positions.push(InvestmentPosition::TermDeposit {
+                deposit_id: deposit.id,
+                principal: deposit.principal,
+                annual_rate: deposit.product.annual_rate,
+                maturity_date: deposit.maturity_date,
+                accrued_interest: deposit.interest_to(date)?,
+            });
+        }
+        for holding in self
+            .fund_holdings
+            .iter()
+            .filter(|h| h.account_id == account_id)
+        {
+            let (nav_date, nav) = self.fund_nav(&holding.fund_code, date)?;
+            positions.push(InvestmentPosition::Fund {
+                fund_code: holding.fund_code.clone(),
+                units: holding.units,
+                nav,
+                nav_date,
+                value: Money::new(holding.units * nav, self.currency).round(),
+            });
+        }
+        Ok(positions)
+    }
+
+    // 设置收费标准, 费用金额必须为正数且使用记账币种
+    pub fn set_fee_schedule(
+        &mut self,
+        manager_id: usize,
+        schedule: FeeSchedule,
+    ) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::SetPolicy)?;
+            let kinds = [
+                FeeKind::MonthlyMaintenance,
+                FeeKind::ExcessWithdrawal,
+                FeeKind::CrossBranch,
+                FeeKind::Overdraft,
+            ];
+            for fee in kinds.into_iter().filter_map(|kind| schedule.fee(kind)) {
+                if fee.currency() != system.currency {
+                    return Err(
+                        MoneyError::CurrencyMismatch(system.currency, fee.currency()).into(),
+                    );
+                }
+                ensure_positive(fee)?;
+            }
+            system.fees = schedule.clone();
+            system.append_journal(Command::SetFeeSchedule { schedule })
+        })
+    }
+
+    // 从配置文件加载收费标准
+    pub fn load_fee_schedule(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::SetPolicy)?;
+            system.set_fee_schedule(manager_id, FeeSchedule::load(path)?)
+        })
+    }
+
+    pub fn get_fee_schedule(&self) -> &FeeSchedule {
+        &self.fees
+    }
+
+    // 月度收费批处理: 对 as_of 所在月份还没有收取账户管理费的账户(已销户的除外)收费, 返回收取的总额
+    pub fn charge_monthly_fees(&mut self, as_of: NaiveDate) -> Result<Money, BankError> {
+        self.atomically(|system| {
+            let mut total = Money::zero(system.currency);
+            let month = (as_of.year(), as_of.month());
+            let account_ids: Vec<usize> = system
+                .accounts
+                .values()
+                .filter(|a| a.status != AccountStatus::Closed)
+                .filter(|a| a.maintenance_fee_month.is_none_or(|m| m < month))
+                .map(|a| a.account_id)
+                .collect();
+            for account_id in account_ids {
+                if let Some(fee) = system.charge_fee(
+                    account_id,
+                    FeeKind::MonthlyMaintenance,
+                    SYSTEM_TELLER_ID,
+                    None,
+                    None,
+                )? {
+                    system
+                        .get_account_mut(account_id)
+                        .ok_or(BankError::AccountNotFound(account_id))?
+                        .maintenance_fee_month = Some(month);
+                    total = total.checked_add(fee)?;
+                }
+            }
+            system.append_journal(Command::ChargeMonthlyFees { as_of })?;
+            Ok(total)
+        })
+    }
+
+    // 交易办理后收取的费用: 超次取款费、跨分行费和透支费, 按交易后的状态判断
+    // debit 表示交易从该账户扣款; 费用可以使账户透支
+    fn charge_transaction_fees(
+        &mut self,
+        account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        transaction_id: u64,
+        debit: bool,
+        cross_branch: bool,
+    ) -> Result<(), BankError> {
+        let account = self.find_account(account_id)?;
+        let mut kinds = Vec::new();
+        if debit && account.withdrawals_in_month(self.today()) > self.fees.free_withdrawals {
+            kinds.push(FeeKind::ExcessWithdrawal);
+        }
+        if cross_branch {
+            kinds.push(FeeKind::CrossBranch);
+        }
+        if debit && self.get_balance(account_id)?.is_negative() {
+            kinds.push(FeeKind::Overdraft);
+        }
+        for kind in kinds {
+            self.charge_fee(
+                account_id,
+                kind,
+                teller_id,
+                Some(branch_id),
+                Some(transaction_id),
+            )?;
+        }
+        Ok(())
+    }
+
+    // 收取一笔费用: 借 客户存款, 贷 手续费收入; 没有配置该费用或账户不是记账币种时不收费
+    fn charge_fee(
+        &mut self,
+        account_id: usize,
+        kind: FeeKind,
+        teller_id: usize,
+        branch_id: Option<usize>,
+        related_transaction_id: Option<u64>,
+    ) -> Result<Option<Money>, BankError> {
+        let Some(fee) = self.fees.fee(kind) else {
+            return Ok(None);
+        };
+        if self.ensure_account_currency(account_id, fee).is_err() {
+            return Ok(None);
+        }
+        let ts = FeeCharge::new(account_id, teller_id, kind, fee, related_transaction_id);
+        self.ledger.transfer(
+            ts.get_transaction_description(),
+            LedgerAccount::Customer(account_id),
+            LedgerAccount::FeeIncome,
+            fee,
+        )?;
+        self.record_transaction(ts, branch_id)?;
+        Ok(Some(fee))
+    }
+
+    // 从 csv 文件导入外汇牌价, 同一币种以后导入的为准
+    pub fn load_fx_rates(&mut self, manager_id: usize, path: &Path) -> Result<(), BankError> {
+        self.atomically(|system| {
+            system.authorize(manager_id, HEADQUARTERS_ID, Operation::LoadMarketData)?;
+            let rates = FxRate::load_csv(path, system.currency)?;
+            system.import_fx_rates(rates)
+        })
+    }
+
+    fn import_fx_rates(&mut self, rates: Vec<FxRate>) -> Result<(), BankError> {
+        for rate in &rates {
+            self.fx_rates.insert(rate.currency, *rate);
+        }
+        self.append_journal(Command::ImportFxRates { rates })
+    }
+
+    pub fn get_fx_rate(&self, currency: Currency) -> Option<&FxRate> {
+        self.fx_rates.get(&currency)
+    }
+
+    // 某币种的 (买入价, 卖出价, 中间价), 记账币种都是1
+    fn fx_quote(&self, currency: Currency) -> Result<(Decimal, Decimal, Decimal), BankError> {
+        if currency == self.currency {
+            return Ok((Decimal::ONE, Decimal::ONE, Decimal::ONE));
+        }
+        let rate = self
+            .get_fx_rate(currency)
+            .ok_or(BankError::FxRateNotAvailable(currency))?;
+        Ok((rate.bid, rate.ask, rate.mid()))
+    }
+
+    // 按中间价折算成 to 币种, 用于统计而不是成交
+    pub fn convert_at_mid(&self, amount: Money, to: Currency) -> Result<Money, BankError> {
+        if amount.currency() == to {
+            return Ok(amount);
+        }
+        let (_, _, from_mid) = self.fx_quote(amount.currency())?;
+        let (_, _, to_mid) = self.fx_quote(to)?;
+        let converted = amount.checked_mul(from_mid)?.checked_div(to_mid)?;
+        Ok(Money::new(converted.amount(), to).round())
+    }
+
+    // 按当前牌价计算卖出 amount 能兑换到的 to 币种金额和银行的汇兑收益:
+    // 外币先按买入价折成记账币种, 再按目标币种的卖出价兑换, 不足最小货币单位的部分舍去
+    pub fn quote_exchange(&self, amount: Money, to: Currency) -> Result<(Money, Money), BankError> {
+        if amount.currency() == to {
+            return Err(BankError::SameCurrencyExchange(to));
+        }
+        let (bid, _, sold_mid) = self.fx_quote(amount.currency())?;
+        let (_, ask, bought_mid) = self.fx_quote(to)?;
+        let bought = amount.checked_mul(bid)?.checked_div(ask)?;
+        let bought = Money::new(bought.amount(), to).round_with(RoundingStrategy::ToZero);
+        // 收益按记账币种计算: 卖出金额与买入金额的中间价之差
+        let sold_value = amount.checked_mul(sold_mid)?.amount();
+        let bought_value = bought.checked_mul(bought_mid)?.amount();
+        let margin = Money::new(sold_value, self.currency)
+            .checked_sub(Money::new(bought_value, self.currency))?
+            .round();
+        Ok((bought, margin))
+    }
+
+    // 在客户的两个不同币种账户之间兑换, 返回转入目标账户的金额:
+    // 借 客户存款(卖出币种), 贷 外汇头寸(卖出币种); 借 外汇头寸(买入币种), 贷 客户存款(买入币种);
+    // 借 外汇头寸(记账币种), 贷 汇兑收益
+    pub fn exchange_currency(
+        &mut self,
+        from_account_id: usize,
+        to_account_id: usize,
+        branch_id: usize,
+        teller_id: usize,
+        amount: Money,
+    ) -> Result<Money, BankError> {
+        self.atomically(|system| {
+            system.authorize(teller_id, branch_id, Operation::Transact)?;
+            ensure_positive(amount)?;
+            system.ensure_account_currency(from_account_id, amount)?;
+            let to = system.find_account(to_account_id)?.currency;
+            system.ensure_account_active(from_account_id)?;
+            system.ensure_account_can_receive(to_account_id)?;
+            system.check_policy(PolicyRequest::new(
+                TransactionKind::CurrencyExchange,
+                from_account_id,
+                teller_id,
+                amount,
+            ))?;
+            system.ensure_can_debit(from_account_id, amount)?;
+            let (bought, margin) = system.quote_exchange(amount, to)?;
+            if bought.is_zero() {
+                return Err(BankError::InvalidAmount(amount));
+            }
+
+            let ts = CurrencyExchange::new(
+                from_account_id,
+                to_account_id,
+                teller_id,
+                amount,
+                bought,
+                margin,
+            );
+            let mut postings = vec![
+                Posting::debit(LedgerAccount::Customer(from_account_id), amount),
+                Posting::credit(LedgerAccount::FxPosition(amount.currency()), amount),
+                Posting::debit(LedgerAccount::FxPosition(to), bought),
+                Posting::credit(LedgerAccount::Customer(to_account_id), bought),
+            ];
+            if !margin.is_zero() {
+                postings.push(Posting::debit(
+                    LedgerAccount::FxPosition(system.currency),
+                    margin,
+                ));
+                postings.push(Posting::credit(LedgerAccount::FxIncome, margin));
+            }
+            system
+                .ledger
+                .post(ts.get_transaction_description(), postings)?;
+            system.record_withdrawal(from_account_id, amount)?;
+            system.record_activity(from_account_id)?;
+            system.record_activity(to_account_id)?;
+            system.record_transaction(ts, Some(branch_id))?;
+            system.append_journal(Command::ExchangeCurrency {
+                from_account_id,
+                to_account_id,
+                branch_id,
+                teller_id,
+                amount,
+            })?;
+            Ok(bought)
+        })
+    }
+
+    // 储蓄账户计息批处理: 按日复利计算从上次计息日到 as_of 的利息
+    // 借 利息支出, 贷 客户存款, 返回本次入账的记账币种利息总额(外币账户的利息按账户币种入账, 不计入总额)
+    pub fn accrue_interest(&mut self, as_of: NaiveDate) -> Result<Money, BankError> {
+        self.atomically(|system| {
+            let mut total = Money::zero(system.currency);
+            let account_ids: Vec<usize> = system.accounts.keys().copied().collect();
+            for account_id in account_ids {
+                let account = &system.accounts[&account_id];
+                if account.status == AccountStatus::Closed {
+                    continue;
+                }
+                let AccountKind::Savings { annual_rate, .. } = account.kind else {
+                    continue;
+                };
+                let days = (as_of - account.interest_accrued_to).num_days();
+                if days <= 0 {
+                    continue;
+                }
+                let balance = system.get_balance(account_id)?;
+                if balance.is_zero() || balance.is_negative() {
+                    system
+                        .get_account_mut(account_id)
+                        .ok_or(BankError::AccountNotFound(account_id))?
+                        .interest_accrued_to = as_of;
+                    continue;
+                }
+
+                let daily_rate = annual_rate / Decimal::from(365);
+                let mut factor = Decimal::ONE;
+                for _ in 0..days {
+                    factor = factor
+                        .checked_mul(Decimal::ONE + daily_rate)
+                        .ok_or(MoneyError::Overflow)?;
+                }
+                let interest = balance.checked_mul(factor - Decimal::ONE)?.round();
+                // 不足最小货币单位的利息留到下次一起计算
+                if interest.is_zero() {
+                    continue;
+                }
+
+                let ts = InterestAccrual::new(account_id, interest, as_of);
+                system.ledger.transfer(
+                    ts.get_transaction_description(),
+                    LedgerAccount::InterestExpense,
+                    LedgerAccount::Customer(account_id),
+                    interest,
+                )?;
+                system.record_transaction(ts, None)?;
+                system
+                    .get_account_mut(account_id)
+                    .ok_or(BankError::AccountNotFound(account_id))?
+                    .interest_accrued_to = as_of;
+                if interest.currency() == system.currency {
+                    total = total.checked_add(interest)?;
+                }
+            }
+            system.append_journal(Command::AccrueInterest { as_of })?;
+            Ok(total)
+        })
+    }
+
+    // 检查账户能否扣款: 可用余额是否足够, 储蓄账户本月取款次数是否已达上限
+    fn ensure_can_debit(&self, account_id: usize, amount: Money) -> Result<(), BankError> {
+        let available = self.get_available_balance(account_id)?;
+        if available.checked_sub(amount)?.is_negative() {
+            return Err(BankError::InsufficientFunds {
+                account_id,
+                balance: self.get_balance(account_id)?,
+                requested: amount,
+            });
+        }
+        let account = self.find_account(account_id)?;
+        if let AccountKind::Savings {
+            monthly_withdrawal_cap,
+            ..
+        } = account.kind
+        {
+            if account.withdrawals_in_month(self.today()) >= monthly_withdrawal_cap {
+                return Err(BankError::WithdrawalLimitExceeded {
+                    account_id,
+                    limit: monthly_withdrawal_cap,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn record_withdrawal(&mut self, account_id: usize, amount: Money) -> Result<(), BankError> {
+        let today = self.today();
+        self.get_account_mut(account_id)
+            .ok_or(BankError::AccountNotFound(account_id))?
+            .record_withdrawal(today, amount)?;
+        Ok(())
+    }
+
+    // 核对密码后把柜员登记为在该分行在岗, 密码错误记录审计事件
+    pub(crate) fn sign_in_staff(
+        &mut self,
+        teller: &BankTeller,
+        branch_id: usize,
+        secret: &str,
+    ) -> Result<(), BankError> {
+        if !teller.credential.as_ref().is_some_and(|c| c.verify(secret)) {
+            self.record_audit_event(teller.id, branch_id, AuditEventKind::LoginFailed)?;
+            return Err(BankError::InvalidCredentials(teller.id));
+        }
+        // 柜员ID只在分行内唯一; 总部员工在分行行使权限时按ID认人, 签到期间这个ID不能出现在总部和分行两边
+        let conflict = self.staff_on_duty.keys().any(|(b, id)| {
+            *id == teller.id && (*b == HEADQUARTERS_ID) != (branch_id == HEADQUARTERS_ID)
+        });
+        if conflict {
+            return Err(BankError::StaffIdInUse(teller.id));
+        }
+        self.staff_on_duty
+            .insert((branch_id, teller.id), teller.role);
+        Ok(())
+    }
+
+    pub(crate) fn sign_out_staff(&mut self, teller_id: usize, branch_id: usize) {
+        self.staff_on_duty.remove(&(branch_id, teller_id));
+    }
+
+    // 检查柜员是否在该分行(或总部)签到且角色允许办理该类业务, 拒绝时记录审计事件
+    // 重放日志时不检查: 日志里的操作都已经授权过
+    pub fn authorize(
+        &mut self,
+        teller_id: usize,
+        branch_id: usize,
+        operation: Operation,
+    ) -> Result<(), BankError> {
+        self.authorize_in(
+            teller_id,
+            branch_id,
+            &[branch_id, HEADQUARTERS_ID],
+            operation,
+        )
+    }
+
+    // 只认在该分行签到的员工, 用于分行自己发起的业务
+    pub fn authorize_local(
+        &mut self,
+        teller_id: usize,
+        branch_id: usize,
+        operation: Operation,
+    ) -> Result<(), BankError> {
+        self.authorize_in(teller_id, branch_id, &[branch_id], operation)
+    }
+
+    // 柜员在 scopes 中任一处签到且角色允许即可办理
+    fn authorize_in(
+        &mut self,
+        teller_id: usize,
+        branch_id: usize,
+        scopes: &[usize],
+        operation: Operation,
+    ) -> Result<(), BankError> {
+        if self.replay_time.is_some() {
+            return Ok(());
+        }
+        let permitted = scopes.iter().any(|b| {
+            self.staff_on_duty
+                .get(&(*b, teller_id))
+                .is_some_and(|role| role.permits(operation))
+        });
+        if permitted {
+            return Ok(());
+        }
+        self.record_audit_event(
+            teller_id,
+            branch_id,
+            AuditEventKind::PermissionDenied(operation),
+        )?;
+        Err(BankError::PermissionDenied {
+            teller_id,
+            operation,
+        })
+    }
+
+    pub fn record_audit_event(
+        &mut self,
+        teller_id: usize,
+        branch_id: usize,
+        kind: AuditEventKind,
+    ) -> Result<(), BankError> {
+        let event = AuditEvent {
+            timestamp: self.now(),
+            teller_id,
+            branch_id,
+            kind,
+        };
+        self.push_audit_event(event)
+    }
+
+    fn push_audit_event(&mut self, event: AuditEvent) -> Result<(), BankError> {
+        self.audit_pending.push(AuditRecord::Event(event.clone()));
+        self.audit_events.push(event.clone());
+        self.append_journal(Command::RecordAuditEvent { event })
+    }
+
+    pub fn get_audit_events(&self) -> &[AuditEvent] {
+        &self.audit_events
+    }
+
+    pub fn get_audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    // 校验审计日志, 返回链头 hash
+    pub fn verify_audit_log(&self) -> Result<String, BankError> {
+        self.audit_log.verify()
+    }
+
+    // 重放日志时不再检查策略: 日志里的操作在记录时已经通过检查, 之后修改的规则不影响历史
+    // 外币账户按当前牌价的中间价折成记账币种后再和规则金额比较
+    fn check_policy(&self, mut request: PolicyRequest) -> Result<(), BankError> {
+        if self.replay_time.is_some() || self.policy.rules.is_empty() {
+            return Ok(());
+        }
+        let account = self.find_account(request.account_id)?;
+        let (_, _, mid) = self.fx_quote(account.currency)?;
+        request.mid_rate = mid;
+        self.policy.check(&request, account, self.today())
+    }
+
+    fn record_activity(&mut self, account_id: usize) -> Result<(), BankError> {
+        let today = self.today();
+        self.get_account_mut(account_id)
+            .ok_or(BankError::AccountNotFound(account_id))?
+            .last_activity = today;
+        Ok(())
+    }
+
+    fn get_credit_card_mut(&mut self, card_id: usize) -> Result<&mut CreditCard, BankError> {
+        self.credit_cards
+            .iter_mut()
+            .find(|c| c.id == card_id)
+            .ok_or(BankError::CardNotFound(card_id))
+    }
+
+    // 出账和办理新业务要求账户状态正常
+    fn ensure_account_active(&self, account_id: usize) -> Result<(), BankError> {
+        match self.get_account_status(account_id)? {
+            AccountStatus::Active => Ok(()),
+            AccountStatus::Frozen => Err(BankError::AccountFrozen(account_id)),
+            AccountStatus::Dormant => Err(BankError::AccountDormant(account_id)),
+            AccountStatus::Closed => Err(BankError::AccountClosed(account_id)),
+        }
+    }
+
+    // 入账只拒绝冻结和已销户的账户, 睡眠户可以入账
+    fn ensure_account_can_receive(&self, account_id: usize) -> Result<(), BankError> {
+        match self.get_account_status(account_id)? {
+            AccountStatus::Active | AccountStatus::Dormant => Ok(()),
+            AccountStatus::Frozen => Err(BankError::AccountFrozen(account_id)),
+            AccountStatus::Closed => Err(BankError::AccountClosed(account_id)),
+        }
+    }
+
+    // 已销户账户的贷款和信用卡不能再办理业务
+    fn ensure_account_open(&self, account_id: usize) -> Result<(), BankError> {
+        if self.get_account_status(account_id)? == AccountStatus::Closed {
+            return Err(BankError::AccountClosed(account_id));
+        }
+        Ok(())
+    }
+
+    // 现金收付只能使用记账币种
+    fn ensure_cash_currency(&self, amount: Money) -> Result<(), BankError> {
+        if amount.currency() != self.currency {
+            return Err(BankError::ForeignCashNotSupported(amount.currency()));
+        }
+        Ok(())
+    }
+
+    // 交易币种必须与账户币种一致
+    fn ensure_account_currency(&self, account_id: usize, amount: Money) -> Result<(), BankError> {
+        let account = self.find_account(account_id)?;
+        if account.currency != amount.currency() {
+            return Err(MoneyError::CurrencyMismatch(account.currency, amount.currency()).into());
+        }
+        Ok(())
+    }
+}
+
+// 交易金额必须大于0
+pub(crate) fn ensure_positive(amount: Money) -> Result<(), BankError> {
+    if amount.is_zero() || amount.is_negative() {
+        return Err(BankError::InvalidAmount(amount));
+    }
+    Ok(())
+}
@@ -0,0 +1,3153 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use rust_decimal::Decimal;
+
+use chrono::TimeZone;
+
+use super::*;
+
+fn cny(units: i64) -> Money {
+    Money::from_major(units, Currency::CNY)
+}
+
+// 设置密码并签到的柜员
+fn on_duty(branch: &mut BankBranch, teller: BankTeller) {
+    let teller_id = teller.id;
+    branch.add_teller(teller.with_credential("secret"));
+    branch.sign_in(teller_id, "secret").unwrap();
+}
+
+// 总部行长在总部签到, 返回员工ID; 已经签到时直接返回
+fn head_office(bank: &mut Bank) -> usize {
+    let manager_id = 100;
+    if bank.staff.iter().any(|t| t.id == manager_id) {
+        return manager_id;
+    }
+    bank.add_staff(
+        BankTeller::new(manager_id)
+            .with_role(TellerRole::BranchManager)
+            .with_credential("secret"),
+    );
+    bank.sign_in(manager_id, "secret").unwrap();
+    manager_id
+}
+
+// 由总部行长开设分行
+fn add_branch(bank: &mut Bank, address: String, funds: Money) -> Arc<Mutex<BankBranch>> {
+    let manager_id = head_office(bank);
+    bank.add_branch(manager_id, address, funds).unwrap()
+}
+
+fn checking() -> AccountKind {
+    AccountKind::Checking {
+        overdraft_limit: cny(0),
+    }
+}
+
+// 登记客户(证件号码使用姓名)并为其开户, 返回账户ID
+fn open_account(
+    branch: &Arc<Mutex<BankBranch>>,
+    name: &str,
+    kind: AccountKind,
+) -> Result<usize, BankError> {
+    let mut branch = branch.lock().unwrap();
+    let document = IdentityDocument::new(DocumentKind::IdCard, name.to_string());
+    let customer_id = branch.register_customer(
+        name.to_string(),
+        NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+        "1 Test Rd".to_string(),
+        document,
+    )?;
+    branch.open_account(vec![customer_id], kind)
+}
+
+fn setup() -> (Bank, Arc<Mutex<BankBranch>>, usize) {
+    let mut bank = Bank::new(cny(10000)).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let account_id = open_account(&branch, "John Doe", checking()).unwrap();
+    (bank, branch, account_id)
+}
+
+#[test]
+fn deposit_and_withdraw() {
+    let (bank, branch, account_id) = setup();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(100))
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(40))
+        .unwrap();
+
+    assert_eq!(branch.lock().unwrap().get_cash_on_hand(), cny(1060));
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_balance(account_id), Ok(cny(60)));
+    assert_eq!(system.get_transactions().len(), 3);
+}
+
+#[test]
+fn unknown_account() {
+    let (_bank, branch, _) = setup();
+    assert_eq!(
+        branch.lock().unwrap().deposit(42, cny(100)),
+        Err(BankError::AccountNotFound(42))
+    );
+    assert_eq!(
+        branch.lock().unwrap().withdraw(42, cny(100)),
+        Err(BankError::AccountNotFound(42))
+    );
+}
+
+#[test]
+fn insufficient_funds() {
+    let (bank, branch, account_id) = setup();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(100))
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().withdraw(account_id, cny(150)),
+        Err(BankError::InsufficientFunds {
+            account_id,
+            balance: cny(100),
+            requested: cny(150),
+        })
+    );
+    // 失败的取款不影响分行现金, 也不记录交易
+    assert_eq!(branch.lock().unwrap().get_cash_on_hand(), cny(1100));
+    assert_eq!(bank.bank_system.lock().unwrap().get_transactions().len(), 2);
+}
+
+#[test]
+fn branch_cash_shortfall() {
+    let (mut bank, branch, account_id) = setup();
+    // 在另一家分行存入大额现金, 本分行的现金不受影响
+    let other = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    on_duty(&mut other.lock().unwrap(), BankTeller::new(2));
+    other
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(5000))
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().withdraw(account_id, cny(2000)),
+        Err(BankError::BranchCashShortfall {
+            cash_on_hand: cny(1000),
+            requested: cny(2000),
+        })
+    );
+}
+
+#[test]
+fn no_teller_available() {
+    let mut bank = Bank::new(cny(10000)).unwrap();
+    let branch = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    assert_eq!(
+        open_account(&branch, "Jane Doe", checking()),
+        Err(BankError::NoTellerAvailable)
+    );
+    assert_eq!(
+        branch.lock().unwrap().deposit(1, cny(100)),
+        Err(BankError::NoTellerAvailable)
+    );
+}
+
+#[test]
+fn invalid_amount() {
+    let (_bank, branch, account_id) = setup();
+    assert_eq!(
+        branch.lock().unwrap().deposit(account_id, cny(0)),
+        Err(BankError::InvalidAmount(cny(0)))
+    );
+    assert_eq!(
+        branch.lock().unwrap().deposit(account_id, cny(-5)),
+        Err(BankError::InvalidAmount(cny(-5)))
+    );
+}
+
+#[test]
+fn currency_mismatch() {
+    let (_bank, branch, account_id) = setup();
+    let usd = Money::from_major(100, Currency::USD);
+    assert_eq!(
+        branch.lock().unwrap().deposit(account_id, usd),
+        Err(BankError::Money(MoneyError::CurrencyMismatch(
+            Currency::CNY,
+            Currency::USD
+        )))
+    );
+}
+
+#[test]
+fn ledger_trial_balance_nets_to_zero() {
+    let (mut bank, branch, account_id) = setup();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(300))
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(120))
+        .unwrap();
+    let manager_id = head_office(&mut bank);
+    bank.collect_cash(manager_id, Decimal::new(5, 1)).unwrap();
+
+    // 分行现金 1000 + 300 - 120 = 1180, 上缴一半 590
+    assert_eq!(branch.lock().unwrap().get_cash_on_hand(), cny(590));
+    assert_eq!(bank.get_total_cash(), cny(10590));
+
+    let system = bank.bank_system.lock().unwrap();
+    let trial_balance = system.get_ledger().trial_balance().unwrap();
+    assert!(trial_balance.is_balanced());
+    assert!(trial_balance.net(Currency::CNY).unwrap().is_zero());
+    let customer = trial_balance
+        .get_lines()
+        .iter()
+        .find(|l| l.account == LedgerAccount::Customer(account_id))
+        .unwrap();
+    assert_eq!(customer.debit, cny(120));
+    assert_eq!(customer.credit, cny(300));
+}
+
+#[test]
+fn ledger_rejects_unbalanced_entry() {
+    let mut ledger = Ledger::new();
+    let postings = vec![
+        Posting::debit(LedgerAccount::Vault, cny(100)),
+        Posting::credit(LedgerAccount::Equity, cny(90)),
+    ];
+    assert_eq!(
+        ledger.post("unbalanced".to_string(), postings),
+        Err(BankError::UnbalancedEntry)
+    );
+    assert!(ledger.get_entries().is_empty());
+    assert!(ledger
+        .balance(LedgerAccount::Vault, Currency::CNY)
+        .is_zero());
+}
+
+#[test]
+fn cross_branch_transfer() {
+    let (mut bank, branch, account_id) = setup();
+    let other = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    on_duty(&mut other.lock().unwrap(), BankTeller::new(2));
+    let other_id = open_account(&other, "Jane Doe", checking()).unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(100))
+        .unwrap();
+
+    other
+        .lock()
+        .unwrap()
+        .transfer(account_id, other_id, cny(70))
+        .unwrap();
+    assert_eq!(
+        other
+            .lock()
+            .unwrap()
+            .transfer(account_id, other_id, cny(70)),
+        Err(BankError::InsufficientFunds {
+            account_id,
+            balance: cny(30),
+            requested: cny(70),
+        })
+    );
+    assert_eq!(
+        other.lock().unwrap().transfer(other_id, other_id, cny(10)),
+        Err(BankError::SameAccountTransfer(other_id))
+    );
+
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_balance(account_id), Ok(cny(30)));
+    assert_eq!(system.get_balance(other_id), Ok(cny(70)));
+    // 转账不涉及现金, 两家分行的现金不变
+    assert_eq!(system.get_branch_cash(1), cny(1100));
+    assert_eq!(system.get_branch_cash(2), cny(1000));
+    assert_eq!(
+        system
+            .get_transactions()
+            .last()
+            .unwrap()
+            .get_transaction_description(),
+        format!(
+            "Teller 2 transferred 70.00 CNY from account {} to account {} (cross-branch)",
+            account_id, other_id
+        )
+    );
+}
+
+#[test]
+fn checking_overdraft_limit() {
+    let (_bank, branch, _) = setup();
+    let account_id = open_account(
+        &branch,
+        "Bob Smith",
+        AccountKind::Checking {
+            overdraft_limit: cny(50),
+        },
+    )
+    .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(100))
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(140))
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().withdraw(account_id, cny(20)),
+        Err(BankError::InsufficientFunds {
+            account_id,
+            balance: cny(-40),
+            requested: cny(20),
+        })
+    );
+}
+
+#[test]
+fn savings_monthly_withdrawal_cap() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 30, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let account_id = open_account(
+        &branch,
+        "Jane Doe",
+        AccountKind::Savings {
+            annual_rate: Decimal::new(2, 2),
+            monthly_withdrawal_cap: 2,
+        },
+    )
+    .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(100))
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(10))
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(10))
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().withdraw(account_id, cny(10)),
+        Err(BankError::WithdrawalLimitExceeded {
+            account_id,
+            limit: 2,
+        })
+    );
+
+    // 下个月重新计数
+    clock.advance(chrono::Duration::days(2));
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(10))
+        .unwrap();
+}
+
+#[test]
+fn savings_interest_compounds_daily() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock)).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let savings_id = open_account(
+        &branch,
+        "Jane Doe",
+        AccountKind::Savings {
+            annual_rate: Decimal::new(365, 4),
+            monthly_withdrawal_cap: 6,
+        },
+    )
+    .unwrap();
+    let checking_id = open_account(&branch, "John Doe", checking()).unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(savings_id, cny(10000))
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(checking_id, cny(10000))
+        .unwrap();
+
+    // 日利率 0.0001, 10天复利: 10000 * (1.0001^10 - 1) = 10.0045 元, 舍入到分
+    let as_of = NaiveDate::from_ymd_opt(2026, 1, 11).unwrap();
+    assert_eq!(bank.accrue_interest(as_of), Ok(cny(10)));
+    // 同一天重复执行不会重复计息
+    assert_eq!(bank.accrue_interest(as_of), Ok(cny(0)));
+
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_balance(savings_id), Ok(cny(10010)));
+    assert_eq!(system.get_balance(checking_id), Ok(cny(10000)));
+    assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+}
+
+#[test]
+fn loan_amortization_schedule() {
+    let start = NaiveDate::from_ymd_opt(2026, 1, 31).unwrap();
+    let schedule = Loan::amortization_schedule(cny(1200), Decimal::new(12, 2), 12, start).unwrap();
+    assert_eq!(schedule.len(), 12);
+    // 月利率 1%, 每期还款 1200 * 0.01 / (1 - 1.01^-12) = 106.62
+    assert_eq!(schedule[0].payment, Money::from_minor(10662, Currency::CNY));
+    assert_eq!(schedule[0].interest, cny(12));
+    assert_eq!(
+        schedule[0].principal,
+        Money::from_minor(9462, Currency::CNY)
+    );
+    assert_eq!(
+        schedule[0].due_date,
+        NaiveDate::from_ymd_opt(2026, 2, 28).unwrap()
+    );
+    assert!(schedule[11].remaining_principal.is_zero());
+
+    let mut principal = cny(0);
+    for installment in &schedule {
+        principal = principal.checked_add(installment.principal).unwrap();
+    }
+    assert_eq!(principal, cny(1200));
+    assert_eq!(
+        Loan::amortization_schedule(cny(1200), Decimal::new(12, 2), 0, start),
+        Err(BankError::InvalidLoanTerm(0))
+    );
+}
+
+#[test]
+fn loan_repayment_and_arrears() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 15, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock)).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let account_id = open_account(&branch, "John Doe", checking()).unwrap();
+    let loan_id = branch
+        .lock()
+        .unwrap()
+        .originate_loan(account_id, cny(1200), Decimal::new(12, 2), 12)
+        .unwrap();
+    let payment = Money::from_minor(10662, Currency::CNY);
+
+    let as_of = NaiveDate::from_ymd_opt(2026, 3, 20).unwrap();
+    {
+        let system = bank.bank_system.lock().unwrap();
+        assert_eq!(system.get_balance(account_id), Ok(cny(1200)));
+        assert_eq!(
+            system.get_loans_in_arrears(as_of),
+            Ok(vec![(loan_id, payment.checked_add(payment).unwrap())])
+        );
+    }
+
+    branch.lock().unwrap().repay_loan(loan_id, payment).unwrap();
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(
+        system.get_loans_in_arrears(as_of),
+        Ok(vec![(loan_id, payment)])
+    );
+    // 第一期的利息计入利息收入, 本金冲减应收贷款
+    let ledger = system.get_ledger();
+    assert_eq!(
+        ledger.balance(LedgerAccount::InterestIncome, Currency::CNY),
+        cny(12)
+    );
+    assert_eq!(
+        ledger.balance(LedgerAccount::LoanReceivable(loan_id), Currency::CNY),
+        Money::from_minor(110538, Currency::CNY)
+    );
+    assert!(ledger.trial_balance().unwrap().is_balanced());
+    drop(system);
+
+    assert_eq!(
+        branch.lock().unwrap().repay_loan(loan_id, cny(5000)),
+        Err(BankError::RepaymentExceedsBalance {
+            loan_id,
+            outstanding: bank
+                .bank_system
+                .lock()
+                .unwrap()
+                .get_loan(loan_id)
+                .unwrap()
+                .outstanding()
+                .unwrap(),
+        })
+    );
+}
+
+#[test]
+fn credit_card_statements() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let account_id = open_account(&branch, "John Doe", checking()).unwrap();
+    let card_id = branch
+        .lock()
+        .unwrap()
+        .issue_credit_card(account_id, cny(1000), Decimal::new(24, 2), cny(50))
+        .unwrap();
+
+    let manager_id = head_office(&mut bank);
+    let mut system = bank.bank_system.lock().unwrap();
+    system
+        .charge_credit_card(manager_id, card_id, "Book Store".to_string(), cny(600))
+        .unwrap();
+    assert_eq!(
+        system.charge_credit_card(manager_id, card_id, "Book Store".to_string(), cny(500)),
+        Err(BankError::CreditLimitExceeded {
+            card_id,
+            available: cny(400),
+        })
+    );
+
+    let date = |m, d| NaiveDate::from_ymd_opt(2026, m, d).unwrap();
+    let close_on = |m, d| clock.set(Utc.with_ymd_and_hms(2026, m, d, 18, 0, 0).unwrap());
+    close_on(1, 31);
+    let first = system.generate_card_statement(manager_id, card_id).unwrap();
+    assert_eq!(first.closing_date, date(1, 31));
+    assert_eq!(first.closing_balance, cny(600));
+    assert_eq!(first.minimum_due, cny(100));
+    assert_eq!(first.due_date, date(2, 25));
+
+    // 还款日前只还了 50, 少于最低还款额: 收取滞纳金 50, 未还的 550 按月利率 2% 计息 11
+    clock.set(Utc.with_ymd_and_hms(2026, 2, 10, 9, 0, 0).unwrap());
+    assert_eq!(
+        system.pay_credit_card(card_id, 1, 1, Money::from_major(50, Currency::USD), None),
+        Err(BankError::Money(MoneyError::CurrencyMismatch(
+            Currency::CNY,
+            Currency::USD
+        )))
+    );
+    system
+        .pay_credit_card(card_id, 1, 1, cny(50), None)
+        .unwrap();
+    // 上期还款日之前不能出新账单
+    assert_eq!(
+        system
+            .generate_card_statement(manager_id, card_id)
+            .map(|_| ()),
+        Err(BankError::StatementTooEarly {
+            card_id,
+            due_date: date(2, 25),
+        })
+    );
+    close_on(2, 28);
+    let second = system.generate_card_statement(manager_id, card_id).unwrap();
+    assert_eq!(second.previous_balance, cny(600));
+    assert_eq!(second.payments, cny(50));
+    assert_eq!(second.late_fee, cny(50));
+    assert_eq!(second.interest, cny(11));
+    assert_eq!(second.closing_balance, cny(611));
+
+    // 全额还款后不再收取滞纳金和利息
+    clock.set(Utc.with_ymd_and_hms(2026, 3, 5, 9, 0, 0).unwrap());
+    system
+        .pay_credit_card(card_id, 1, 1, cny(611), None)
+        .unwrap();
+    close_on(3, 31);
+    let third = system.generate_card_statement(manager_id, card_id).unwrap();
+    assert!(third.late_fee.is_zero());
+    assert!(third.interest.is_zero());
+    assert!(third.closing_balance.is_zero());
+    assert!(third.minimum_due.is_zero());
+    assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+}
+
+#[test]
+fn transactions_carry_id_time_branch_and_balance() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let account_id = open_account(&branch, "John Doe", checking()).unwrap();
+    clock.advance(chrono::Duration::hours(1));
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(100))
+        .unwrap();
+    clock.advance(chrono::Duration::hours(1));
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(30))
+        .unwrap();
+
+    let system = bank.bank_system.lock().unwrap();
+    let ids: Vec<u64> = system
+        .get_transactions()
+        .iter()
+        .map(|t| t.get_transaction().get_id())
+        .collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+
+    let withdrawal = system.get_transaction(3).unwrap().get_transaction();
+    assert_eq!(
+        withdrawal.get_timestamp(),
+        Utc.with_ymd_and_hms(2026, 1, 1, 11, 0, 0).unwrap()
+    );
+    assert_eq!(withdrawal.get_branch_id(), Some(1));
+    assert_eq!(withdrawal.get_branch_address(), Some("123 Main St"));
+    assert_eq!(withdrawal.get_balance_after(), Some(cny(70)));
+
+    let between = system.get_transactions_between(
+        Utc.with_ymd_and_hms(2026, 1, 1, 10, 0, 0).unwrap(),
+        Utc.with_ymd_and_hms(2026, 1, 1, 11, 0, 0).unwrap(),
+    );
+    assert_eq!(between.len(), 1);
+    assert_eq!(between[0].get_transaction().get_id(), 2);
+    assert_eq!(system.get_branch_transactions(1).len(), 3);
+    assert!(system.get_branch_transactions(2).is_empty());
+}
+
+#[test]
+fn query_transactions_and_statement() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let other = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    on_duty(&mut other.lock().unwrap(), BankTeller::new(2));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let jane = open_account(&branch, "Jane Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(john, cny(500)).unwrap();
+
+    clock.set(Utc.with_ymd_and_hms(2026, 2, 1, 9, 0, 0).unwrap());
+    other.lock().unwrap().deposit(john, cny(200)).unwrap();
+    other
+        .lock()
+        .unwrap()
+        .transfer(john, jane, cny(150))
+        .unwrap();
+    branch.lock().unwrap().withdraw(john, cny(50)).unwrap();
+
+    clock.set(Utc.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap());
+    branch.lock().unwrap().deposit(john, cny(1)).unwrap();
+
+    let system = bank.bank_system.lock().unwrap();
+    let query = TransactionQuery::new()
+        .account(jane)
+        .kind(TransactionKind::Transfer);
+    assert_eq!(system.query_transactions(&query).total, 1);
+    let query = TransactionQuery::new().teller(2);
+    assert_eq!(system.query_transactions(&query).total, 2);
+    let query = TransactionQuery::new()
+        .branch(1)
+        .kind(TransactionKind::Deposit)
+        .kind(TransactionKind::Withdrawal);
+    assert_eq!(system.query_transactions(&query).total, 3);
+
+    // 分页: 第二页只有剩下的一条
+    let query = TransactionQuery::new().account(john).page(1, 5);
+    let page = system.query_transactions(&query);
+    assert_eq!(page.total, 6);
+    assert_eq!(page.transactions.len(), 1);
+    assert_eq!(page.transactions[0].get_transaction().get_id(), 7);
+
+    let statement = system
+        .account_statement(
+            john,
+            Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(statement.opening_balance, cny(500));
+    assert_eq!(statement.closing_balance, cny(500));
+    let amounts: Vec<Money> = statement.lines.iter().map(|l| l.amount).collect();
+    assert_eq!(amounts, vec![cny(200), cny(-150), cny(-50)]);
+    assert_eq!(statement.lines[1].balance, cny(550));
+}
+
+/// TestStore 每个测试独立的临时数据目录, 测试结束时(包括断言失败)删除
+struct TestStore {
+    dir: PathBuf,
+}
+
+impl TestStore {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("bank-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Self { dir }
+    }
+
+    // 打开(或重启)目录里的银行
+    fn try_open(&self, clock: impl Clock + 'static) -> Result<Bank, BankError> {
+        Bank::open(&self.dir, cny(10000), Box::new(clock))
+    }
+
+    fn open(&self) -> Bank {
+        self.try_open(SystemClock).unwrap()
+    }
+
+    fn open_with(&self, clock: &ManualClock) -> Bank {
+        self.try_open(clock.clone()).unwrap()
+    }
+
+    // 目录里的文件, 用来放配置、牌价和导出的日志
+    fn file(&self, name: &str) -> PathBuf {
+        fs::create_dir_all(&self.dir).unwrap();
+        self.dir.join(name)
+    }
+}
+
+impl Drop for TestStore {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// 恢复前后需要一致的状态: 各科目余额、交易明细和信用卡账单
+fn state_of(bank: &Bank) -> (Vec<TrialBalanceLine>, Vec<String>, usize) {
+    let system = bank.bank_system.lock().unwrap();
+    let lines = system
+        .get_ledger()
+        .trial_balance()
+        .unwrap()
+        .get_lines()
+        .to_vec();
+    let transactions = system
+        .get_transactions()
+        .iter()
+        .map(|t| {
+            format!(
+                "#{} {} {}",
+                t.get_transaction().get_id(),
+                t.get_transaction().get_timestamp(),
+                t.get_transaction_description()
+            )
+        })
+        .collect();
+    let statements = system.get_credit_card(1).unwrap().get_statements().len();
+    (lines, transactions, statements)
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn journal_replay_recovers_state() {
+    let store = TestStore::new("replay");
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+
+    let mut bank = store.open_with(&clock);
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let savings = AccountKind::Savings {
+        annual_rate: Decimal::new(2, 2),
+        monthly_withdrawal_cap: 6,
+    };
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let bob = open_account(&branch, "Bob Smith", savings).unwrap();
+    branch.lock().unwrap().deposit(john, cny(500)).unwrap();
+    clock.advance(chrono::Duration::hours(1));
+    branch.lock().unwrap().deposit(bob, cny(300)).unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .transfer(john, bob, cny(120))
+        .unwrap();
+    branch.lock().unwrap().withdraw(bob, cny(20)).unwrap();
+    let loan_id = branch
+        .lock()
+        .unwrap()
+        .originate_loan(john, cny(1200), Decimal::new(6, 2), 12)
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .repay_loan(loan_id, cny(150))
+        .unwrap();
+    let card_id = branch
+        .lock()
+        .unwrap()
+        .issue_credit_card(john, cny(1000), Decimal::new(18, 2), cny(50))
+        .unwrap();
+    let manager_id = head_office(&mut bank);
+    {
+        let mut system = bank.bank_system.lock().unwrap();
+        system
+            .charge_credit_card(manager_id, card_id, "Book Store".to_string(), cny(200))
+            .unwrap();
+        system.generate_card_statement(manager_id, card_id).unwrap();
+    }
+    bank.accrue_interest(NaiveDate::from_ymd_opt(2026, 3, 1).unwrap())
+        .unwrap();
+    let before = state_of(&bank);
+    let outstanding = bank
+        .bank_system
+        .lock()
+        .unwrap()
+        .get_loan(loan_id)
+        .unwrap()
+        .outstanding();
+    drop((bank, branch));
+
+    // 重启时时钟已经走到别的时间, 交易时间仍然来自日志
+    clock.advance(chrono::Duration::days(30));
+    let bank = store.open_with(&clock);
+    assert_eq!(state_of(&bank), before);
+    assert_eq!(bank.get_branches().len(), 1);
+    assert_eq!(bank.get_total_cash(), cny(10000));
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_loan(loan_id).unwrap().outstanding(), outstanding);
+    let owner = system.get_account(bob).unwrap().get_owners()[0];
+    assert_eq!(system.get_customer(owner).unwrap().get_name(), "Bob Smith");
+}
+
+#[test]
+fn snapshot_then_replay_tail() {
+    let store = TestStore::new("snapshot");
+
+    let mut bank = store.open();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(john, cny(500)).unwrap();
+    bank.snapshot().unwrap();
+    branch.lock().unwrap().withdraw(john, cny(70)).unwrap();
+    drop((bank, branch));
+
+    let bank = store.open();
+    {
+        let system = bank.bank_system.lock().unwrap();
+        assert_eq!(system.get_balance(john), Ok(cny(430)));
+        assert_eq!(system.get_branch_cash(1), cny(1430));
+        assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+        // 快照之前的交易明细从快照恢复, 之后的交易被重放, 交易ID接着快照继续分配
+        let ids: Vec<u64> = system
+            .get_transactions()
+            .iter()
+            .map(|t| t.get_transaction().get_id())
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        let statement = system
+            .account_statement(john, DateTime::UNIX_EPOCH, system.now())
+            .unwrap();
+        let amounts: Vec<Money> = statement.lines.iter().map(|l| l.amount).collect();
+        assert_eq!(amounts, vec![cny(500), cny(-70)]);
+        assert_eq!(
+            system.get_transaction(1).unwrap().get_kind(),
+            TransactionKind::OpenAccount
+        );
+        let stats = system.get_teller_stats(1);
+        assert_eq!(stats[0].transactions, 3);
+    }
+
+    // 新操作继续追加到同一个日志
+    let branch = Arc::clone(&bank.get_branches()[0]);
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    branch.lock().unwrap().deposit(john, cny(30)).unwrap();
+    drop((bank, branch));
+    let bank = store.open();
+    assert_eq!(
+        bank.bank_system.lock().unwrap().get_balance(john),
+        Ok(cny(460))
+    );
+}
+
+#[test]
+fn torn_and_corrupted_journal() {
+    let store = TestStore::new("torn");
+
+    let mut bank = store.open();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(john, cny(500)).unwrap();
+    drop((bank, branch));
+
+    // 崩溃时最后一行只写了一半: 丢弃该记录并截断
+    let path = store.file(JOURNAL_FILE);
+    let intact = fs::read(&path).unwrap();
+    let mut torn = intact.clone();
+    torn.extend_from_slice(b"1234abcd {\"seq\":5,\"times");
+    fs::write(&path, &torn).unwrap();
+    let bank = store.open();
+    assert_eq!(
+        bank.bank_system.lock().unwrap().get_balance(john),
+        Ok(cny(500))
+    );
+    drop(bank);
+    assert_eq!(fs::read(&path).unwrap(), intact);
+
+    // 中间的记录被改动: 校验和不匹配, 拒绝恢复
+    let text = String::from_utf8(intact).unwrap();
+    fs::write(&path, text.replacen("John Doe", "Jane Doe", 1)).unwrap();
+    assert!(matches!(
+        store.try_open(SystemClock),
+        Err(BankError::JournalCorrupted { line: 3 })
+    ));
+}
+
+#[test]
+fn failed_journal_write_rolls_back() {
+    let store = TestStore::new("rollback");
+
+    let mut bank = store.open();
+    let manager_id = head_office(&mut bank);
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    // 记下收到通知的交易
+    struct Seen(Vec<u64>);
+    impl TransactionListener for Seen {
+        fn on_transaction(&mut self, ts: &dyn TransactionDescription, _: &BankSystem) {
+            self.0.push(ts.get_transaction().get_id());
+        }
+    }
+    let seen = Arc::new(Mutex::new(Seen(Vec::new())));
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .subscribe(Arc::clone(&seen) as Arc<Mutex<dyn TransactionListener>>);
+    branch.lock().unwrap().deposit(john, cny(500)).unwrap();
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .set_fee_schedule(
+            manager_id,
+            FeeSchedule {
+                withdrawal_fee: Some(cny(2)),
+                ..FeeSchedule::default()
+            },
+        )
+        .unwrap();
+    let state = |bank: &Bank| {
+        let system = bank.bank_system.lock().unwrap();
+        (
+            system.get_balance(john),
+            system.get_branch_cash(1),
+            system.get_transactions().len(),
+            system.get_ledger().get_entries().len(),
+            system.get_audit_log().head().to_string(),
+        )
+    };
+    let before = state(&bank);
+
+    // 磁盘写满: 取款和手续费都已经过账, 最后写日志失败, 整个操作撤销
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .journal
+        .as_mut()
+        .unwrap()
+        .file = OpenOptions::new().write(true).open("/dev/full").unwrap();
+    assert!(matches!(
+        branch.lock().unwrap().withdraw(john, cny(100)),
+        Err(BankError::Storage(_))
+    ));
+    assert_eq!(state(&bank), before);
+    // 没能截掉写了一半的记录, 重新打开之前不再写日志
+    assert!(matches!(
+        branch.lock().unwrap().deposit(john, cny(30)),
+        Err(BankError::Storage(_))
+    ));
+    assert_eq!(state(&bank), before);
+    // 撤销的交易没有通知监听者
+    assert_eq!(seen.lock().unwrap().0.len(), 1);
+    drop((bank, branch));
+
+    // 重启后和失败前的状态相同, 之后的操作正常写入
+    let bank = store.open();
+    assert_eq!(state(&bank), before);
+    let branch = Arc::clone(&bank.get_branches()[0]);
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    branch.lock().unwrap().withdraw(john, cny(100)).unwrap();
+    drop(branch);
+    drop(bank);
+    let bank = store.open();
+    assert_eq!(
+        bank.bank_system.lock().unwrap().get_balance(john),
+        Ok(cny(398))
+    );
+}
+
+#[test]
+fn bank_system_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BankSystem>();
+    assert_send_sync::<BankBranch>();
+    assert_send_sync::<Bank>();
+}
+
+#[test]
+fn concurrent_tellers_do_not_lose_updates() {
+    const BRANCHES: usize = 4;
+    const THREADS_PER_BRANCH: usize = 4;
+    const ROUNDS: usize = 200;
+
+    let mut bank = Bank::new(cny(100000)).unwrap();
+    let mut branches = Vec::new();
+    let mut customers = Vec::new();
+    for b in 0..BRANCHES {
+        let branch = add_branch(&mut bank, format!("{} Main St", b + 1), cny(10000));
+        for t in 0..THREADS_PER_BRANCH {
+            on_duty(
+                &mut branch.lock().unwrap(),
+                BankTeller::new(b * THREADS_PER_BRANCH + t + 1),
+            );
+        }
+        let account_id = open_account(&branch, &format!("Customer {}", b + 1), checking()).unwrap();
+        customers.push(account_id);
+        branches.push(branch);
+    }
+
+    // 每个线程: 存 10, 取 3, 转 2 给下一家分行的客户
+    std::thread::scope(|scope| {
+        for (b, branch) in branches.iter().enumerate() {
+            for _ in 0..THREADS_PER_BRANCH {
+                let account_id = customers[b];
+                let next_account_id = customers[(b + 1) % BRANCHES];
+                scope.spawn(move || {
+                    for _ in 0..ROUNDS {
+                        branch.lock().unwrap().deposit(account_id, cny(10)).unwrap();
+                        branch.lock().unwrap().withdraw(account_id, cny(3)).unwrap();
+                        branch
+                            .lock()
+                            .unwrap()
+                            .transfer(account_id, next_account_id, cny(2))
+                            .unwrap();
+                    }
+                });
+            }
+        }
+    });
+
+    let per_branch = i64::try_from(THREADS_PER_BRANCH * ROUNDS).unwrap();
+    let system = bank.bank_system.lock().unwrap();
+    for (b, account_id) in customers.iter().enumerate() {
+        // 转出的 2 和从上一家分行转入的 2 相互抵消
+        assert_eq!(system.get_balance(*account_id), Ok(cny(7 * per_branch)));
+        assert_eq!(system.get_branch_cash(b + 1), cny(10000 + 7 * per_branch));
+    }
+    assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+
+    let ids: Vec<u64> = system
+        .get_transactions()
+        .iter()
+        .map(|t| t.get_transaction().get_id())
+        .collect();
+    let expected = BRANCHES * (1 + THREADS_PER_BRANCH * ROUNDS * 3);
+    assert_eq!(ids.len(), expected);
+    assert!(ids.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn teller_assignment_respects_shift_and_breaks() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+    {
+        let mut branch = branch.lock().unwrap();
+        on_duty(
+            &mut branch,
+            BankTeller::new(1).with_shift(Shift::new(time(8), time(12))),
+        );
+        on_duty(
+            &mut branch,
+            BankTeller::new(2).with_shift(Shift::new(time(12), time(18))),
+        );
+        on_duty(&mut branch, BankTeller::new(3));
+    }
+    let account_id = open_account(&branch, "John Doe", checking()).unwrap();
+    let deposit = || branch.lock().unwrap().deposit(account_id, cny(10)).unwrap();
+    deposit();
+    deposit();
+    branch.lock().unwrap().start_break(1).unwrap();
+    deposit();
+    branch.lock().unwrap().end_break(1).unwrap();
+    clock.set(Utc.with_ymd_and_hms(2026, 1, 1, 13, 0, 0).unwrap());
+    deposit();
+    deposit();
+
+    let system = bank.bank_system.lock().unwrap();
+    let tellers: Vec<usize> = system
+        .get_transactions()
+        .iter()
+        .map(|t| t.get_transaction().get_teller_id())
+        .collect();
+    // 轮询: 9点只有 1、3 在班, 1 休息时只剩 3, 13点只有 2、3 在班
+    assert_eq!(tellers, vec![1, 3, 1, 3, 2, 3]);
+}
+
+#[test]
+fn customer_queue_and_teller_stats() {
+    let (bank, branch, john) = setup();
+    let mut branch = branch.lock().unwrap();
+    on_duty(&mut branch, BankTeller::new(2));
+    branch.set_assignment_policy(Box::new(LeastBusy));
+    let mut customer = |name: &str| {
+        let document = IdentityDocument::new(DocumentKind::Passport, name.to_string());
+        let date_of_birth = NaiveDate::from_ymd_opt(1990, 1, 1).unwrap();
+        branch
+            .register_customer(
+                name.to_string(),
+                date_of_birth,
+                "1 Test Rd".to_string(),
+                document,
+            )
+            .unwrap()
+    };
+    let jane_id = customer("Jane Doe");
+    let bob_id = customer("Bob Smith");
+    let jane = branch.open_account(vec![jane_id], checking()).unwrap();
+    branch.open_account(vec![bob_id], checking()).unwrap();
+    for customer_id in [1, jane_id, bob_id] {
+        branch.enqueue_customer(customer_id);
+    }
+
+    // 最空闲: 柜员1 办了两次开户, 柜员2 办了一次
+    assert_eq!(branch.call_next_customer(), Ok(Some((1, 2))));
+    assert_eq!(branch.call_next_customer(), Ok(Some((jane_id, 1))));
+    assert_eq!(
+        branch.call_next_customer(),
+        Err(BankError::NoTellerAvailable)
+    );
+    assert_eq!(branch.get_queue().len(), 1);
+    assert_eq!(
+        branch.get_tellers()[0].get_status(),
+        TellerStatus::Busy {
+            customer_id: jane_id
+        }
+    );
+
+    // 叫到号的客户由接待他的柜员办理
+    branch.deposit(jane, cny(100)).unwrap();
+    branch.deposit(john, cny(50)).unwrap();
+    branch.withdraw(john, cny(20)).unwrap();
+    branch.finish_service(1).unwrap();
+    assert_eq!(branch.call_next_customer(), Ok(Some((bob_id, 1))));
+    assert_eq!(branch.call_next_customer(), Ok(None));
+
+    drop(branch);
+    let stats = bank.bank_system.lock().unwrap().get_teller_stats(1);
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].teller_id, 1);
+    assert_eq!(stats[0].transactions, 3);
+    assert_eq!(stats[0].customers_served, 3);
+    assert_eq!(
+        stats[0].by_kind.get(&TransactionKind::OpenAccount),
+        Some(&2)
+    );
+    assert_eq!(stats[1].teller_id, 2);
+    assert_eq!(stats[1].transactions, 3);
+    assert_eq!(stats[1].customers_served, 2);
+    assert_eq!(stats[1].by_kind.get(&TransactionKind::Deposit), Some(&1));
+    assert_eq!(stats[1].by_kind.get(&TransactionKind::Withdrawal), Some(&1));
+}
+
+#[test]
+fn seeded_random_assignment_is_reproducible() {
+    let tellers: Vec<BankTeller> = (1..=5).map(BankTeller::new).collect();
+    let candidates: Vec<&BankTeller> = tellers.iter().collect();
+    let picks = |seed| {
+        let mut policy = SeededRandom::new(seed);
+        (0..20)
+            .map(|_| policy.choose(&candidates))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(picks(7), picks(7));
+    assert!(picks(7).iter().all(|idx| *idx < candidates.len()));
+}
+
+#[test]
+fn end_of_day_settlement() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let account_id = open_account(&branch, "John Doe", checking()).unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(300))
+        .unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(120))
+        .unwrap();
+
+    // 日终结算由分行行长办理
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .settle(1, cny(1170), Decimal::new(5, 1)),
+        Err(BankError::PermissionDenied {
+            teller_id: 1,
+            operation: Operation::ManageCash,
+        })
+    );
+    on_duty(
+        &mut branch.lock().unwrap(),
+        BankTeller::new(9).with_role(TellerRole::BranchManager),
+    );
+    // 应有 1000 + 300 - 120 = 1180, 实际清点 1170, 短款 10, 上缴一半
+    let report = branch
+        .lock()
+        .unwrap()
+        .settle(9, cny(1170), Decimal::new(5, 1))
+        .unwrap();
+    assert_eq!(report.opening_cash, cny(1000));
+    assert_eq!(report.cash_in, cny(300));
+    assert_eq!(report.cash_out, cny(120));
+    assert_eq!(report.expected_cash, cny(1180));
+    assert_eq!(report.discrepancy, cny(-10));
+    assert!(report.has_discrepancy());
+    assert_eq!(report.transferred_to_headquarters, cny(585));
+    assert_eq!(report.closing_cash, cny(585));
+    assert_eq!(branch.lock().unwrap().get_cash_on_hand(), cny(585));
+    assert_eq!(bank.get_total_cash(), cny(10585));
+    {
+        let system = bank.bank_system.lock().unwrap();
+        let ledger = system.get_ledger();
+        assert_eq!(
+            ledger.balance(LedgerAccount::CashOverShort, Currency::CNY),
+            cny(10)
+        );
+        assert!(ledger.trial_balance().unwrap().is_balanced());
+        let transfer = system
+            .get_transaction(report.transfer_transaction_id.unwrap())
+            .unwrap();
+        assert_eq!(transfer.get_kind(), TransactionKind::CashTransfer);
+        assert_eq!(
+            transfer.get_transaction_description(),
+            "Branch 1 sent 585.00 CNY to headquarters"
+        );
+    }
+
+    // 第二天从上次留下的现金开始, 没有清点数据时按账面结算
+    clock.advance(chrono::Duration::days(1));
+    branch.lock().unwrap().deposit(account_id, cny(50)).unwrap();
+    let manager_id = head_office(&mut bank);
+    let reports = bank
+        .end_of_day(manager_id, &BTreeMap::new(), Decimal::ZERO)
+        .unwrap();
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(
+        report.business_date,
+        NaiveDate::from_ymd_opt(2026, 1, 2).unwrap()
+    );
+    assert_eq!(report.opening_cash, cny(585));
+    assert_eq!(report.cash_in, cny(50));
+    assert_eq!(report.expected_cash, cny(635));
+    assert!(!report.has_discrepancy());
+    assert_eq!(report.transfer_transaction_id, None);
+    assert_eq!(bank.bank_system.lock().unwrap().get_settlements().len(), 2);
+}
+
+#[test]
+fn vault_replenishes_branch_cash() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch1 = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    let branch2 = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    on_duty(&mut branch1.lock().unwrap(), BankTeller::new(1));
+    on_duty(&mut branch2.lock().unwrap(), BankTeller::new(2));
+    let account_id = open_account(&branch1, "John Doe", checking()).unwrap();
+    // 在分行2存款, 在分行1取款, 分行1现金降到 200
+    branch2
+        .lock()
+        .unwrap()
+        .deposit(account_id, cny(1500))
+        .unwrap();
+    branch1
+        .lock()
+        .unwrap()
+        .withdraw(account_id, cny(800))
+        .unwrap();
+
+    let manager_id = head_office(&mut bank);
+    assert_eq!(
+        bank.set_branch_cash_limits(manager_id, 1, cny(2000), cny(500)),
+        Err(BankError::InvalidCashLimits {
+            min: cny(2000),
+            max: cny(500),
+        })
+    );
+    for branch_id in [1, 2] {
+        bank.set_branch_cash_limits(manager_id, branch_id, cny(500), cny(2000))
+            .unwrap();
+    }
+    on_duty(
+        &mut branch2.lock().unwrap(),
+        BankTeller::new(20).with_role(TellerRole::BranchManager),
+    );
+    let request_id = branch2
+        .lock()
+        .unwrap()
+        .request_replenishment(20, cny(400))
+        .unwrap();
+    bank.schedule_replenishment(manager_id, NaiveTime::from_hms_opt(18, 0, 0).unwrap())
+        .unwrap();
+
+    // 还没到补款时间
+    assert_eq!(
+        bank.run_scheduled_replenishment(),
+        Ok(ReplenishmentRun::default())
+    );
+    clock.set(Utc.with_ymd_and_hms(2026, 1, 1, 18, 30, 0).unwrap());
+    let transaction_ids = bank.run_scheduled_replenishment().unwrap().transaction_ids;
+    assert_eq!(transaction_ids.len(), 2);
+    // 每天只补一次
+    assert_eq!(
+        bank.run_scheduled_replenishment(),
+        Ok(ReplenishmentRun::default())
+    );
+
+    assert_eq!(branch1.lock().unwrap().get_cash_on_hand(), cny(2000));
+    assert_eq!(branch2.lock().unwrap().get_cash_on_hand(), cny(2900));
+    assert_eq!(bank.get_total_cash(), cny(7800));
+
+    let mut system = bank.bank_system.lock().unwrap();
+    assert_eq!(
+        system.get_replenishment_requests()[0].status,
+        ReplenishmentStatus::Fulfilled {
+            transaction_id: transaction_ids[0]
+        }
+    );
+    let top_up = system.get_transaction(transaction_ids[1]).unwrap();
+    assert_eq!(
+        top_up.get_transaction_description(),
+        "Headquarters sent 1800.00 CNY to branch 1"
+    );
+    assert_eq!(
+        system.fulfill_replenishment(manager_id, request_id),
+        Err(BankError::ReplenishmentNotPending(request_id))
+    );
+
+    let request_id = system.request_replenishment(2, 20, cny(8000)).unwrap();
+    assert_eq!(
+        system.fulfill_replenishment(manager_id, request_id),
+        Err(BankError::VaultCashShortfall {
+            vault_cash: cny(7800),
+            requested: cny(8000),
+        })
+    );
+    let small = system.request_replenishment(2, 20, cny(100)).unwrap();
+    drop(system);
+
+    // 调拨失败的申请跳过并报告, 不影响其他申请, 保持待处理等下次重试
+    clock.set(Utc.with_ymd_and_hms(2026, 1, 2, 18, 30, 0).unwrap());
+    let run = bank.run_scheduled_replenishment().unwrap();
+    assert_eq!(
+        run.failed,
+        vec![(
+            request_id,
+            BankError::VaultCashShortfall {
+                vault_cash: cny(7800),
+                requested: cny(8000),
+            }
+        )]
+    );
+    assert_eq!(run.transaction_ids.len(), 1);
+    assert_eq!(bank.get_total_cash(), cny(7700));
+    let system = bank.bank_system.lock().unwrap();
+    let requests = system.get_replenishment_requests();
+    assert_eq!(
+        requests[request_id - 1].status,
+        ReplenishmentStatus::Pending
+    );
+    assert_eq!(
+        requests[small - 1].status,
+        ReplenishmentStatus::Fulfilled {
+            transaction_id: run.transaction_ids[0]
+        }
+    );
+    assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+}
+
+#[test]
+fn customers_own_many_and_joint_accounts() {
+    let (mut bank, branch, john_account) = setup();
+    let manager_id = head_office(&mut bank);
+    let mut branch = branch.lock().unwrap();
+    let passport = IdentityDocument::new(DocumentKind::Passport, "E12345678".to_string());
+    let jane = branch
+        .register_customer(
+            "Jane Doe".to_string(),
+            NaiveDate::from_ymd_opt(1992, 11, 23).unwrap(),
+            "56 Birch Ln".to_string(),
+            passport.clone(),
+        )
+        .unwrap();
+    assert_eq!(
+        branch.register_customer(
+            "Someone Else".to_string(),
+            NaiveDate::from_ymd_opt(1980, 1, 1).unwrap(),
+            "1 Other St".to_string(),
+            passport.clone(),
+        ),
+        Err(BankError::DuplicateIdentityDocument(passport.clone()))
+    );
+
+    // John(客户1)和 Jane 的联名账户, 以及 Jane 自己的储蓄账户
+    let joint = branch
+        .open_account(vec![1, jane, jane], checking())
+        .unwrap();
+    let savings = AccountKind::Savings {
+        annual_rate: Decimal::new(2, 2),
+        monthly_withdrawal_cap: 6,
+    };
+    let jane_savings = branch.open_account(vec![jane], savings).unwrap();
+    assert_eq!(
+        branch.open_account(Vec::new(), checking()),
+        Err(BankError::NoAccountOwner)
+    );
+    assert_eq!(
+        branch.open_account(vec![jane, 42], checking()),
+        Err(BankError::CustomerNotFound(42))
+    );
+    drop(branch);
+
+    let mut system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_account(joint).unwrap().get_owners(), &[1, jane]);
+    let accounts = |system: &BankSystem, customer_id| -> Vec<usize> {
+        system
+            .get_customer_accounts(customer_id)
+            .iter()
+            .map(|a| a.get_account_id())
+            .collect()
+    };
+    assert_eq!(accounts(&system, 1), vec![john_account, joint]);
+    assert_eq!(accounts(&system, jane), vec![joint, jane_savings]);
+
+    let found = system.find_customer_by_document(&passport).unwrap();
+    assert_eq!(found.get_id(), jane);
+    assert_eq!(found.get_address(), "56 Birch Ln");
+    let by_name: Vec<usize> = system
+        .find_customers_by_name("jane doe")
+        .iter()
+        .map(|c| c.get_id())
+        .collect();
+    assert_eq!(by_name, vec![jane]);
+
+    // 后来加入的持有人
+    system
+        .add_account_owner(jane_savings, HEADQUARTERS_ID, manager_id, 1)
+        .unwrap();
+    assert_eq!(
+        accounts(&system, 1),
+        vec![john_account, joint, jane_savings]
+    );
+    assert_eq!(
+        system.add_account_owner(jane_savings, HEADQUARTERS_ID, manager_id, 42),
+        Err(BankError::CustomerNotFound(42))
+    );
+}
+
+#[test]
+fn account_ids_have_check_digits_and_survive_closing() {
+    let store = TestStore::new("account-ids");
+
+    let mut bank = store.open();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let jane = open_account(&branch, "Jane Doe", checking()).unwrap();
+    assert_eq!((john, jane), (10000008, 10000016));
+    assert!(AccountIdAllocator::is_valid(jane));
+    // 输错一位或相邻两位颠倒都通不过校验
+    assert!(!AccountIdAllocator::is_valid(10000018));
+    assert!(!AccountIdAllocator::is_valid(10000061));
+    // 办理业务时输错的账号与不存在的账号分开报错
+    assert_eq!(
+        branch.lock().unwrap().deposit(10000018, cny(10)),
+        Err(BankError::InvalidAccountNumber(10000018))
+    );
+    assert_eq!(
+        branch.lock().unwrap().transfer(john, 10000061, cny(10)),
+        Err(BankError::InvalidAccountNumber(10000061))
+    );
+    assert_eq!(
+        branch.lock().unwrap().deposit(10000024, cny(10)),
+        Err(BankError::AccountNotFound(10000024))
+    );
+
+    branch.lock().unwrap().deposit(jane, cny(100)).unwrap();
+    assert_eq!(branch.lock().unwrap().close_account(jane), Ok(cny(100)));
+    assert_eq!(
+        branch.lock().unwrap().deposit(jane, cny(10)),
+        Err(BankError::AccountClosed(jane))
+    );
+    bank.snapshot().unwrap();
+    drop((bank, branch));
+
+    // 重启后销户状态和分配器都被恢复, 新账户不会拿到旧账号
+    let bank = store.open();
+    let branch = Arc::clone(&bank.get_branches()[0]);
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    assert_eq!(
+        bank.bank_system
+            .lock()
+            .unwrap()
+            .get_account(jane)
+            .map(|a| a.get_status()),
+        Some(AccountStatus::Closed)
+    );
+    let bob = open_account(&branch, "Bob Smith", checking()).unwrap();
+    assert_eq!(bob, 10000024);
+}
+
+#[test]
+fn account_lifecycle_transitions() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 5, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(10000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let jane = open_account(&branch, "Jane Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(jane, cny(300)).unwrap();
+    let manager_id = head_office(&mut bank);
+
+    // 冻结期间存取款、转入转出都被拒绝, 也不能销户
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .freeze_account(
+            jane,
+            HEADQUARTERS_ID,
+            manager_id,
+            "court order 2026-17".to_string(),
+        )
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().deposit(jane, cny(10)),
+        Err(BankError::AccountFrozen(jane))
+    );
+    assert_eq!(
+        branch.lock().unwrap().withdraw(jane, cny(10)),
+        Err(BankError::AccountFrozen(jane))
+    );
+    branch.lock().unwrap().deposit(john, cny(50)).unwrap();
+    assert_eq!(
+        branch.lock().unwrap().transfer(john, jane, cny(10)),
+        Err(BankError::AccountFrozen(jane))
+    );
+    assert_eq!(
+        branch.lock().unwrap().close_account(jane),
+        Err(BankError::AccountFrozen(jane))
+    );
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .unfreeze_account(
+            jane,
+            HEADQUARTERS_ID,
+            manager_id,
+            "order lifted".to_string(),
+        )
+        .unwrap();
+
+    // 一年没有交易的账户转为睡眠户: 可以入账, 激活前不能出账
+    clock.advance(chrono::Duration::days(200));
+    branch.lock().unwrap().withdraw(john, cny(20)).unwrap();
+    clock.advance(chrono::Duration::days(200));
+    let today = clock.now().date_naive();
+    let dormant = bank
+        .bank_system
+        .lock()
+        .unwrap()
+        .mark_dormant_accounts(manager_id, today, 365)
+        .unwrap();
+    assert_eq!(dormant, vec![jane]);
+    branch
+        .lock()
+        .unwrap()
+        .transfer(john, jane, cny(30))
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().withdraw(jane, cny(10)),
+        Err(BankError::AccountDormant(jane))
+    );
+    branch.lock().unwrap().reactivate_account(jane).unwrap();
+
+    // 信用卡还有欠款时不能销户, 销户后信用卡不能再消费、还款或出账单
+    let card_id = branch
+        .lock()
+        .unwrap()
+        .issue_credit_card(jane, cny(500), Decimal::new(18, 2), cny(50))
+        .unwrap();
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .charge_credit_card(manager_id, card_id, "Book Store".to_string(), cny(40))
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().close_account(jane),
+        Err(BankError::AccountHasOutstandingCredit(jane))
+    );
+    branch
+        .lock()
+        .unwrap()
+        .pay_credit_card(card_id, cny(40))
+        .unwrap();
+
+    // 销户时剩余余额以现金支付, 分行现金相应减少; 透支的账户要先还清
+    assert_eq!(branch.lock().unwrap().close_account(jane), Ok(cny(330)));
+    assert_eq!(
+        branch.lock().unwrap().withdraw(jane, cny(10)),
+        Err(BankError::AccountClosed(jane))
+    );
+    {
+        let mut system = bank.bank_system.lock().unwrap();
+        assert_eq!(
+            system.charge_credit_card(manager_id, card_id, "Book Store".to_string(), cny(5)),
+            Err(BankError::AccountClosed(jane))
+        );
+        assert_eq!(
+            system.pay_credit_card(card_id, 1, 1, cny(5), None),
+            Err(BankError::AccountClosed(jane))
+        );
+        assert_eq!(
+            system
+                .generate_card_statement(manager_id, card_id)
+                .map(|_| ()),
+            Err(BankError::AccountClosed(jane))
+        );
+    }
+    // 贷款没有还清也不能销户
+    let amy = open_account(&branch, "Amy Lee", checking()).unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .originate_loan(amy, cny(100), Decimal::new(6, 2), 12)
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().close_account(amy),
+        Err(BankError::AccountHasOutstandingCredit(amy))
+    );
+    let overdraft = open_account(
+        &branch,
+        "Bob Smith",
+        AccountKind::Checking {
+            overdraft_limit: cny(100),
+        },
+    )
+    .unwrap();
+    branch.lock().unwrap().withdraw(overdraft, cny(40)).unwrap();
+    assert_eq!(
+        branch.lock().unwrap().close_account(overdraft),
+        Err(BankError::AccountOverdrawn {
+            account_id: overdraft,
+            balance: cny(-40),
+        })
+    );
+
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(
+        system.get_branch_cash(1),
+        cny(1000 + 300 + 50 - 20 + 40 - 330 - 40)
+    );
+    assert_eq!(
+        system.get_account(jane).map(|a| a.get_status()),
+        Some(AccountStatus::Closed)
+    );
+    let query = TransactionQuery::new()
+        .account(jane)
+        .kind(TransactionKind::AccountStatusChange);
+    let changes: Vec<String> = system
+        .query_transactions(&query)
+        .transactions
+        .iter()
+        .map(|t| t.get_transaction_description())
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            format!("Teller 100 froze account {}: court order 2026-17", jane),
+            format!("Teller 100 unfroze account {}: order lifted", jane),
+            format!(
+                "Bank marked dormant account {}: no activity since 2026-01-05",
+                jane
+            ),
+            format!(
+                "Teller 1 reactivated account {}: customer identity verified",
+                jane
+            ),
+            format!("Teller 1 closed account {}: paid out 330.00 CNY", jane),
+        ]
+    );
+}
+
+#[test]
+fn policy_rules_from_config() {
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 3, 30, 9, 0, 0).unwrap());
+    let mut bank = Bank::with_clock(cny(100000), Box::new(clock.clone())).unwrap();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(50000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(2));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let jane = open_account(&branch, "Jane Doe", checking()).unwrap();
+
+    let manager_id = head_office(&mut bank);
+    let store = TestStore::new("policy");
+    let path = store.file("policy.json");
+    let config = |rules: &str| fs::write(&path, format!("{{\"rules\": [{}]}}", rules)).unwrap();
+    config(
+        r#"{"rule": "withdrawal_limit", "period": "daily", "limit": {"amount": "1", "currency": "USD"}}"#,
+    );
+    assert_eq!(
+        bank.load_policy(manager_id, &path),
+        Err(MoneyError::CurrencyMismatch(Currency::CNY, Currency::USD).into())
+    );
+    config(&format!(
+        r#"{{"rule": "withdrawal_limit", "period": "daily", "limit": {{"amount": "1000", "currency": "CNY"}}}},
+           {{"rule": "withdrawal_limit", "period": "monthly", "limit": {{"amount": "1500", "currency": "CNY"}}}},
+           {{"rule": "withdrawal_limit", "period": "daily", "limit": {{"amount": "100", "currency": "CNY"}}, "account_id": {}}},
+           {{"rule": "large_cash_approval", "threshold": {{"amount": "10000", "currency": "CNY"}}}}"#,
+        jane
+    ));
+    bank.load_policy(manager_id, &path).unwrap();
+    // 代码中安装的规则同样校验金额
+    let mut invalid = PolicyEngine::new();
+    invalid.add_rule(Box::new(LargeCashApprovalRule::new(cny(-1))));
+    assert_eq!(
+        bank.bank_system
+            .lock()
+            .unwrap()
+            .set_policy(manager_id, invalid),
+        Err(BankError::InvalidAmount(cny(-1)))
+    );
+
+    // 大额现金存款需要另一名柜员复核, 复核人记录在交易上
+    assert_eq!(
+        branch.lock().unwrap().deposit(john, cny(10000)),
+        Err(BankError::ApprovalRequired {
+            amount: cny(10000),
+            threshold: cny(10000),
+        })
+    );
+    // 轮询下一位是柜员2, 不能复核自己经办的交易
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .deposit_with_approval(john, cny(10000), 2),
+        Err(BankError::InvalidApprover(2))
+    );
+    branch
+        .lock()
+        .unwrap()
+        .deposit_with_approval(john, cny(10000), 2)
+        .unwrap();
+    branch.lock().unwrap().deposit(jane, cny(500)).unwrap();
+
+    // 取款和转出合计计入每日限额, 单个账户可以配置更低的限额
+    branch.lock().unwrap().withdraw(john, cny(600)).unwrap();
+    branch
+        .lock()
+        .unwrap()
+        .transfer(john, jane, cny(300))
+        .unwrap();
+    assert_eq!(
+        branch.lock().unwrap().withdraw(john, cny(200)),
+        Err(BankError::WithdrawalAmountExceeded {
+            account_id: john,
+            period: LimitPeriod::Daily,
+            limit: cny(1000),
+            withdrawn: cny(900),
+        })
+    );
+    assert_eq!(
+        branch.lock().unwrap().withdraw(jane, cny(150)),
+        Err(BankError::WithdrawalAmountExceeded {
+            account_id: jane,
+            period: LimitPeriod::Daily,
+            limit: cny(100),
+            withdrawn: cny(0),
+        })
+    );
+
+    // 第二天每日限额重置, 但当月累计仍然受每月限额限制
+    clock.advance(chrono::Duration::days(1));
+    branch.lock().unwrap().withdraw(john, cny(500)).unwrap();
+    assert_eq!(
+        branch.lock().unwrap().withdraw(john, cny(200)),
+        Err(BankError::WithdrawalAmountExceeded {
+            account_id: john,
+            period: LimitPeriod::Monthly,
+            limit: cny(1500),
+            withdrawn: cny(1400),
+        })
+    );
+    // 进入新的月份后每月限额也重置
+    clock.advance(chrono::Duration::days(2));
+    branch.lock().unwrap().withdraw(john, cny(200)).unwrap();
+
+    // 兑换也计入取款限额, 外币账户的金额按中间价 7.15 折成人民币后比较
+    let rates = store.file("rates.csv");
+    fs::write(&rates, "currency,bid,ask\nUSD,7.10,7.20\n").unwrap();
+    bank.load_fx_rates(manager_id, &rates).unwrap();
+    let dollars = || {
+        branch.lock().unwrap().open_account_in(
+            vec![1],
+            AccountKind::Checking {
+                overdraft_limit: Money::from_major(0, Currency::USD),
+            },
+            Currency::USD,
+        )
+    };
+    let (usd1, usd2) = (dollars().unwrap(), dollars().unwrap());
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .exchange_currency(john, usd1, cny(900)),
+        Err(BankError::WithdrawalAmountExceeded {
+            account_id: john,
+            period: LimitPeriod::Daily,
+            limit: cny(1000),
+            withdrawn: cny(200),
+        })
+    );
+    branch
+        .lock()
+        .unwrap()
+        .exchange_currency(john, usd1, cny(700))
+        .unwrap();
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .transfer(usd1, usd2, Money::from_major(150, Currency::USD)),
+        Err(BankError::WithdrawalAmountExceeded {
+            account_id: usd1,
+            period: LimitPeriod::Daily,
+            limit: cny(1000),
+            withdrawn: cny(0),
+        })
+    );
+
+    // 现金还贷、还信用卡和销户支付同样需要复核大额现金
+    let mut system = bank.bank_system.lock().unwrap();
+    let required = BankError::ApprovalRequired {
+        amount: cny(12000),
+        threshold: cny(10000),
+    };
+    let loan_id = system
+        .originate_loan(jane, 1, 1, cny(20000), Decimal::new(6, 2), 12)
+        .unwrap();
+    assert_eq!(
+        system.repay_loan(loan_id, 1, 1, cny(12000), None),
+        Err(required.clone())
+    );
+    system
+        .repay_loan(loan_id, 1, 1, cny(12000), Some(2))
+        .unwrap();
+    let card_id = system
+        .issue_credit_card(jane, 1, 1, cny(20000), Decimal::new(18, 2), cny(50))
+        .unwrap();
+    system
+        .charge_credit_card(manager_id, card_id, "Jeweller".to_string(), cny(12000))
+        .unwrap();
+    assert_eq!(
+        system.pay_credit_card(card_id, 1, 1, cny(12000), None),
+        Err(required.clone())
+    );
+    system
+        .pay_credit_card(card_id, 1, 1, cny(12000), Some(2))
+        .unwrap();
+    let bob = system.open_account(vec![2], checking(), 1, 1).unwrap();
+    system.deposit(bob, 1, 1, cny(12000), Some(2)).unwrap();
+    assert_eq!(system.close_account(bob, 1, 1, None), Err(required.clone()));
+    assert_eq!(system.close_account(bob, 1, 1, Some(2)), Ok(cny(12000)));
+
+    let approved: Vec<usize> = system
+        .get_transactions()
+        .iter()
+        .filter(|t| t.get_transaction().get_teller_id() != SYSTEM_TELLER_ID)
+        .filter_map(|t| t.get_transaction().get_approved_by())
+        .collect();
+    assert_eq!(approved, vec![2; 5]);
+}
+
+#[test]
+fn aml_monitor_raises_alerts_for_review() {
+    let mut bank = Bank::new(cny(100000)).unwrap();
+    let manager_id = head_office(&mut bank);
+    let branches: Vec<Arc<Mutex<BankBranch>>> = (1..=3)
+        .map(|id| {
+            let branch = add_branch(&mut bank, format!("{} Main St", id), cny(1000));
+            on_duty(&mut branch.lock().unwrap(), BankTeller::new(id));
+            branch
+        })
+        .collect();
+    let mut config = AmlConfig::new(cny(10000));
+    config.cross_branch_count = 2;
+    let aml = bank.start_aml_monitor(config);
+    let home = &branches[0];
+    on_duty(
+        &mut home.lock().unwrap(),
+        BankTeller::new(11).with_role(TellerRole::Supervisor),
+    );
+    let john = open_account(home, "John Doe", checking()).unwrap();
+    let jane = open_account(home, "Jane Doe", checking()).unwrap();
+    let bob = open_account(home, "Bob Smith", checking()).unwrap();
+
+    // 多笔略低于申报门槛的现金存款, 之后再存也不重复预警
+    for _ in 0..4 {
+        home.lock().unwrap().deposit(john, cny(9500)).unwrap();
+    }
+    // 存入后很快转出
+    home.lock().unwrap().deposit(jane, cny(20000)).unwrap();
+    home.lock()
+        .unwrap()
+        .transfer(jane, bob, cny(19000))
+        .unwrap();
+    // 在开户行以外的两家分行取款
+    branches[1].lock().unwrap().withdraw(bob, cny(100)).unwrap();
+    branches[2].lock().unwrap().withdraw(bob, cny(100)).unwrap();
+    // 冲正的存款从窗口中去掉: 冲正后只有两笔略低于门槛的存款, 不预警
+    let amy = open_account(home, "Amy Lee", checking()).unwrap();
+    for _ in 0..2 {
+        home.lock().unwrap().deposit(amy, cny(9500)).unwrap();
+    }
+    let mistaken = bank
+        .bank_system
+        .lock()
+        .unwrap()
+        .get_transactions()
+        .last()
+        .unwrap()
+        .get_transaction()
+        .get_id();
+    home.lock()
+        .unwrap()
+        .reverse_transaction(mistaken, 11, "keyed twice".to_string())
+        .unwrap();
+    home.lock().unwrap().deposit(amy, cny(9500)).unwrap();
+
+    let mut system = bank.bank_system.lock().unwrap();
+    let mut monitor = aml.lock().unwrap();
+    let alerts: Vec<(AlertKind, usize, usize)> = monitor
+        .get_alerts()
+        .iter()
+        .map(|a| {
+            (
+                a.get_kind(),
+                a.get_account_id(),
+                a.get_transaction_ids().len(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        alerts,
+        vec![
+            (AlertKind::Structuring, john, 3),
+            (AlertKind::RapidMovement, jane, 2),
+            (AlertKind::CrossBranch, bob, 2),
+        ]
+    );
+    assert_eq!(
+        monitor.get_alerts()[0].get_detail(),
+        "3 cash deposits just under 10000.00 CNY within 7 days"
+    );
+
+    // 普通柜员不能复核预警
+    assert_eq!(
+        monitor.dismiss_alert(&mut system, 1, 1, 1, String::new()),
+        Err(BankError::PermissionDenied {
+            teller_id: 1,
+            operation: Operation::ReviewAlerts,
+        })
+    );
+    monitor
+        .dismiss_alert(&mut system, 1, 1, 11, "salary paid in cash".to_string())
+        .unwrap();
+    monitor
+        .escalate_alert(&mut system, 2, 1, 11, "reported to compliance".to_string())
+        .unwrap();
+    assert_eq!(
+        monitor.dismiss_alert(&mut system, 1, 1, 11, String::new()),
+        Err(BankError::AlertNotOpen(1))
+    );
+    let pending: Vec<usize> = monitor
+        .pending_alerts()
+        .iter()
+        .map(|a| a.get_id())
+        .collect();
+    assert_eq!(pending, vec![3]);
+    assert_eq!(monitor.get_alerts()[1].get_status(), AlertStatus::Escalated);
+    assert_eq!(monitor.get_alerts()[1].get_reviewed_by(), Some(11));
+    drop((system, monitor));
+
+    // 美元账户的金额按中间价 7.15 折成人民币与门槛比较: 1400 美元约合 10010 元
+    let store = TestStore::new("aml");
+    let rates = store.file("rates.csv");
+    fs::write(&rates, "currency,bid,ask\nUSD,7.10,7.20\n").unwrap();
+    bank.load_fx_rates(manager_id, &rates).unwrap();
+    let usd = |units| Money::from_major(units, Currency::USD);
+    let owner = bank
+        .bank_system
+        .lock()
+        .unwrap()
+        .get_account(john)
+        .unwrap()
+        .get_owners()[0];
+    let mut teller = home.lock().unwrap();
+    let mut dollars = || {
+        teller.open_account_in(
+            vec![owner],
+            AccountKind::Checking {
+                overdraft_limit: usd(0),
+            },
+            Currency::USD,
+        )
+    };
+    let (from, to) = (dollars().unwrap(), dollars().unwrap());
+    teller.exchange_currency(john, from, cny(11000)).unwrap();
+    teller.transfer(from, to, usd(1400)).unwrap();
+    teller.transfer(to, from, usd(1300)).unwrap();
+    drop(teller);
+    // 兑换也计入统计: 兑换得到的美元很快转出
+    let monitor = aml.lock().unwrap();
+    let exchanged = &monitor.get_alerts()[monitor.get_alerts().len() - 2];
+    assert_eq!(
+        (exchanged.get_kind(), exchanged.get_account_id()),
+        (AlertKind::RapidMovement, from)
+    );
+    let last = monitor.get_alerts().last().unwrap();
+    assert_eq!(
+        (last.get_kind(), last.get_account_id()),
+        (AlertKind::RapidMovement, to)
+    );
+    assert_eq!(
+        last.get_detail(),
+        "10010.00 CNY moved in and 9295.00 CNY moved out within 7 days"
+    );
+}
+
+#[test]
+fn reversal_restores_balances_and_keeps_original() {
+    let store = TestStore::new("reversal");
+    let last_id = |bank: &Bank| {
+        let system = bank.bank_system.lock().unwrap();
+        system
+            .get_transactions()
+            .last()
+            .unwrap()
+            .get_transaction()
+            .get_id()
+    };
+    let staff = |branch: &Arc<Mutex<BankBranch>>| {
+        let mut branch = branch.lock().unwrap();
+        on_duty(&mut branch, BankTeller::new(1));
+        on_duty(
+            &mut branch,
+            BankTeller::new(2).with_role(TellerRole::Supervisor),
+        );
+    };
+
+    let mut bank = store.open();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    let other = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    staff(&branch);
+    staff(&other);
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let jane = open_account(&branch, "Jane Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(john, cny(500)).unwrap();
+    let mistaken = {
+        branch.lock().unwrap().deposit(john, cny(50)).unwrap();
+        last_id(&bank)
+    };
+    bank.snapshot().unwrap();
+    drop((bank, branch, other));
+
+    // 快照包含交易明细, 重启后仍然可以冲正快照之前的交易
+    let mut bank = store.open();
+    let branch = Arc::clone(&bank.get_branches()[0]);
+    let other = Arc::clone(&bank.get_branches()[1]);
+    staff(&branch);
+    staff(&other);
+    branch
+        .lock()
+        .unwrap()
+        .reverse_transaction(mistaken, 2, "keyed twice".to_string())
+        .unwrap();
+    let withdrawal = {
+        branch.lock().unwrap().withdraw(john, cny(100)).unwrap();
+        last_id(&bank)
+    };
+    let transfer = {
+        branch
+            .lock()
+            .unwrap()
+            .transfer(john, jane, cny(200))
+            .unwrap();
+        last_id(&bank)
+    };
+
+    // 只有主管可以授权, 只能在原交易的分行办理
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .reverse_transaction(withdrawal, 1, "wrong amount".to_string()),
+        Err(BankError::PermissionDenied {
+            teller_id: 1,
+            operation: Operation::Reverse,
+        })
+    );
+    assert_eq!(
+        other
+            .lock()
+            .unwrap()
+            .reverse_transaction(withdrawal, 2, "wrong amount".to_string()),
+        Err(BankError::TransactionNotReversible(withdrawal))
+    );
+    let reversal = branch
+        .lock()
+        .unwrap()
+        .reverse_transaction(withdrawal, 2, "wrong amount".to_string())
+        .unwrap();
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .reverse_transaction(withdrawal, 2, "again".to_string()),
+        Err(BankError::TransactionAlreadyReversed(withdrawal))
+    );
+    branch
+        .lock()
+        .unwrap()
+        .reverse_transaction(transfer, 2, "wrong payee".to_string())
+        .unwrap();
+
+    {
+        let system = bank.bank_system.lock().unwrap();
+        assert_eq!(system.get_balance(john), Ok(cny(500)));
+        assert_eq!(system.get_balance(jane), Ok(cny(0)));
+        assert_eq!(system.get_branch_cash(1), cny(1500));
+        assert_eq!(system.get_reversal_of(withdrawal), Some(reversal));
+        // 原交易记录保持不变
+        assert_eq!(
+            system
+                .get_transaction(withdrawal)
+                .unwrap()
+                .get_transaction_description(),
+            format!("Teller 1 withdraw 100.00 CNY from account {}", john)
+        );
+        let statement = system
+            .account_statement(john, DateTime::UNIX_EPOCH, system.now())
+            .unwrap();
+        let amounts: Vec<Money> = statement.lines.iter().map(|l| l.amount).collect();
+        assert_eq!(
+            amounts,
+            vec![
+                cny(500),
+                cny(50),
+                cny(-50),
+                cny(-100),
+                cny(-200),
+                cny(100),
+                cny(200)
+            ]
+        );
+        assert_eq!(
+            statement.lines[5].description,
+            format!(
+                "Supervisor 2 reversed transaction #{} (Withdrawal of 100.00 CNY on account {}): wrong amount",
+                withdrawal, john
+            )
+        );
+    }
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .reverse_transaction(reversal, 2, String::new()),
+        Err(BankError::TransactionNotReversible(reversal))
+    );
+
+    // 冲正取款退还它引起的超次取款费, 并恢复当月的取款次数
+    let manager_id = head_office(&mut bank);
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .set_fee_schedule(
+            manager_id,
+            FeeSchedule {
+                free_withdrawals: 1,
+                withdrawal_fee: Some(cny(2)),
+                ..FeeSchedule::default()
+            },
+        )
+        .unwrap();
+    let savings = AccountKind::Savings {
+        annual_rate: Decimal::ZERO,
+        monthly_withdrawal_cap: 2,
+    };
+    let amy = open_account(&branch, "Amy Lee", savings).unwrap();
+    branch.lock().unwrap().deposit(amy, cny(100)).unwrap();
+    branch.lock().unwrap().withdraw(amy, cny(10)).unwrap();
+    let second = {
+        branch.lock().unwrap().withdraw(amy, cny(10)).unwrap();
+        let system = bank.bank_system.lock().unwrap();
+        let mut withdrawals = system
+            .get_transactions()
+            .iter()
+            .rev()
+            .filter(|t| t.get_kind() == TransactionKind::Withdrawal);
+        withdrawals.next().unwrap().get_transaction().get_id()
+    };
+    let capped = Err(BankError::WithdrawalLimitExceeded {
+        account_id: amy,
+        limit: 2,
+    });
+    assert_eq!(branch.lock().unwrap().withdraw(amy, cny(10)), capped);
+    branch
+        .lock()
+        .unwrap()
+        .reverse_transaction(second, 2, "customer changed mind".to_string())
+        .unwrap();
+    assert_eq!(
+        bank.bank_system.lock().unwrap().get_balance(amy),
+        Ok(cny(90))
+    );
+    branch.lock().unwrap().withdraw(amy, cny(10)).unwrap();
+    assert_eq!(branch.lock().unwrap().withdraw(amy, cny(10)), capped);
+    drop((bank, branch, other));
+
+    let bank = store.open();
+    {
+        let system = bank.bank_system.lock().unwrap();
+        assert_eq!(system.get_balance(amy), Ok(cny(78)));
+        assert_eq!(system.get_balance(john), Ok(cny(500)));
+        assert_eq!(
+            system.get_branch_cash(1),
+            cny(1500 + 100 - 10 - 10 + 10 - 10)
+        );
+        assert_eq!(system.get_reversal_of(withdrawal), Some(reversal));
+        assert!(system.get_reversal_of(mistaken).is_some());
+    }
+}
+
+#[test]
+fn term_deposits_and_funds_appear_on_statement() {
+    let store = TestStore::new("investments");
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap());
+    let mut bank = store.open_with(&clock);
+    let manager_id = head_office(&mut bank);
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(john, cny(20000)).unwrap();
+
+    let product = TermDepositProduct {
+        term_months: 12,
+        annual_rate: Decimal::new(3, 2),
+        early_withdrawal_penalty: Decimal::new(5, 3),
+    };
+    let yearly = {
+        let mut branch = branch.lock().unwrap();
+        assert_eq!(
+            branch.open_term_deposit(
+                john,
+                cny(5000),
+                TermDepositProduct {
+                    term_months: 0,
+                    ..product
+                }
+            ),
+            Err(BankError::InvalidDepositTerm(0))
+        );
+        branch.open_term_deposit(john, cny(10000), product).unwrap()
+    };
+    let short = branch
+        .lock()
+        .unwrap()
+        .open_term_deposit(john, cny(5000), product)
+        .unwrap();
+
+    let navs = store.file("navs.csv");
+    fs::write(&navs, "fund_code,date,nav\nF001,2026-01-01\n").unwrap();
+    assert_eq!(
+        bank.load_fund_navs(manager_id, &navs),
+        Err(BankError::InvalidNavRecord { line: 2 })
+    );
+    fs::write(
+        &navs,
+        "fund_code,date,nav\nF001,2026-01-01,1.0000\nF001,2026-03-01,1.2500\n",
+    )
+    .unwrap();
+    bank.load_fund_navs(manager_id, &navs).unwrap();
+    assert_eq!(
+        branch.lock().unwrap().buy_fund(john, "F002", cny(100)),
+        Err(BankError::FundNotFound("F002".to_string()))
+    );
+    assert_eq!(
+        branch.lock().unwrap().buy_fund(john, "F001", cny(1000)),
+        Ok(Decimal::from(1000))
+    );
+
+    // 59 天的利息 24.25 不够扣罚金 25, 提前支取只退还本金
+    clock.set(Utc.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap());
+    assert_eq!(
+        branch.lock().unwrap().withdraw_term_deposit(short),
+        Ok(cny(5000))
+    );
+    assert_eq!(
+        branch.lock().unwrap().withdraw_term_deposit(short),
+        Err(BankError::TermDepositNotActive(short))
+    );
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .redeem_fund(john, "F001", Decimal::from(400)),
+        Ok(cny(500))
+    );
+    assert_eq!(
+        branch
+            .lock()
+            .unwrap()
+            .redeem_fund(john, "F001", Decimal::from(700)),
+        Err(BankError::InsufficientUnits {
+            fund_code: "F001".to_string(),
+            held: Decimal::from(600),
+        })
+    );
+    assert_eq!(
+        branch.lock().unwrap().close_account(john),
+        Err(BankError::AccountHasInvestments(john))
+    );
+
+    let statement = bank
+        .bank_system
+        .lock()
+        .unwrap()
+        .account_statement(
+            john,
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 3, 1, 23, 59, 59).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(statement.closing_balance, cny(9500));
+    assert_eq!(
+        statement.positions,
+        vec![
+            InvestmentPosition::TermDeposit {
+                deposit_id: yearly,
+                principal: cny(10000),
+                annual_rate: Decimal::new(3, 2),
+                maturity_date: NaiveDate::from_ymd_opt(2027, 1, 1).unwrap(),
+                accrued_interest: Money::new(Decimal::new(4849, 2), Currency::CNY),
+            },
+            InvestmentPosition::Fund {
+                fund_code: "F001".to_string(),
+                units: Decimal::from(600),
+                nav: Decimal::new(125, 2),
+                nav_date: NaiveDate::from_ymd_opt(2026, 3, 1).unwrap(),
+                value: cny(750),
+            },
+        ]
+    );
+
+    // 到期批处理支付一年的利息 300
+    let due = NaiveDate::from_ymd_opt(2027, 1, 1).unwrap();
+    assert_eq!(bank.mature_term_deposits(due), Ok(cny(10300)));
+    drop((bank, branch));
+
+    let bank = store.open_with(&clock);
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_balance(john), Ok(cny(19800)));
+    assert_eq!(system.get_fund_units(john, "F001"), Decimal::from(600));
+    assert_eq!(
+        system.get_term_deposit(yearly).map(|d| d.get_status()),
+        Some(TermDepositStatus::Matured)
+    );
+    assert_eq!(
+        system.get_term_deposit(short).map(|d| d.get_status()),
+        Some(TermDepositStatus::WithdrawnEarly)
+    );
+    // 以前期间的对账单按当时的持仓列出, 不受之后的兑付和赎回影响
+    let january = system
+        .account_statement(
+            john,
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap(),
+        )
+        .unwrap();
+    let held: Vec<(Option<usize>, Money)> = january
+        .positions
+        .iter()
+        .map(|p| match p {
+            InvestmentPosition::TermDeposit {
+                deposit_id,
+                principal,
+                ..
+            } => (Some(*deposit_id), *principal),
+            InvestmentPosition::Fund { value, .. } => (None, *value),
+        })
+        .collect();
+    assert_eq!(
+        held,
+        vec![
+            (Some(yearly), cny(10000)),
+            (Some(short), cny(5000)),
+            (None, cny(1000))
+        ]
+    );
+    assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+}
+
+#[test]
+fn currency_exchange_books_fx_margin() {
+    let store = TestStore::new("fx");
+    let usd = |units| Money::from_major(units, Currency::USD);
+    let mut bank = store.open();
+    let manager_id = head_office(&mut bank);
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let jane = open_account(&branch, "Jane Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(john, cny(10000)).unwrap();
+    let owner = bank
+        .bank_system
+        .lock()
+        .unwrap()
+        .get_account(john)
+        .unwrap()
+        .get_owners()[0];
+    let savings_in = |currency| {
+        branch.lock().unwrap().open_account_in(
+            vec![owner],
+            AccountKind::Savings {
+                annual_rate: Decimal::ZERO,
+                monthly_withdrawal_cap: 5,
+            },
+            currency,
+        )
+    };
+    let dollars = savings_in(Currency::USD).unwrap();
+    let yen = savings_in(Currency::JPY).unwrap();
+    let euros = savings_in(Currency::EUR).unwrap();
+    assert_eq!(
+        branch.lock().unwrap().open_account_in(
+            vec![owner],
+            AccountKind::Checking {
+                overdraft_limit: cny(100)
+            },
+            Currency::USD
+        ),
+        Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::CNY).into())
+    );
+    assert_eq!(
+        branch.lock().unwrap().deposit(dollars, usd(100)),
+        Err(BankError::ForeignCashNotSupported(Currency::USD))
+    );
+
+    let rates = store.file("rates.csv");
+    fs::write(&rates, "currency,bid,ask\nUSD,7.30,7.20\n").unwrap();
+    assert_eq!(
+        bank.load_fx_rates(manager_id, &rates),
+        Err(BankError::InvalidFxRecord { line: 2 })
+    );
+    fs::write(
+        &rates,
+        "currency,bid,ask\nUSD,7.10,7.20\nJPY,0.0470,0.0490\n",
+    )
+    .unwrap();
+    bank.load_fx_rates(manager_id, &rates).unwrap();
+
+    // 720 元按卖出价 7.20 买入 100 美元, 与中间价 7.15 相差 5 元
+    let mut teller = branch.lock().unwrap();
+    assert_eq!(
+        teller.exchange_currency(john, dollars, cny(720)),
+        Ok(usd(100))
+    );
+    // 50 美元按买入价 7.10 兑换 355 元, 收益 2.5 元
+    assert_eq!(
+        teller.exchange_currency(dollars, john, usd(50)),
+        Ok(cny(355))
+    );
+    // 10 美元折合 71 元, 按日元卖出价兑换 1448 日元(舍去不足1日元的部分), 收益约 2 元
+    assert_eq!(
+        teller.exchange_currency(dollars, yen, usd(10)),
+        Ok(Money::from_major(1448, Currency::JPY))
+    );
+    assert_eq!(
+        teller.exchange_currency(john, jane, cny(10)),
+        Err(BankError::SameCurrencyExchange(Currency::CNY))
+    );
+    assert_eq!(
+        teller.exchange_currency(john, euros, cny(10)),
+        Err(BankError::FxRateNotAvailable(Currency::EUR))
+    );
+    assert_eq!(
+        teller.close_account(dollars),
+        Err(BankError::ForeignCashNotSupported(Currency::USD))
+    );
+    // 贷款和信用卡都在柜台用现金归还, 外币现金同样不收
+    let rate = Decimal::new(5, 2);
+    assert_eq!(
+        teller.originate_loan(dollars, usd(1000), rate, 12),
+        Err(BankError::ForeignCashNotSupported(Currency::USD))
+    );
+    assert_eq!(
+        teller.originate_loan(dollars, cny(1000), rate, 12),
+        Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::CNY).into())
+    );
+    let card = teller
+        .issue_credit_card(dollars, usd(500), rate, usd(10))
+        .unwrap();
+    assert_eq!(
+        teller.pay_credit_card(card, usd(10)),
+        Err(BankError::ForeignCashNotSupported(Currency::USD))
+    );
+    let loan = teller.originate_loan(john, cny(1000), rate, 12).unwrap();
+    assert_eq!(
+        teller.repay_loan(loan, usd(10)),
+        Err(BankError::ForeignCashNotSupported(Currency::USD))
+    );
+    drop(teller);
+
+    // 牌价极大时折算溢出, 返回错误而不是 panic
+    let huge = "79000000000000000000000000000";
+    fs::write(&rates, format!("currency,bid,ask\nEUR,{},{}\n", huge, huge)).unwrap();
+    bank.load_fx_rates(manager_id, &rates).unwrap();
+    {
+        let system = bank.bank_system.lock().unwrap();
+        let euro = Money::from_major(1000, Currency::EUR);
+        let overflow = BankError::Money(MoneyError::Overflow);
+        assert_eq!(
+            system.convert_at_mid(euro, Currency::CNY),
+            Err(overflow.clone())
+        );
+        assert_eq!(
+            system.quote_exchange(euro, Currency::USD).map(|_| ()),
+            Err(overflow)
+        );
+    }
+    drop((bank, branch));
+
+    let bank = store.open();
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_balance(john), Ok(cny(10635)));
+    assert_eq!(system.get_balance(dollars), Ok(usd(40)));
+    assert_eq!(
+        system.get_balance(yen),
+        Ok(Money::from_major(1448, Currency::JPY))
+    );
+    assert_eq!(
+        system
+            .get_ledger()
+            .balance(LedgerAccount::FxIncome, Currency::CNY),
+        Money::from_minor(950, Currency::CNY)
+    );
+    assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+}
+
+#[test]
+fn fees_charged_at_transaction_time_and_monthly() {
+    let store = TestStore::new("fees");
+    let clock = ManualClock::new(Utc.with_ymd_and_hms(2026, 1, 5, 9, 0, 0).unwrap());
+    let mut bank = store.open_with(&clock);
+    let manager_id = head_office(&mut bank);
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    let other = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    on_duty(&mut other.lock().unwrap(), BankTeller::new(2));
+    let john = open_account(
+        &branch,
+        "John Doe",
+        AccountKind::Checking {
+            overdraft_limit: cny(500),
+        },
+    )
+    .unwrap();
+
+    let path = store.file("fees.json");
+    fs::write(
+        &path,
+        r#"{"overdraft_fee": {"amount": "30", "currency": "USD"}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        bank.load_fee_schedule(manager_id, &path),
+        Err(MoneyError::CurrencyMismatch(Currency::CNY, Currency::USD).into())
+    );
+    fs::write(
+        &path,
+        r#"{
+            "monthly_maintenance": {"amount": "10", "currency": "CNY"},
+            "free_withdrawals": 2,
+            "withdrawal_fee": {"amount": "2", "currency": "CNY"},
+            "cross_branch_fee": {"amount": "5", "currency": "CNY"},
+            "overdraft_fee": {"amount": "30", "currency": "CNY"}
+        }"#,
+    )
+    .unwrap();
+    bank.load_fee_schedule(manager_id, &path).unwrap();
+
+    let balance = |bank: &Bank| bank.bank_system.lock().unwrap().get_balance(john).unwrap();
+    branch.lock().unwrap().deposit(john, cny(300)).unwrap();
+    branch.lock().unwrap().withdraw(john, cny(50)).unwrap();
+    branch.lock().unwrap().withdraw(john, cny(50)).unwrap();
+    assert_eq!(balance(&bank), cny(200));
+    // 第3笔取款超过免费次数
+    branch.lock().unwrap().withdraw(john, cny(50)).unwrap();
+    assert_eq!(balance(&bank), cny(148));
+    // 在开户分行以外存款
+    other.lock().unwrap().deposit(john, cny(100)).unwrap();
+    assert_eq!(balance(&bank), cny(243));
+    // 超次取款并透支, 两笔费用都引用这笔取款
+    branch.lock().unwrap().withdraw(john, cny(300)).unwrap();
+    assert_eq!(balance(&bank), cny(-89));
+    {
+        let system = bank.bank_system.lock().unwrap();
+        let transactions = system.get_transactions();
+        let withdrawal_id = transactions[transactions.len() - 3]
+            .get_transaction()
+            .get_id();
+        let fees: Vec<_> = transactions[transactions.len() - 2..]
+            .iter()
+            .map(|ts| (ts.get_kind(), ts.get_transaction_description()))
+            .collect();
+        assert_eq!(
+            fees,
+            vec![
+                (
+                    TransactionKind::WithdrawalFee,
+                    format!(
+                        "Teller 1 charged excess withdrawal fee of 2.00 CNY to account {} for transaction #{}",
+                        john, withdrawal_id
+                    )
+                ),
+                (
+                    TransactionKind::OverdraftFee,
+                    format!(
+                        "Teller 1 charged overdraft fee of 30.00 CNY to account {} for transaction #{}",
+                        john, withdrawal_id
+                    )
+                ),
+            ]
+        );
+    }
+
+    // 账户管理费每月只收一次
+    let date = |m, d| NaiveDate::from_ymd_opt(2026, m, d).unwrap();
+    assert_eq!(bank.charge_monthly_fees(date(1, 31)), Ok(cny(10)));
+    assert_eq!(bank.charge_monthly_fees(date(1, 31)), Ok(cny(0)));
+    clock.set(Utc.with_ymd_and_hms(2026, 2, 28, 9, 0, 0).unwrap());
+    assert_eq!(bank.charge_monthly_fees(date(2, 28)), Ok(cny(10)));
+    drop((bank, branch, other));
+
+    let bank = store.open_with(&clock);
+    let system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_balance(john), Ok(cny(-109)));
+    assert_eq!(
+        system
+            .get_ledger()
+            .balance(LedgerAccount::FeeIncome, Currency::CNY),
+        cny(59)
+    );
+    assert_eq!(system.get_fee_schedule().free_withdrawals, 2);
+    assert!(system.get_ledger().trial_balance().unwrap().is_balanced());
+}
+
+#[test]
+fn tellers_sign_in_and_denials_are_audited() {
+    let store = TestStore::new("authorization");
+    let mut bank = store.open();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    let other = add_branch(&mut bank, "456 Elm St".to_string(), cny(1000));
+    {
+        let mut branch = branch.lock().unwrap();
+        on_duty(&mut branch, BankTeller::new(1));
+        on_duty(
+            &mut branch,
+            BankTeller::new(2).with_role(TellerRole::Supervisor),
+        );
+        on_duty(
+            &mut branch,
+            BankTeller::new(3).with_role(TellerRole::Auditor),
+        );
+        branch.add_teller(BankTeller::new(4).with_credential("secret"));
+        branch.add_teller(BankTeller::new(5));
+        assert_eq!(
+            branch.sign_in(4, "guess"),
+            Err(BankError::InvalidCredentials(4))
+        );
+        assert_eq!(branch.sign_in(5, ""), Err(BankError::InvalidCredentials(5)));
+    }
+    // 没有柜员签到的分行不能办理业务
+    other
+        .lock()
+        .unwrap()
+        .add_teller(BankTeller::new(6).with_credential("secret"));
+    assert_eq!(
+        open_account(&other, "Jane Doe", checking()),
+        Err(BankError::NoTellerAvailable)
+    );
+
+    // 审计员不接待客户
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    for _ in 0..4 {
+        branch.lock().unwrap().deposit(john, cny(10)).unwrap();
+    }
+    assert!(bank
+        .bank_system
+        .lock()
+        .unwrap()
+        .get_transactions()
+        .iter()
+        .all(|ts| ts.get_transaction().get_teller_id() != 3));
+
+    let denied = |teller_id, operation| BankError::PermissionDenied {
+        teller_id,
+        operation,
+    };
+    {
+        let mut system = bank.bank_system.lock().unwrap();
+        // 没有签到的柜员、在别的分行签到的柜员和审计员都不能办理存款
+        assert_eq!(
+            system.deposit(john, 1, 4, cny(10), None),
+            Err(denied(4, Operation::Transact))
+        );
+        assert_eq!(
+            system.deposit(john, 2, 1, cny(10), None),
+            Err(denied(1, Operation::Transact))
+        );
+        assert_eq!(
+            system.deposit(john, 1, 3, cny(10), None),
+            Err(denied(3, Operation::Transact))
+        );
+        // 柜员不能冻结账户; 分行主管不能修改总部的策略
+        assert_eq!(
+            system.freeze_account(john, 1, 1, String::new()),
+            Err(denied(1, Operation::FreezeAccounts))
+        );
+        assert_eq!(
+            system.set_policy(2, PolicyEngine::new()),
+            Err(denied(2, Operation::SetPolicy))
+        );
+    }
+    assert_eq!(
+        branch.lock().unwrap().get_audit_events(1),
+        Err(denied(1, Operation::Audit))
+    );
+    let events = branch.lock().unwrap().get_audit_events(3).unwrap();
+    let summary: Vec<_> = events
+        .iter()
+        .map(|e| (e.teller_id, e.branch_id, e.kind))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (4, 1, AuditEventKind::LoginFailed),
+            (5, 1, AuditEventKind::LoginFailed),
+            (4, 1, AuditEventKind::PermissionDenied(Operation::Transact)),
+            (1, 2, AuditEventKind::PermissionDenied(Operation::Transact)),
+            (3, 1, AuditEventKind::PermissionDenied(Operation::Transact)),
+            (
+                1,
+                1,
+                AuditEventKind::PermissionDenied(Operation::FreezeAccounts)
+            ),
+            (
+                2,
+                HEADQUARTERS_ID,
+                AuditEventKind::PermissionDenied(Operation::SetPolicy)
+            ),
+            (1, 1, AuditEventKind::PermissionDenied(Operation::Audit)),
+        ]
+    );
+    assert!(events[0]
+        .to_string()
+        .ends_with("Teller 4 failed to sign in at branch 1"));
+
+    // 签到期间总部员工和分行柜员不能使用同一个ID, 否则分行柜员会被当成总部员工
+    let manager_id = head_office(&mut bank);
+    other
+        .lock()
+        .unwrap()
+        .add_teller(BankTeller::new(manager_id).with_credential("secret"));
+    assert_eq!(
+        other.lock().unwrap().sign_in(manager_id, "secret"),
+        Err(BankError::StaffIdInUse(manager_id))
+    );
+    bank.add_staff(
+        BankTeller::new(1)
+            .with_role(TellerRole::BranchManager)
+            .with_credential("secret"),
+    );
+    assert_eq!(bank.sign_in(1, "secret"), Err(BankError::StaffIdInUse(1)));
+    // 分行发起的现金业务只认在本分行签到的行长, 总部员工通过总部办理
+    {
+        let mut branch = branch.lock().unwrap();
+        assert_eq!(
+            branch.settle(manager_id, cny(1040), Decimal::ZERO),
+            Err(denied(manager_id, Operation::ManageCash))
+        );
+        assert_eq!(
+            branch.collect_cash(manager_id, Decimal::ONE),
+            Err(denied(manager_id, Operation::ManageCash))
+        );
+        assert_eq!(
+            branch.request_replenishment(manager_id, cny(100)),
+            Err(denied(manager_id, Operation::ManageCash))
+        );
+    }
+    bank.collect_cash(manager_id, Decimal::ZERO).unwrap();
+
+    // 签退后不再分配业务
+    {
+        let mut branch = branch.lock().unwrap();
+        branch.sign_out(1).unwrap();
+        branch.sign_out(2).unwrap();
+        assert_eq!(
+            branch.deposit(john, cny(10)),
+            Err(BankError::NoTellerAvailable)
+        );
+    }
+    drop((bank, branch, other));
+
+    // 审计记录写入日志, 重启后柜员需要重新签到
+    let bank = store.open();
+    let mut system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.get_audit_events().len(), 11);
+    assert_eq!(
+        system.deposit(john, 1, 1, cny(10), None),
+        Err(denied(1, Operation::Transact))
+    );
+    assert_eq!(system.get_balance(john), Ok(cny(40)));
+}
+
+#[test]
+fn bank_administration_requires_authorized_roles() {
+    let mut bank = Bank::new(cny(10000)).unwrap();
+    let manager_id = head_office(&mut bank);
+    for (staff_id, role) in [(101, TellerRole::Supervisor), (102, TellerRole::Teller)] {
+        bank.add_staff(
+            BankTeller::new(staff_id)
+                .with_role(role)
+                .with_credential("secret"),
+        );
+        bank.sign_in(staff_id, "secret").unwrap();
+    }
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    {
+        let mut branch = branch.lock().unwrap();
+        on_duty(&mut branch, BankTeller::new(1));
+        on_duty(
+            &mut branch,
+            BankTeller::new(2).with_role(TellerRole::BranchManager),
+        );
+        on_duty(
+            &mut branch,
+            BankTeller::new(3).with_role(TellerRole::Auditor),
+        );
+    }
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let card_id = branch
+        .lock()
+        .unwrap()
+        .issue_credit_card(john, cny(1000), Decimal::new(18, 2), cny(50))
+        .unwrap();
+    let denied = |teller_id, operation| BankError::PermissionDenied {
+        teller_id,
+        operation,
+    };
+    let missing = Path::new("missing.csv");
+    let today = Utc::now().date_naive();
+    let mut system = bank.bank_system.lock().unwrap();
+
+    // 开设分行、注资和定时补款只能由总部的经理办理, 分行经理和总部主管都不行
+    assert_eq!(
+        system.open_branch(2, "456 Elm St".to_string(), cny(1000)),
+        Err(denied(2, Operation::ManageVault))
+    );
+    assert_eq!(
+        system.open_branch(101, "456 Elm St".to_string(), cny(1000)),
+        Err(denied(101, Operation::ManageVault))
+    );
+    assert_eq!(
+        system.inject_capital(101, LedgerAccount::Vault, cny(100)),
+        Err(denied(101, Operation::ManageVault))
+    );
+    assert_eq!(
+        system.schedule_replenishment(101, NaiveTime::from_hms_opt(18, 0, 0).unwrap()),
+        Err(denied(101, Operation::ManageVault))
+    );
+    system
+        .inject_capital(manager_id, LedgerAccount::Vault, cny(100))
+        .unwrap();
+    assert_eq!(system.get_vault_cash(), cny(10100));
+
+    // 收费标准、交易策略和行情数据由总部经理设置, 没有权限时不读取文件
+    let fees = FeeSchedule {
+        withdrawal_fee: Some(cny(2)),
+        ..FeeSchedule::default()
+    };
+    assert_eq!(
+        system.set_fee_schedule(101, fees.clone()),
+        Err(denied(101, Operation::SetPolicy))
+    );
+    assert_eq!(
+        system.load_fee_schedule(101, missing),
+        Err(denied(101, Operation::SetPolicy))
+    );
+    assert_eq!(
+        system.set_policy(101, PolicyEngine::new()),
+        Err(denied(101, Operation::SetPolicy))
+    );
+    assert_eq!(
+        system.load_policy(101, missing),
+        Err(denied(101, Operation::SetPolicy))
+    );
+    assert_eq!(
+        system.load_fx_rates(101, missing),
+        Err(denied(101, Operation::LoadMarketData))
+    );
+    assert_eq!(
+        system.load_fund_navs(101, missing),
+        Err(denied(101, Operation::LoadMarketData))
+    );
+    system.set_fee_schedule(manager_id, fees).unwrap();
+
+    // 睡眠户批处理需要冻结账户的权限: 总部柜员不行, 总部主管可以
+    assert_eq!(
+        system.mark_dormant_accounts(102, today, 365),
+        Err(denied(102, Operation::FreezeAccounts))
+    );
+    assert_eq!(
+        system.mark_dormant_accounts(101, today, 365),
+        Ok(Vec::new())
+    );
+
+    // 登记客户、增加持有人和出信用卡账单由柜台办理, 审计员不行
+    let document = IdentityDocument::new(DocumentKind::IdCard, "Jane Doe".to_string());
+    let register = |system: &mut BankSystem, teller_id| {
+        system.register_customer(
+            1,
+            teller_id,
+            "Jane Doe".to_string(),
+            NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            "1 Test Rd".to_string(),
+            document.clone(),
+        )
+    };
+    assert_eq!(
+        register(&mut system, 3),
+        Err(denied(3, Operation::ServeCustomer))
+    );
+    let jane = register(&mut system, 1).unwrap();
+    assert_eq!(
+        system.add_account_owner(john, 1, 3, jane),
+        Err(denied(3, Operation::ServeCustomer))
+    );
+    system.add_account_owner(john, 1, 1, jane).unwrap();
+    assert_eq!(
+        system.generate_card_statement(3, card_id),
+        Err(denied(3, Operation::Transact))
+    );
+    system.generate_card_statement(1, card_id).unwrap();
+}
+
+#[test]
+fn audit_log_detects_tampering_and_exports_chain() {
+    let store = TestStore::new("audit_log");
+    let mut bank = store.open();
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    {
+        let mut branch = branch.lock().unwrap();
+        on_duty(&mut branch, BankTeller::new(1));
+        on_duty(
+            &mut branch,
+            BankTeller::new(2).with_role(TellerRole::Auditor),
+        );
+    }
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    branch.lock().unwrap().deposit(john, cny(100)).unwrap();
+    branch.lock().unwrap().withdraw(john, cny(30)).unwrap();
+    assert_eq!(
+        branch.lock().unwrap().sign_in(1, "guess"),
+        Err(BankError::InvalidCredentials(1))
+    );
+
+    let entries = {
+        let system = bank.bank_system.lock().unwrap();
+        let log = system.get_audit_log();
+        assert_eq!(system.verify_audit_log().unwrap(), log.head());
+        assert!(matches!(
+            log.get_entries().last().unwrap().record,
+            AuditRecord::Event(AuditEvent {
+                kind: AuditEventKind::LoginFailed,
+                ..
+            })
+        ));
+        log.get_entries().to_vec()
+    };
+    assert!(entries.len() >= 4);
+    let head = entries.last().unwrap().hash.clone();
+
+    // 改动、调换、删除中间的记录都会被发现
+    let mut edited = entries.clone();
+    if let AuditRecord::Transaction { description, .. } = &mut edited[1].record {
+        description.push_str(" (edited)");
+    }
+    assert_eq!(
+        AuditLog::verify_entries(&edited),
+        Err(BankError::AuditChainBroken { seq: 2 })
+    );
+    let mut reordered = entries.clone();
+    reordered.swap(1, 2);
+    assert_eq!(
+        AuditLog::verify_entries(&reordered),
+        Err(BankError::AuditChainBroken { seq: 2 })
+    );
+    let mut deleted = entries.clone();
+    deleted.remove(0);
+    assert_eq!(
+        AuditLog::verify_entries(&deleted),
+        Err(BankError::AuditChainBroken { seq: 1 })
+    );
+    // 删除末尾的记录链仍然完整, 但链头变了
+    let truncated = AuditLog::verify_entries(&entries[..entries.len() - 1]).unwrap();
+    assert_ne!(truncated, head);
+
+    // 只有审计员可以导出
+    let export = store.file("audit.jsonl");
+    assert_eq!(
+        branch.lock().unwrap().export_audit_log(1, &export),
+        Err(BankError::PermissionDenied {
+            teller_id: 1,
+            operation: Operation::Audit,
+        })
+    );
+    let exported_head = branch.lock().unwrap().export_audit_log(2, &export).unwrap();
+    assert_eq!(AuditLog::verify_export(&export).unwrap(), exported_head);
+
+    // 导出的文件被篡改也能校验出来
+    let data = fs::read_to_string(&export).unwrap();
+    let lines: Vec<&str> = data.lines().collect();
+    let tampered = export.with_extension("tampered");
+    let mut swapped = lines.clone();
+    swapped.swap(0, 1);
+    fs::write(&tampered, swapped.join("\n")).unwrap();
+    assert_eq!(
+        AuditLog::verify_export(&tampered),
+        Err(BankError::AuditChainBroken { seq: 1 })
+    );
+    fs::write(&tampered, lines[1..].join("\n")).unwrap();
+    assert_eq!(
+        AuditLog::verify_export(&tampered),
+        Err(BankError::AuditChainBroken { seq: 1 })
+    );
+    let mut edited = lines.clone();
+    let line = edited[2].replacen("30.00 CNY", "3.00 CNY", 1);
+    assert_ne!(line, edited[2]);
+    edited[2] = &line;
+    fs::write(&tampered, edited.join("\n")).unwrap();
+    assert_eq!(
+        AuditLog::verify_export(&tampered),
+        Err(BankError::AuditChainBroken { seq: 3 })
+    );
+
+    // 重新打开后审计日志保持不变, 并继续在原链上追加
+    drop(branch);
+    drop(bank);
+    let bank = store.open();
+    let mut system = bank.bank_system.lock().unwrap();
+    assert_eq!(system.verify_audit_log().unwrap(), exported_head);
+    let len = system.get_audit_log().get_entries().len();
+    system
+        .record_audit_event(1, 0, AuditEventKind::LoginFailed)
+        .unwrap();
+    let last = system.get_audit_log().get_entries().last().unwrap();
+    assert_eq!(
+        (last.seq, last.prev_hash.as_str()),
+        (len as u64 + 1, exported_head.as_str())
+    );
+    assert_ne!(system.verify_audit_log().unwrap(), exported_head);
+}
+
+#[test]
+fn failed_batch_leaves_no_postings_or_audit_records() {
+    let store = TestStore::new("batch");
+    let today = Utc::now().date_naive();
+
+    let mut bank = store.open();
+    let manager_id = head_office(&mut bank);
+    let branch = add_branch(&mut bank, "123 Main St".to_string(), cny(1000));
+    on_duty(&mut branch.lock().unwrap(), BankTeller::new(1));
+    let john = open_account(&branch, "John Doe", checking()).unwrap();
+    let jane = open_account(&branch, "Jane Doe", checking()).unwrap();
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .set_fee_schedule(
+            manager_id,
+            FeeSchedule {
+                monthly_maintenance: Some(cny(5)),
+                ..FeeSchedule::default()
+            },
+        )
+        .unwrap();
+    let state = |bank: &Bank| {
+        let system = bank.bank_system.lock().unwrap();
+        (
+            system.get_balance(john),
+            system.get_balance(jane),
+            system.get_transactions().len(),
+            system.get_audit_log().get_entries().len(),
+            system.get_audit_log().head().to_string(),
+        )
+    };
+    let before = state(&bank);
+
+    // 两个账户的管理费都已经过账, 最后写日志失败: 费用和审计记录都不留下
+    bank.bank_system
+        .lock()
+        .unwrap()
+        .journal
+        .as_mut()
+        .unwrap()
+        .file = OpenOptions::new().write(true).open("/dev/full").unwrap();
+    assert!(matches!(
+        bank.charge_monthly_fees(today),
+        Err(BankError::Storage(_))
+    ));
+    assert_eq!(state(&bank), before);
+    drop((bank, branch));
+
+    // 重启后重新收费, 新的审计记录接在失败前的链头后面
+    let mut bank = store.open();
+    assert_eq!(state(&bank), before);
+    assert_eq!(bank.charge_monthly_fees(today), Ok(cny(10)));
+    let system = bank.bank_system.lock().unwrap();
+    let entries = system.get_audit_log().get_entries();
+    assert_eq!(entries.len(), before.3 + 2);
+    assert_eq!(entries[before.3].prev_hash, before.4);
+    assert!(system.verify_audit_log().is_ok());
+    assert_eq!(system.get_balance(john), Ok(cny(-5)));
+}
@@ -0,0 +1,1484 @@
+// 交易记录: 各类交易、交易查询和对账单
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// Transaction 交易的公共信息
+/// id、时间、分行和交易后余额由 BankSystem 在记录交易时填写
+#[derive(Serialize, Deserialize)]
+pub struct Transaction {
+    // 交易ID, 单调递增
+    pub(crate) id: u64,
+    // 交易时间
+    pub(crate) timestamp: DateTime<Utc>,
+    // 账户ID
+    pub(crate) account_id: usize,
+    // 柜员ID(银行开户是有柜员带用户开户)
+    pub(crate) teller_id: usize,
+    // 大额现金交易的复核柜员
+    pub(crate) approved_by: Option<usize>,
+    // 办理交易的分行(系统批处理产生的交易没有分行)
+    pub(crate) branch_id: Option<usize>,
+    pub(crate) branch_address: Option<String>,
+    // 交易完成后客户账户的余额
+    pub(crate) balance_after: Option<Money>,
+}
+
+impl Transaction {
+    fn new(account_id: usize, teller_id: usize) -> Self {
+        Self {
+            id: 0,
+            timestamp: DateTime::UNIX_EPOCH,
+            account_id,
+            teller_id,
+            approved_by: None,
+            branch_id: None,
+            branch_address: None,
+            balance_after: None,
+        }
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn get_timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    pub fn get_account_id(&self) -> usize {
+        self.account_id
+    }
+
+    pub fn get_teller_id(&self) -> usize {
+        self.teller_id
+    }
+
+    pub fn get_approved_by(&self) -> Option<usize> {
+        self.approved_by
+    }
+
+    pub fn get_branch_id(&self) -> Option<usize> {
+        self.branch_id
+    }
+
+    pub fn get_branch_address(&self) -> Option<&str> {
+        self.branch_address.as_deref()
+    }
+
+    pub fn get_balance_after(&self) -> Option<Money> {
+        self.balance_after
+    }
+}
+
+/// TransactionKind 交易类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TransactionKind {
+    OpenAccount,
+    Deposit,
+    Withdrawal,
+    Transfer,
+    InterestAccrual,
+    LoanOrigination,
+    LoanRepayment,
+    IssueCreditCard,
+    CreditCardCharge,
+    CreditCardPayment,
+    CashTransfer,
+    AccountStatusChange,
+    Reversal,
+    TermDepositOpening,
+    TermDepositPayout,
+    FundPurchase,
+    FundRedemption,
+    CurrencyExchange,
+    MaintenanceFee,
+    WithdrawalFee,
+    CrossBranchFee,
+    OverdraftFee,
+}
+
+pub trait TransactionDescription: Send + Sync {
+    fn get_transaction(&self) -> &Transaction;
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction;
+
+    fn get_kind(&self) -> TransactionKind;
+
+    fn get_transaction_description(&self) -> String;
+
+    // 写快照时按具体的交易类型序列化
+    fn stored(&self) -> StoredTransactionRef<'_>;
+
+    // 交易是否涉及某个客户账户
+    fn involves_account(&self, account_id: usize) -> bool {
+        self.get_transaction().account_id == account_id
+    }
+
+    // 交易的另一个客户账户(例如转账的转入账户), 没有时返回 None
+    fn get_counterparty_account_id(&self) -> Option<usize> {
+        None
+    }
+
+    // 交易对某个客户存款账户余额的影响(正数为增加), 不影响余额时返回 None
+    fn get_account_effect(&self, _account_id: usize) -> Option<Money> {
+        None
+    }
+
+    // 交易对办理分行库存现金的影响(正数为收入现金), 不收付现金时返回 None
+    fn get_cash_effect(&self) -> Option<Money> {
+        None
+    }
+
+    // 冲正交易冲掉的原交易ID, 其他交易返回 None
+    fn get_reversed_id(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// StoredTransactionRef 快照中的一笔交易明细, 按具体的交易类型区分
+#[derive(Serialize)]
+pub enum StoredTransactionRef<'a> {
+    Deposit(&'a Deposit),
+    Withdrawal(&'a Withdrawal),
+    Transfer(&'a Transfer),
+    InterestAccrual(&'a InterestAccrual),
+    LoanOrigination(&'a LoanOrigination),
+    LoanRepayment(&'a LoanRepayment),
+    IssueCreditCard(&'a IssueCreditCard),
+    CreditCardCharge(&'a CreditCardCharge),
+    CreditCardPayment(&'a CreditCardPayment),
+    CashTransfer(&'a CashTransfer),
+    OpenAccount(&'a OpenAccount),
+    AccountStatusChange(&'a AccountStatusChange),
+    Reversal(&'a Reversal),
+    OpenTermDeposit(&'a OpenTermDeposit),
+    TermDepositPayout(&'a TermDepositPayout),
+    FundTrade(&'a FundTrade),
+    FeeCharge(&'a FeeCharge),
+    CurrencyExchange(&'a CurrencyExchange),
+}
+
+// 从快照读出的交易明细, 和 StoredTransactionRef 的格式相同
+#[derive(Deserialize)]
+pub(crate) enum StoredTransaction {
+    Deposit(Deposit),
+    Withdrawal(Withdrawal),
+    Transfer(Transfer),
+    InterestAccrual(InterestAccrual),
+    LoanOrigination(LoanOrigination),
+    LoanRepayment(LoanRepayment),
+    IssueCreditCard(IssueCreditCard),
+    CreditCardCharge(CreditCardCharge),
+    CreditCardPayment(CreditCardPayment),
+    CashTransfer(CashTransfer),
+    OpenAccount(OpenAccount),
+    AccountStatusChange(AccountStatusChange),
+    Reversal(Reversal),
+    OpenTermDeposit(OpenTermDeposit),
+    TermDepositPayout(TermDepositPayout),
+    FundTrade(FundTrade),
+    FeeCharge(FeeCharge),
+    CurrencyExchange(CurrencyExchange),
+}
+
+impl StoredTransaction {
+    pub(crate) fn into_boxed(self) -> Box<dyn TransactionDescription> {
+        match self {
+            Self::Deposit(ts) => Box::new(ts),
+            Self::Withdrawal(ts) => Box::new(ts),
+            Self::Transfer(ts) => Box::new(ts),
+            Self::InterestAccrual(ts) => Box::new(ts),
+            Self::LoanOrigination(ts) => Box::new(ts),
+            Self::LoanRepayment(ts) => Box::new(ts),
+            Self::IssueCreditCard(ts) => Box::new(ts),
+            Self::CreditCardCharge(ts) => Box::new(ts),
+            Self::CreditCardPayment(ts) => Box::new(ts),
+            Self::CashTransfer(ts) => Box::new(ts),
+            Self::OpenAccount(ts) => Box::new(ts),
+            Self::AccountStatusChange(ts) => Box::new(ts),
+            Self::Reversal(ts) => Box::new(ts),
+            Self::OpenTermDeposit(ts) => Box::new(ts),
+            Self::TermDepositPayout(ts) => Box::new(ts),
+            Self::FundTrade(ts) => Box::new(ts),
+            Self::FeeCharge(ts) => Box::new(ts),
+            Self::CurrencyExchange(ts) => Box::new(ts),
+        }
+    }
+}
+
+/// Deposit 存款
+#[derive(Serialize, Deserialize)]
+pub struct Deposit {
+    pub(crate) transaction: Transaction,
+    amount: Money,
+}
+
+impl Deposit {
+    pub fn new(account_id: usize, teller_id: usize, amount: Money) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            amount,
+        }
+    }
+}
+
+impl TransactionDescription for Deposit {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::Deposit
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::Deposit(self)
+    }
+
+    fn get_cash_effect(&self) -> Option<Money> {
+        Some(self.amount)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        (account_id == self.transaction.account_id).then_some(self.amount)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} deposited {} to account {}",
+            self.transaction.teller_id, self.amount, self.transaction.account_id
+        )
+    }
+}
+
+/// Withdrawal 取款
+#[derive(Serialize, Deserialize)]
+pub struct Withdrawal {
+    pub(crate) transaction: Transaction,
+    amount: Money,
+}
+
+impl Withdrawal {
+    pub fn new(account_id: usize, teller_id: usize, amount: Money) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            amount,
+        }
+    }
+}
+
+impl TransactionDescription for Withdrawal {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::Withdrawal
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::Withdrawal(self)
+    }
+
+    fn get_cash_effect(&self) -> Option<Money> {
+        Some(-self.amount)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        (account_id == self.transaction.account_id).then_some(-self.amount)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} withdraw {} from account {}",
+            self.transaction.teller_id, self.amount, self.transaction.account_id
+        )
+    }
+}
+
+/// Transfer 转账(两个客户账户之间, 账户可以在不同分行开户)
+#[derive(Serialize, Deserialize)]
+pub struct Transfer {
+    // transaction.account_id 为转出账户
+    transaction: Transaction,
+    // 转入账户
+    to_account_id: usize,
+    amount: Money,
+    // 转出和转入账户是否在不同分行开户
+    cross_branch: bool,
+}
+
+impl Transfer {
+    pub fn new(
+        from_account_id: usize,
+        to_account_id: usize,
+        teller_id: usize,
+        amount: Money,
+        cross_branch: bool,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(from_account_id, teller_id),
+            to_account_id,
+            amount,
+            cross_branch,
+        }
+    }
+}
+
+impl TransactionDescription for Transfer {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::Transfer
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::Transfer(self)
+    }
+
+    fn involves_account(&self, account_id: usize) -> bool {
+        account_id == self.transaction.account_id || account_id == self.to_account_id
+    }
+
+    fn get_counterparty_account_id(&self) -> Option<usize> {
+        Some(self.to_account_id)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        if account_id == self.transaction.account_id {
+            Some(-self.amount)
+        } else if account_id == self.to_account_id {
+            Some(self.amount)
+        } else {
+            None
+        }
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} transferred {} from account {} to account {}{}",
+            self.transaction.teller_id,
+            self.amount,
+            self.transaction.account_id,
+            self.to_account_id,
+            if self.cross_branch {
+                " (cross-branch)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// InterestAccrual 储蓄账户计息
+#[derive(Serialize, Deserialize)]
+pub struct InterestAccrual {
+    transaction: Transaction,
+    amount: Money,
+    // 计息截止日期
+    as_of: NaiveDate,
+}
+
+impl InterestAccrual {
+    pub fn new(account_id: usize, amount: Money, as_of: NaiveDate) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, SYSTEM_TELLER_ID),
+            amount,
+            as_of,
+        }
+    }
+}
+
+impl TransactionDescription for InterestAccrual {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::InterestAccrual
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::InterestAccrual(self)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        (account_id == self.transaction.account_id).then_some(self.amount)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Interest of {} credited to account {} as of {}",
+            self.amount, self.transaction.account_id, self.as_of
+        )
+    }
+}
+
+/// LoanOrigination 发放贷款
+#[derive(Serialize, Deserialize)]
+pub struct LoanOrigination {
+    transaction: Transaction,
+    loan_id: usize,
+    principal: Money,
+    annual_rate: Decimal,
+    term_months: u32,
+}
+
+impl LoanOrigination {
+    pub fn new(
+        account_id: usize,
+        teller_id: usize,
+        loan_id: usize,
+        principal: Money,
+        annual_rate: Decimal,
+        term_months: u32,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            loan_id,
+            principal,
+            annual_rate,
+            term_months,
+        }
+    }
+}
+
+impl TransactionDescription for LoanOrigination {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::LoanOrigination
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::LoanOrigination(self)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        (account_id == self.transaction.account_id).then_some(self.principal)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} originated loan {} of {} at {}% for {} months to account {}",
+            self.transaction.teller_id,
+            self.loan_id,
+            self.principal,
+            self.annual_rate * Decimal::from(100),
+            self.term_months,
+            self.transaction.account_id
+        )
+    }
+}
+
+/// LoanRepayment 偿还贷款
+#[derive(Serialize, Deserialize)]
+pub struct LoanRepayment {
+    pub(crate) transaction: Transaction,
+    loan_id: usize,
+    amount: Money,
+}
+
+impl LoanRepayment {
+    pub fn new(account_id: usize, teller_id: usize, loan_id: usize, amount: Money) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            loan_id,
+            amount,
+        }
+    }
+}
+
+impl TransactionDescription for LoanRepayment {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::LoanRepayment
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::LoanRepayment(self)
+    }
+
+    fn get_cash_effect(&self) -> Option<Money> {
+        Some(self.amount)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} received repayment of {} for loan {} from account {}",
+            self.transaction.teller_id, self.amount, self.loan_id, self.transaction.account_id
+        )
+    }
+}
+
+/// IssueCreditCard 办理信用卡
+#[derive(Serialize, Deserialize)]
+pub struct IssueCreditCard {
+    transaction: Transaction,
+    card_id: usize,
+    credit_limit: Money,
+}
+
+impl IssueCreditCard {
+    pub fn new(account_id: usize, teller_id: usize, card_id: usize, credit_limit: Money) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            card_id,
+            credit_limit,
+        }
+    }
+}
+
+impl TransactionDescription for IssueCreditCard {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::IssueCreditCard
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::IssueCreditCard(self)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} issued credit card {} with limit {} to account {}",
+            self.transaction.teller_id,
+            self.card_id,
+            self.credit_limit,
+            self.transaction.account_id
+        )
+    }
+}
+
+/// CardChargeKind 信用卡记账类型
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CardChargeKind {
+    // 消费, 记录商户名称
+    Purchase(String),
+    // 滞纳金
+    LateFee,
+    // 循环利息
+    Interest,
+}
+
+/// CreditCardCharge 信用卡消费、滞纳金和利息
+#[derive(Serialize, Deserialize)]
+pub struct CreditCardCharge {
+    transaction: Transaction,
+    card_id: usize,
+    kind: CardChargeKind,
+    amount: Money,
+}
+
+impl CreditCardCharge {
+    pub fn new(account_id: usize, card_id: usize, kind: CardChargeKind, amount: Money) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, SYSTEM_TELLER_ID),
+            card_id,
+            kind,
+            amount,
+        }
+    }
+}
+
+impl TransactionDescription for CreditCardCharge {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::CreditCardCharge
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::CreditCardCharge(self)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        match &self.kind {
+            CardChargeKind::Purchase(merchant) => format!(
+                "Credit card {} of account {} charged {} at {}",
+                self.card_id, self.transaction.account_id, self.amount, merchant
+            ),
+            CardChargeKind::LateFee => format!(
+                "Credit card {} of account {} charged late fee of {}",
+                self.card_id, self.transaction.account_id, self.amount
+            ),
+            CardChargeKind::Interest => format!(
+                "Credit card {} of account {} charged interest of {}",
+                self.card_id, self.transaction.account_id, self.amount
+            ),
+        }
+    }
+}
+
+/// CreditCardPayment 信用卡还款
+#[derive(Serialize, Deserialize)]
+pub struct CreditCardPayment {
+    pub(crate) transaction: Transaction,
+    card_id: usize,
+    amount: Money,
+}
+
+impl CreditCardPayment {
+    pub fn new(account_id: usize, teller_id: usize, card_id: usize, amount: Money) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            card_id,
+            amount,
+        }
+    }
+}
+
+impl TransactionDescription for CreditCardPayment {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::CreditCardPayment
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::CreditCardPayment(self)
+    }
+
+    fn get_cash_effect(&self) -> Option<Money> {
+        Some(self.amount)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} received payment of {} for credit card {}",
+            self.transaction.teller_id, self.amount, self.card_id
+        )
+    }
+}
+
+/// CashDirection 现金调拨方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CashDirection {
+    ToHeadquarters,
+    FromHeadquarters,
+}
+
+/// CashTransfer 分行和总部之间的现金调拨
+#[derive(Serialize, Deserialize)]
+pub struct CashTransfer {
+    transaction: Transaction,
+    branch_id: usize,
+    direction: CashDirection,
+    amount: Money,
+}
+
+impl CashTransfer {
+    pub fn new(branch_id: usize, direction: CashDirection, amount: Money) -> Self {
+        Self {
+            transaction: Transaction::new(BANK_ACCOUNT_ID, SYSTEM_TELLER_ID),
+            branch_id,
+            direction,
+            amount,
+        }
+    }
+
+    pub fn get_direction(&self) -> CashDirection {
+        self.direction
+    }
+}
+
+impl TransactionDescription for CashTransfer {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::CashTransfer
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::CashTransfer(self)
+    }
+
+    fn get_cash_effect(&self) -> Option<Money> {
+        match self.direction {
+            CashDirection::ToHeadquarters => Some(-self.amount),
+            CashDirection::FromHeadquarters => Some(self.amount),
+        }
+    }
+
+    fn get_transaction_description(&self) -> String {
+        match self.direction {
+            CashDirection::ToHeadquarters => format!(
+                "Branch {} sent {} to headquarters",
+                self.branch_id, self.amount
+            ),
+            CashDirection::FromHeadquarters => format!(
+                "Headquarters sent {} to branch {}",
+                self.amount, self.branch_id
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OpenAccount {
+    transaction: Transaction,
+}
+
+impl OpenAccount {
+    pub fn new(account_id: usize, teller_id: usize) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+        }
+    }
+}
+
+impl TransactionDescription for OpenAccount {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::OpenAccount
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::OpenAccount(self)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} opened account {} ",
+            self.transaction.teller_id, self.transaction.account_id
+        )
+    }
+}
+
+/// AccountStatusChange 账户状态变更(冻结、解冻、转为睡眠户、激活、销户)
+#[derive(Serialize, Deserialize)]
+pub struct AccountStatusChange {
+    transaction: Transaction,
+    from: AccountStatus,
+    to: AccountStatus,
+    // 变更原因, 例如冻结的依据、销户时支付的余额
+    reason: String,
+}
+
+impl AccountStatusChange {
+    pub fn new(
+        account_id: usize,
+        teller_id: usize,
+        from: AccountStatus,
+        to: AccountStatus,
+        reason: String,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            from,
+            to,
+            reason,
+        }
+    }
+
+    pub fn get_from(&self) -> AccountStatus {
+        self.from
+    }
+
+    pub fn get_to(&self) -> AccountStatus {
+        self.to
+    }
+}
+
+impl TransactionDescription for AccountStatusChange {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::AccountStatusChange
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::AccountStatusChange(self)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        // 系统批处理和后台操作没有柜员
+        let actor = if self.transaction.teller_id == SYSTEM_TELLER_ID {
+            "Bank".to_string()
+        } else {
+            format!("Teller {}", self.transaction.teller_id)
+        };
+        let action = match (self.from, self.to) {
+            (_, AccountStatus::Frozen) => "froze",
+            (AccountStatus::Frozen, AccountStatus::Active) => "unfroze",
+            (_, AccountStatus::Active) => "reactivated",
+            (_, AccountStatus::Dormant) => "marked dormant",
+            (_, AccountStatus::Closed) => "closed",
+        };
+        format!(
+            "{} {} account {}: {}",
+            actor, action, self.transaction.account_id, self.reason
+        )
+    }
+}
+
+/// Reversal 冲正, 按原交易的反方向记账, 原交易记录保持不变
+#[derive(Serialize, Deserialize)]
+pub struct Reversal {
+    // transaction.account_id 为原交易的账户(转账为转出账户), teller_id 为授权的主管
+    pub(crate) transaction: Transaction,
+    pub(crate) original_id: u64,
+    pub(crate) original_kind: TransactionKind,
+    // 原转账的转入账户
+    pub(crate) counterparty_account_id: Option<usize>,
+    pub(crate) amount: Money,
+    pub(crate) reason: String,
+}
+
+impl Reversal {
+    pub fn new(
+        original_id: u64,
+        original_kind: TransactionKind,
+        account_id: usize,
+        counterparty_account_id: Option<usize>,
+        supervisor_id: usize,
+        amount: Money,
+        reason: String,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, supervisor_id),
+            original_id,
+            original_kind,
+            counterparty_account_id,
+            amount,
+            reason,
+        }
+    }
+
+    pub fn get_original_id(&self) -> u64 {
+        self.original_id
+    }
+}
+
+impl TransactionDescription for Reversal {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::Reversal
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::Reversal(self)
+    }
+
+    fn involves_account(&self, account_id: usize) -> bool {
+        account_id == self.transaction.account_id
+            || Some(account_id) == self.counterparty_account_id
+    }
+
+    fn get_counterparty_account_id(&self) -> Option<usize> {
+        self.counterparty_account_id
+    }
+
+    fn get_reversed_id(&self) -> Option<u64> {
+        Some(self.original_id)
+    }
+
+    // 与原交易的影响相反
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        let effect = match self.original_kind {
+            TransactionKind::Deposit => -self.amount,
+            _ => self.amount,
+        };
+        if account_id == self.transaction.account_id {
+            Some(effect)
+        } else if Some(account_id) == self.counterparty_account_id {
+            Some(-effect)
+        } else {
+            None
+        }
+    }
+
+    fn get_cash_effect(&self) -> Option<Money> {
+        match self.original_kind {
+            TransactionKind::Deposit => Some(-self.amount),
+            TransactionKind::Withdrawal => Some(self.amount),
+            _ => None,
+        }
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Supervisor {} reversed transaction #{} ({:?} of {} on account {}): {}",
+            self.transaction.teller_id,
+            self.original_id,
+            self.original_kind,
+            self.amount,
+            self.transaction.account_id,
+            self.reason
+        )
+    }
+}
+
+/// OpenTermDeposit 从客户账户转出资金开立定期存款
+#[derive(Serialize, Deserialize)]
+pub struct OpenTermDeposit {
+    transaction: Transaction,
+    pub(crate) deposit_id: usize,
+    principal: Money,
+    product: TermDepositProduct,
+}
+
+impl OpenTermDeposit {
+    pub fn new(
+        account_id: usize,
+        teller_id: usize,
+        deposit_id: usize,
+        principal: Money,
+        product: TermDepositProduct,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            deposit_id,
+            principal,
+            product,
+        }
+    }
+}
+
+impl TransactionDescription for OpenTermDeposit {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::TermDepositOpening
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::OpenTermDeposit(self)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        (account_id == self.transaction.account_id).then_some(-self.principal)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} opened term deposit {} of {} at {}% for {} months from account {}",
+            self.transaction.teller_id,
+            self.deposit_id,
+            self.principal,
+            self.product.annual_rate * Decimal::from(100),
+            self.product.term_months,
+            self.transaction.account_id
+        )
+    }
+}
+
+/// TermDepositPayout 定期存款到期或提前支取, 本金和利息转入客户账户
+#[derive(Serialize, Deserialize)]
+pub struct TermDepositPayout {
+    transaction: Transaction,
+    pub(crate) deposit_id: usize,
+    principal: Money,
+    // 提前支取时为扣除罚金后的利息
+    interest: Money,
+    early: bool,
+}
+
+impl TermDepositPayout {
+    pub fn new(
+        account_id: usize,
+        teller_id: usize,
+        deposit_id: usize,
+        principal: Money,
+        interest: Money,
+        early: bool,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            deposit_id,
+            principal,
+            interest,
+            early,
+        }
+    }
+}
+
+impl TransactionDescription for TermDepositPayout {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::TermDepositPayout
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::TermDepositPayout(self)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        if account_id != self.transaction.account_id {
+            return None;
+        }
+        self.principal.checked_add(self.interest).ok()
+    }
+
+    fn get_transaction_description(&self) -> String {
+        if self.early {
+            format!(
+                "Teller {} paid out term deposit {} early: {} principal and {} interest after penalty to account {}",
+                self.transaction.teller_id,
+                self.deposit_id,
+                self.principal,
+                self.interest,
+                self.transaction.account_id
+            )
+        } else {
+            format!(
+                "Term deposit {} matured: {} principal and {} interest paid to account {}",
+                self.deposit_id, self.principal, self.interest, self.transaction.account_id
+            )
+        }
+    }
+}
+
+/// FundTrade 申购或赎回基金, 按交易日最近的净值成交
+#[derive(Serialize, Deserialize)]
+pub struct FundTrade {
+    transaction: Transaction,
+    pub(crate) fund_code: String,
+    pub(crate) units: Decimal,
+    nav: Decimal,
+    amount: Money,
+    pub(crate) redemption: bool,
+}
+
+impl FundTrade {
+    pub fn new(
+        account_id: usize,
+        teller_id: usize,
+        fund_code: String,
+        units: Decimal,
+        nav: Decimal,
+        amount: Money,
+        redemption: bool,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            fund_code,
+            units,
+            nav,
+            amount,
+            redemption,
+        }
+    }
+}
+
+impl TransactionDescription for FundTrade {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        if self.redemption {
+            TransactionKind::FundRedemption
+        } else {
+            TransactionKind::FundPurchase
+        }
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::FundTrade(self)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        if account_id != self.transaction.account_id {
+            None
+        } else if self.redemption {
+            Some(self.amount)
+        } else {
+            Some(-self.amount)
+        }
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} {} {} units of fund {} at NAV {} for {} {} account {}",
+            self.transaction.teller_id,
+            if self.redemption {
+                "redeemed"
+            } else {
+                "bought"
+            },
+            self.units,
+            self.fund_code,
+            self.nav,
+            self.amount,
+            if self.redemption { "to" } else { "from" },
+            self.transaction.account_id
+        )
+    }
+}
+
+/// FeeCharge 按收费标准从客户账户扣收的费用, 每种费用对应一种交易类型
+#[derive(Serialize, Deserialize)]
+pub struct FeeCharge {
+    pub(crate) transaction: Transaction,
+    kind: FeeKind,
+    pub(crate) amount: Money,
+    // 引起收费的交易, 月度账户管理费没有
+    pub(crate) related_transaction_id: Option<u64>,
+}
+
+impl FeeCharge {
+    pub fn new(
+        account_id: usize,
+        teller_id: usize,
+        kind: FeeKind,
+        amount: Money,
+        related_transaction_id: Option<u64>,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(account_id, teller_id),
+            kind,
+            amount,
+            related_transaction_id,
+        }
+    }
+
+    pub fn get_fee_kind(&self) -> FeeKind {
+        self.kind
+    }
+}
+
+impl TransactionDescription for FeeCharge {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        match self.kind {
+            FeeKind::MonthlyMaintenance => TransactionKind::MaintenanceFee,
+            FeeKind::ExcessWithdrawal => TransactionKind::WithdrawalFee,
+            FeeKind::CrossBranch => TransactionKind::CrossBranchFee,
+            FeeKind::Overdraft => TransactionKind::OverdraftFee,
+        }
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::FeeCharge(self)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        (account_id == self.transaction.account_id).then_some(-self.amount)
+    }
+
+    fn get_transaction_description(&self) -> String {
+        let actor = if self.transaction.teller_id == SYSTEM_TELLER_ID {
+            "Bank".to_string()
+        } else {
+            format!("Teller {}", self.transaction.teller_id)
+        };
+        let mut description = format!(
+            "{} charged {} of {} to account {}",
+            actor, self.kind, self.amount, self.transaction.account_id
+        );
+        if let Some(related) = self.related_transaction_id {
+            description.push_str(&format!(" for transaction #{}", related));
+        }
+        description
+    }
+}
+
+/// CurrencyExchange 在两个不同币种的账户之间兑换, 按牌价买入卖出, 与中间价的差额为银行的汇兑收益
+#[derive(Serialize, Deserialize)]
+pub struct CurrencyExchange {
+    // transaction.account_id 为卖出外币的账户
+    transaction: Transaction,
+    // 买入币种的账户
+    to_account_id: usize,
+    sold: Money,
+    bought: Money,
+    // 记账币种的汇兑收益
+    margin: Money,
+}
+
+impl CurrencyExchange {
+    pub fn new(
+        from_account_id: usize,
+        to_account_id: usize,
+        teller_id: usize,
+        sold: Money,
+        bought: Money,
+        margin: Money,
+    ) -> Self {
+        Self {
+            transaction: Transaction::new(from_account_id, teller_id),
+            to_account_id,
+            sold,
+            bought,
+            margin,
+        }
+    }
+}
+
+impl TransactionDescription for CurrencyExchange {
+    fn get_transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    fn get_transaction_mut(&mut self) -> &mut Transaction {
+        &mut self.transaction
+    }
+
+    fn get_kind(&self) -> TransactionKind {
+        TransactionKind::CurrencyExchange
+    }
+
+    fn stored(&self) -> StoredTransactionRef<'_> {
+        StoredTransactionRef::CurrencyExchange(self)
+    }
+
+    fn involves_account(&self, account_id: usize) -> bool {
+        account_id == self.transaction.account_id || account_id == self.to_account_id
+    }
+
+    fn get_counterparty_account_id(&self) -> Option<usize> {
+        Some(self.to_account_id)
+    }
+
+    fn get_account_effect(&self, account_id: usize) -> Option<Money> {
+        if account_id == self.transaction.account_id {
+            Some(-self.sold)
+        } else if account_id == self.to_account_id {
+            Some(self.bought)
+        } else {
+            None
+        }
+    }
+
+    fn get_transaction_description(&self) -> String {
+        format!(
+            "Teller {} exchanged {} from account {} for {} to account {} (FX margin {})",
+            self.transaction.teller_id,
+            self.sold,
+            self.transaction.account_id,
+            self.bought,
+            self.to_account_id,
+            self.margin
+        )
+    }
+}
+
+/// TransactionQuery 交易查询条件, 所有条件同时满足的交易才会返回
+#[derive(Debug, Clone, Default)]
+pub struct TransactionQuery {
+    account_id: Option<usize>,
+    teller_id: Option<usize>,
+    branch_id: Option<usize>,
+    kinds: Vec<TransactionKind>,
+    // 时间区间 [start, end)
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    // 分页
+    pub(crate) offset: usize,
+    pub(crate) limit: Option<usize>,
+}
+
+impl TransactionQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn account(mut self, account_id: usize) -> Self {
+        self.account_id = Some(account_id);
+        self
+    }
+
+    pub fn teller(mut self, teller_id: usize) -> Self {
+        self.teller_id = Some(teller_id);
+        self
+    }
+
+    pub fn branch(mut self, branch_id: usize) -> Self {
+        self.branch_id = Some(branch_id);
+        self
+    }
+
+    // 可以多次调用, 匹配其中任意一种交易类型
+    pub fn kind(mut self, kind: TransactionKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    pub fn since(mut self, start: DateTime<Utc>) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    pub fn until(mut self, end: DateTime<Utc>) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    // 第 page 页(从0开始), 每页 page_size 条
+    pub fn page(mut self, page: usize, page_size: usize) -> Self {
+        self.offset = page * page_size;
+        self.limit = Some(page_size);
+        self
+    }
+
+    pub fn matches(&self, ts: &dyn TransactionDescription) -> bool {
+        let transaction = ts.get_transaction();
+        self.account_id.is_none_or(|c| ts.involves_account(c))
+            && self.teller_id.is_none_or(|t| transaction.teller_id == t)
+            && self
+                .branch_id
+                .is_none_or(|b| transaction.branch_id == Some(b))
+            && (self.kinds.is_empty() || self.kinds.contains(&ts.get_kind()))
+            && self
+                .start
+                .is_none_or(|start| transaction.timestamp >= start)
+            && self.end.is_none_or(|end| transaction.timestamp < end)
+    }
+}
+
+/// TransactionPage 一页查询结果
+pub struct TransactionPage<'a> {
+    pub transactions: Vec<&'a dyn TransactionDescription>,
+    // 满足条件的交易总数(不分页)
+    pub total: usize,
+}
+
+/// StatementLine 对账单中的一笔交易
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementLine {
+    pub transaction_id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub description: String,
+    // 对账户余额的影响(正数为存入)
+    pub amount: Money,
+    // 本笔交易后的余额
+    pub balance: Money,
+}
+
+/// AccountStatement 账户对账单
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountStatement {
+    pub account_id: usize,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub opening_balance: Money,
+    pub closing_balance: Money,
+    pub lines: Vec<StatementLine>,
+    // 账户当前的投资持仓, 按期末日期计算利息和市值
+    pub positions: Vec<InvestmentPosition>,
+}
+
+/// InvestmentPosition 对账单上的投资持仓
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvestmentPosition {
+    TermDeposit {
+        deposit_id: usize,
+        principal: Money,
+        annual_rate: Decimal,
+        maturity_date: NaiveDate,
+        // 截至期末已经产生的利息
+        accrued_interest: Money,
+    },
+    Fund {
+        fund_code: String,
+        units: Decimal,
+        nav: Decimal,
+        nav_date: NaiveDate,
+        value: Money,
+    },
+}